@@ -3,6 +3,19 @@ use std::env;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+// GDCM built as shared libraries with the `dynamic` feature
+fn shared_libs() -> &'static str {
+    if cfg!(feature = "dynamic") { "ON" } else { "OFF" }
+}
+
+// Shared GDCM libs used by the wrapper, they load their other codecs (jpeg...) on their own.
+// CharLS and OpenJPEG are called directly for the JPEG-LS and JPEG 2000 options.
+fn link_gdcm_shared() {
+    for lib in &["gdcmMSFF", "gdcmMEXD", "gdcmIOD", "gdcmDSED", "gdcmDICT", "gdcmCommon", "gdcmcharls", "gdcmopenjp2"] {
+        println!("cargo:rustc-link-lib=dylib={}", lib);
+    }
+}
+
 #[cfg(target_os = "linux")]
 fn build() {
     // Run GDCM cmake
@@ -13,6 +26,8 @@ fn build() {
                  .define("GDCM_BUILD_EXAMPLES", "OFF")
                  .define("GDCM_BUILD_DOCBOOK_MANPAGES", "OFF")
                  .define("GDCM_SUPPORT_BROKEN_IMPLEMENTATION", "ON")
+                 .define("GDCM_ALWAYS_TRACE_MACRO", "ON")
+                 .define("GDCM_BUILD_SHARED_LIBS", shared_libs())
                  .cflag("-fPIC")
                  .build_arg("-j8")
                  .build();
@@ -39,21 +54,25 @@ fn build() {
     println!("cargo:rustc-link-lib=stdc++");
 
     // GDCM libs
-    println!("cargo:rustc-link-lib=static=gdcmMSFF");
-    println!("cargo:rustc-link-lib=static=gdcmcharls");
-    println!("cargo:rustc-link-lib=static=gdcmCommon");
-    println!("cargo:rustc-link-lib=static=gdcmDICT");
-    println!("cargo:rustc-link-lib=static=gdcmDSED");
-    println!("cargo:rustc-link-lib=static=gdcmIOD");
-    println!("cargo:rustc-link-lib=static=gdcmexpat");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg12");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg16");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg8");
-    println!("cargo:rustc-link-lib=static=gdcmopenjp2");
-    println!("cargo:rustc-link-lib=static=gdcmuuid");
-    println!("cargo:rustc-link-lib=static=gdcmMEXD");
-    println!("cargo:rustc-link-lib=static=gdcmzlib");
-    println!("cargo:rustc-link-lib=static=socketxx");
+    if cfg!(feature = "dynamic") {
+        link_gdcm_shared();
+    } else {
+        println!("cargo:rustc-link-lib=static=gdcmMSFF");
+        println!("cargo:rustc-link-lib=static=gdcmcharls");
+        println!("cargo:rustc-link-lib=static=gdcmCommon");
+        println!("cargo:rustc-link-lib=static=gdcmDICT");
+        println!("cargo:rustc-link-lib=static=gdcmDSED");
+        println!("cargo:rustc-link-lib=static=gdcmIOD");
+        println!("cargo:rustc-link-lib=static=gdcmexpat");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg12");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg16");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg8");
+        println!("cargo:rustc-link-lib=static=gdcmopenjp2");
+        println!("cargo:rustc-link-lib=static=gdcmuuid");
+        println!("cargo:rustc-link-lib=static=gdcmMEXD");
+        println!("cargo:rustc-link-lib=static=gdcmzlib");
+        println!("cargo:rustc-link-lib=static=socketxx");
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -66,6 +85,8 @@ fn build() {
                  .define("GDCM_BUILD_EXAMPLES", "OFF")
                  .define("GDCM_BUILD_DOCBOOK_MANPAGES", "OFF")
                  .define("GDCM_SUPPORT_BROKEN_IMPLEMENTATION", "ON")
+                 .define("GDCM_ALWAYS_TRACE_MACRO", "ON")
+                 .define("GDCM_BUILD_SHARED_LIBS", shared_libs())
                  .cflag("-fPIC")
                  .build_arg("-j8")
                  .build();
@@ -91,23 +112,28 @@ fn build() {
     // Set libs
     println!("cargo:rustc-link-lib=static=gdcm_conv");
     println!("cargo:rustc-link-lib=c++");
+    println!("cargo:rustc-link-lib=iconv");
 
     // GDCM libs
-    println!("cargo:rustc-link-lib=static=gdcmMSFF");
-    println!("cargo:rustc-link-lib=static=gdcmcharls");
-    println!("cargo:rustc-link-lib=static=gdcmCommon");
-    println!("cargo:rustc-link-lib=static=gdcmDICT");
-    println!("cargo:rustc-link-lib=static=gdcmDSED");
-    println!("cargo:rustc-link-lib=static=gdcmIOD");
-    println!("cargo:rustc-link-lib=static=gdcmexpat");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg12");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg16");
-    println!("cargo:rustc-link-lib=static=gdcmjpeg8");
-    println!("cargo:rustc-link-lib=static=gdcmopenjp2");
-    println!("cargo:rustc-link-lib=static=gdcmuuid");
-    println!("cargo:rustc-link-lib=static=gdcmMEXD");
-    println!("cargo:rustc-link-lib=static=gdcmzlib");
-    println!("cargo:rustc-link-lib=static=socketxx");
+    if cfg!(feature = "dynamic") {
+        link_gdcm_shared();
+    } else {
+        println!("cargo:rustc-link-lib=static=gdcmMSFF");
+        println!("cargo:rustc-link-lib=static=gdcmcharls");
+        println!("cargo:rustc-link-lib=static=gdcmCommon");
+        println!("cargo:rustc-link-lib=static=gdcmDICT");
+        println!("cargo:rustc-link-lib=static=gdcmDSED");
+        println!("cargo:rustc-link-lib=static=gdcmIOD");
+        println!("cargo:rustc-link-lib=static=gdcmexpat");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg12");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg16");
+        println!("cargo:rustc-link-lib=static=gdcmjpeg8");
+        println!("cargo:rustc-link-lib=static=gdcmopenjp2");
+        println!("cargo:rustc-link-lib=static=gdcmuuid");
+        println!("cargo:rustc-link-lib=static=gdcmMEXD");
+        println!("cargo:rustc-link-lib=static=gdcmzlib");
+        println!("cargo:rustc-link-lib=static=socketxx");
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -121,6 +147,8 @@ fn build() {
                  .define("GDCM_BUILD_EXAMPLES", "OFF")
                  .define("GDCM_BUILD_DOCBOOK_MANPAGES", "OFF")
                  .define("GDCM_SUPPORT_BROKEN_IMPLEMENTATION", "ON")
+                 .define("GDCM_ALWAYS_TRACE_MACRO", "ON")
+                 .define("GDCM_BUILD_SHARED_LIBS", shared_libs())
                  .cflag("/MP8")
                  .cxxflag("/MP8")
                  .build();
@@ -146,21 +174,87 @@ fn build() {
     println!("cargo:rustc-link-lib=ws2_32");
 
     // GDCM libs
-    println!("cargo:rustc-link-lib=gdcmMSFF");
-    println!("cargo:rustc-link-lib=gdcmcharls");
-    println!("cargo:rustc-link-lib=gdcmCommon");
-    println!("cargo:rustc-link-lib=gdcmDICT");
-    println!("cargo:rustc-link-lib=gdcmDSED");
-    println!("cargo:rustc-link-lib=gdcmIOD");
-    println!("cargo:rustc-link-lib=gdcmexpat");
-    println!("cargo:rustc-link-lib=gdcmgetopt");
-    println!("cargo:rustc-link-lib=gdcmjpeg12");
-    println!("cargo:rustc-link-lib=gdcmjpeg16");
-    println!("cargo:rustc-link-lib=gdcmjpeg8");
-    println!("cargo:rustc-link-lib=gdcmopenjp2");
-    println!("cargo:rustc-link-lib=gdcmMEXD");
-    println!("cargo:rustc-link-lib=gdcmzlib");
-    println!("cargo:rustc-link-lib=socketxx");
+    if cfg!(feature = "dynamic") {
+        // DLLs next to the import libraries, on the PATH of cargo run and cargo test
+        println!("cargo:rustc-link-search={}", dst.join("bin").display());
+        link_gdcm_shared();
+    } else {
+        println!("cargo:rustc-link-lib=gdcmMSFF");
+        println!("cargo:rustc-link-lib=gdcmcharls");
+        println!("cargo:rustc-link-lib=gdcmCommon");
+        println!("cargo:rustc-link-lib=gdcmDICT");
+        println!("cargo:rustc-link-lib=gdcmDSED");
+        println!("cargo:rustc-link-lib=gdcmIOD");
+        println!("cargo:rustc-link-lib=gdcmexpat");
+        println!("cargo:rustc-link-lib=gdcmgetopt");
+        println!("cargo:rustc-link-lib=gdcmjpeg12");
+        println!("cargo:rustc-link-lib=gdcmjpeg16");
+        println!("cargo:rustc-link-lib=gdcmjpeg8");
+        println!("cargo:rustc-link-lib=gdcmopenjp2");
+        println!("cargo:rustc-link-lib=gdcmMEXD");
+        println!("cargo:rustc-link-lib=gdcmzlib");
+        println!("cargo:rustc-link-lib=socketxx");
+    }
+}
+
+// First GDCM release with the HTJ2K transfer syntaxes used by convert.cc
+#[cfg(feature = "system-gdcm")]
+const SYSTEM_GDCM_VERSION: &str = "3.0.23";
+
+// Link against a GDCM discovered with pkg-config, false when it is not found or older than
+// SYSTEM_GDCM_VERSION.
+#[cfg(feature = "system-gdcm")]
+fn build_system() -> bool {
+    let mut library = None;
+    for name in &["gdcm", "gdcm-3.1", "gdcm-3.0"] {
+        let probe = pkg_config::Config::new()
+            .atleast_version(SYSTEM_GDCM_VERSION)
+            .cargo_metadata(false)
+            .probe(name);
+        if let Ok(t) = probe {
+            library = Some(t);
+            break;
+        }
+    }
+    let library = match library {
+        Some(t) => t,
+        None => return false,
+    };
+
+    // Create library against the system headers
+    let mut build = cc::Build::new();
+    build.file("convert.cc")
+         .cpp(true)
+         .cargo_metadata(false)
+         .warnings(false);
+    if !cfg!(target_os = "windows") {
+        build.flag("-fPIC")
+             .flag("-std=c++11");
+    }
+    for include_dir in &library.include_paths {
+        build.include(include_dir);
+    }
+    build.compile("gdcm_conv");
+
+    // Set libs paths
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rustc-link-search={}", out_path.display());
+
+    // Set libs, GDCM after the wrapper that uses it
+    println!("cargo:rustc-link-lib=static=gdcm_conv");
+    for link_path in &library.link_paths {
+        println!("cargo:rustc-link-search={}", link_path.display());
+    }
+    for lib in &library.libs {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+    if cfg!(target_os = "macos") {
+        println!("cargo:rustc-link-lib=c++");
+    } else if !cfg!(target_os = "windows") {
+        println!("cargo:rustc-link-lib=stdc++");
+    }
+
+    true
 }
 
 fn main() {
@@ -170,6 +264,18 @@ fn main() {
     // Unset DESTDIR envar to avoid others libs destinations
     env::remove_var("DESTDIR");
 
+    // System GDCM, falls back to the bundled build when not found
+    #[cfg(feature = "system-gdcm")]
+    {
+        if build_system() {
+            return;
+        }
+        println!(
+            "cargo:warning=system GDCM {} or newer not found with pkg-config, building the bundled one",
+            SYSTEM_GDCM_VERSION
+        );
+    }
+
     // Update GIT
     if !Path::new("GDCM/.git").exists() {
         let _ = Command::new("git")
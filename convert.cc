@@ -7,17 +7,45 @@
 #include "gdcmImageChangeTransferSyntax.h"
 #include "gdcmImageChangePhotometricInterpretation.h"
 #include "gdcmImageApplyLookupTable.h"
+#include "gdcmImageChangePlanarConfiguration.h"
 #include "gdcmFileExplicitFilter.h"
 #include "gdcmFileMetaInformation.h"
 #include "gdcmJPEG2000Codec.h"
 #include "gdcmJPEGLSCodec.h"
 #include "gdcmJPEGCodec.h"
+#include "gdcmRLECodec.h"
+#include "gdcmReader.h"
+#include "gdcmAnonymizer.h"
+#include "gdcmUIDGenerator.h"
+#include "gdcmGlobal.h"
+#include "gdcmCommand.h"
+#include "gdcmProgressEvent.h"
+#include "gdcmDicts.h"
+#include "gdcmSequenceOfItems.h"
+#include "gdcmSequenceOfFragments.h"
+#include "gdcmVersion.h"
+#include "gdcmImageRegionReader.h"
+#include "gdcmBoxRegion.h"
+#include "gdcmImageHelper.h"
+#include "gdcmTrace.h"
+#include "gdcmStringFilter.h"
+#include "gdcm_charls.h"
+#include "gdcm_openjpeg.h"
 
 #include <iostream>
 #include <istream>
 #include <fstream>
 #include <streambuf>
 #include <string>
+#include <vector>
+#include <cstring>
+#include <cmath>
+#include <cstdlib>
+#include <algorithm>
+
+#ifndef _WIN32
+#include <iconv.h>
+#endif
 
 #include "wrapper.h"
 
@@ -25,7 +53,7 @@ using namespace std;
 
 namespace gdcm
 {
-    static bool derives(File &file, const Pixmap &compressed_image)
+    static bool derives(File &file, const Pixmap &compressed_image, const char *method, const char *description)
     {
         DataSet &ds = file.GetDataSet();
 
@@ -58,10 +86,9 @@ namespace gdcm
         // CID 7203 Image Derivation
         // { "DCM",113040,"Lossy Compression" },
         fd.SetDerivationCodeSequenceCodeValue(113040);
-        fd.SetDerivationDescription("lossy conversion");
+        fd.SetDerivationDescription(description);
         if (!fd.Derive())
         {
-            std::cerr << "Sorry could not derive using input info" << std::endl;
             return false;
         }
 
@@ -85,335 +112,4567 @@ namespace gdcm
         ds.Replace(at2.GetAsDataElement());
 
         Attribute<0x0028, 0x2114> at3;
+        at3.SetValue(method);
+        ds.Replace(at3.GetAsDataElement());
 
         return true;
     }
 } // namespace gdcm
 
-struct ProcResp
+// Check the cancellation callback.
+static bool is_cancelled(const OptionsStruct *options)
 {
-    unsigned int status;
-    std::string image;
-};
+    return options != NULL && options->cancelled != NULL && options->cancelled(options->cancel_data);
+}
 
-// Modify Photometric Interpretation from incoming stream.
-ProcResp change_photometric(
-    int photometric_interpretation,
-    std::string &src)
+// Forward GDCM progress events as frame numbers to the options callback.
+class ProgressObserver : public gdcm::Command
 {
-    struct ProcResp proc_resp;
-
-    std::istringstream dicomInput(src);
-    std::ostringstream dicomOutput;
-
-    gdcm::PixmapReader reader;
-    reader.SetStream(dicomInput);
+public:
+    const OptionsStruct *options;
+    unsigned int total;
 
-    if (!reader.Read())
+    void Execute(gdcm::Subject *caller, const gdcm::Event &event)
     {
-        proc_resp.status = 0x01;
-        return proc_resp;
+        Execute(const_cast<const gdcm::Subject *>(caller), event);
     }
 
-    gdcm::Pixmap &image = reader.GetPixmap();
-
-    gdcm::PixmapWriter writer;
-    writer.SetStream(dicomOutput);
-    writer.SetFile(reader.GetFile());
-
-    if(image.GetPhotometricInterpretation() != gdcm::PhotometricInterpretation::PALETTE_COLOR) {
-        gdcm::ImageChangePhotometricInterpretation change;
-        change.SetInput(image);
-
-        switch (photometric_interpretation)
-        {
-        case 1:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::MONOCHROME1);
-            break;
-        case 2:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::MONOCHROME2);
-            break;
-        case 3:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::PALETTE_COLOR);
-            break;
-        case 4:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::RGB);
-            break;
-        case 5:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::HSV);
-            break;
-        case 6:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::ARGB);
-            break;
-        case 7:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::CMYK);
-            break;
-        case 8:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_FULL);
-            break;
-        case 9:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_FULL_422);
-            break;
-        case 10:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_PARTIAL_422);
-            break;
-        case 11:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_PARTIAL_420);
-            break;
-        case 12:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_ICT);
-            break;
-        case 13:
-            change.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_RCT);
-            break;
-        default:
-            proc_resp.status = 0x02;
-            return proc_resp;
-        }
-
-        if (!change.Change())
-        {
-            proc_resp.status = 0x03;
-            return proc_resp;
-        }
-
-        writer.SetPixmap(change.PixmapToPixmapFilter::GetOutput());
-    } else {
-        gdcm::ImageApplyLookupTable change;
-        change.SetInput(image);
-
-        if (!change.Apply())
-        {
-            proc_resp.status = 0x04;
-            return proc_resp;
-        }
-
-        writer.SetPixmap(change.PixmapToPixmapFilter::GetOutput());
+    void Execute(const gdcm::Subject *, const gdcm::Event &event)
+    {
+        const gdcm::ProgressEvent *pe = dynamic_cast<const gdcm::ProgressEvent *>(&event);
+        if (pe != NULL)
+            Report(static_cast<unsigned int>(pe->GetProgress() * total));
     }
 
-    if (!writer.Write())
+    void Report(unsigned int current)
     {
-        proc_resp.status = 0x05;
-        return proc_resp;
+        if (options != NULL && options->progress != NULL)
+            options->progress(options->progress_data, current, total);
     }
+};
 
-    proc_resp.status = 0x00;
-    proc_resp.image = dicomOutput.str();
-    return proc_resp;
-}
+// GDCM writes warnings to a global stream, messages go to the warning callback of the conversion
+// running on the same thread and are dropped otherwise. A message ends with an empty line.
+static thread_local const OptionsStruct *trace_options = NULL;
+static thread_local std::string trace_message;
 
-ProcResp change_transfer(
-    int transfer_syntax,
-    char is_lossy,
-    int quality1,
-    int quality2,
-    int quality3,
-    char irreversible,
-    int allow_error,
-    std::string &src)
+static void flush_trace_message()
 {
-    struct ProcResp proc_resp;
+    trace_message.erase(trace_message.find_last_not_of('\n') + 1);
+    if (trace_message.empty())
+        return;
 
-    std::istringstream dicomInput(src);
-    std::ostringstream dicomOutput;
+    if (trace_options != NULL && trace_options->warning != NULL)
+        trace_options->warning(trace_options->warning_data, trace_message.c_str());
+    trace_message.clear();
+}
 
-    gdcm::FileMetaInformation::SetImplementationVersionName("Idria Software");
-    gdcm::FileMetaInformation::SetSourceApplicationEntityTitle("PROTEUS");
+class TraceBuffer : public std::streambuf
+{
+protected:
+    int overflow(int c)
+    {
+        if (c == traits_type::eof())
+            return traits_type::not_eof(c);
 
-    bool is_jpeg = false;
-    bool is_jpegls = false;
-    bool is_j2k = false;
-    bool derive = false;
+        trace_message += static_cast<char>(c);
+        size_t len = trace_message.size();
+        if (len >= 2 && trace_message[len - 1] == '\n' && trace_message[len - 2] == '\n')
+            flush_trace_message();
+        return c;
+    }
+};
 
-    gdcm::PixmapReader reader;
-    reader.SetStream(dicomInput);
-    if (!reader.Read())
+// Route the GDCM messages of the current thread to the options callback while in scope.
+class TraceScope
+{
+public:
+    TraceScope(const OptionsStruct *options)
     {
-        proc_resp.status = 0x01;
-        return proc_resp;
+        trace_options = options;
     }
-    gdcm::Pixmap &image = reader.GetPixmap();
 
-    // Make sure the DICOM attributes follows PS 3.6 rules,
-    // when converting to an explicit little transfer syntax.
-    if (transfer_syntax != 1)
+    ~TraceScope()
     {
-        gdcm::FileExplicitFilter toExplicit;
-        toExplicit.SetChangePrivateTags(false);
-        toExplicit.SetFile(reader.GetFile());
-        if (!toExplicit.Change())
-        {
-            proc_resp.status = 0x02;
-            return proc_resp;
-        }
+        flush_trace_message();
+        trace_options = NULL;
     }
+};
 
-    gdcm::JPEGCodec jpegcodec;
-    gdcm::JPEGLSCodec jpeglscodec;
-    gdcm::JPEG2000Codec j2kcodec;
-    gdcm::ImageChangeTransferSyntax change;
+// Read a sample of the decoded buffer as double.
+static double get_sample(const std::vector<char> &buffer, const gdcm::PixelFormat &pf, size_t index)
+{
+    const char *p = buffer.data();
+    switch (pf.GetScalarType())
+    {
+    case gdcm::PixelFormat::SINGLEBIT:
+        return (p[index / 8] >> (index % 8)) & 1;
+    case gdcm::PixelFormat::INT8:
+        return reinterpret_cast<const int8_t *>(p)[index];
+    case gdcm::PixelFormat::UINT16:
+        return reinterpret_cast<const uint16_t *>(p)[index];
+    case gdcm::PixelFormat::INT16:
+        return reinterpret_cast<const int16_t *>(p)[index];
+    case gdcm::PixelFormat::UINT32:
+        return reinterpret_cast<const uint32_t *>(p)[index];
+    case gdcm::PixelFormat::INT32:
+        return reinterpret_cast<const int32_t *>(p)[index];
+    case gdcm::PixelFormat::FLOAT32:
+        return reinterpret_cast<const float *>(p)[index];
+    case gdcm::PixelFormat::FLOAT64:
+        return reinterpret_cast<const double *>(p)[index];
+    default:
+        return reinterpret_cast<const uint8_t *>(p)[index];
+    }
+}
 
-    switch (transfer_syntax)
+// Write a sample of the decoded buffer from double.
+static void set_sample(std::vector<char> &buffer, const gdcm::PixelFormat &pf, size_t index, double value)
+{
+    char *p = buffer.data();
+    switch (pf.GetScalarType())
     {
-    case 1:
-        change.SetTransferSyntax(gdcm::TransferSyntax::ImplicitVRLittleEndian);
-        break;
-    case 2:
-        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
-        break;
-    case 3:
-        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRBigEndian);
-        break;
-    case 4:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGBaselineProcess1);
-        is_jpeg = true;
-        break;
-    case 5:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGExtendedProcess2_4);
-        is_jpeg = true;
-        break;
-    case 6:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLosslessProcess14);
-        is_jpeg = true;
-        break;
-    case 7:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLosslessProcess14_1);
-        is_jpeg = true;
-        break;
-    case 8:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLSLossless);
-        is_jpegls = true;
+    case gdcm::PixelFormat::SINGLEBIT:
+        if (value != 0)
+            p[index / 8] |= static_cast<char>(1 << (index % 8));
+        else
+            p[index / 8] &= static_cast<char>(~(1 << (index % 8)));
         break;
-    case 9:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLSNearLossless);
-        is_jpegls = true;
+    case gdcm::PixelFormat::INT8:
+        reinterpret_cast<int8_t *>(p)[index] = static_cast<int8_t>(value);
         break;
-    case 10:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Lossless);
-        is_j2k = true;
+    case gdcm::PixelFormat::UINT16:
+        reinterpret_cast<uint16_t *>(p)[index] = static_cast<uint16_t>(value);
         break;
-    case 11:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000);
-        is_j2k = true;
+    case gdcm::PixelFormat::INT16:
+        reinterpret_cast<int16_t *>(p)[index] = static_cast<int16_t>(value);
         break;
-    case 12:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Part2Lossless);
-        is_j2k = true;
+    case gdcm::PixelFormat::UINT32:
+        reinterpret_cast<uint32_t *>(p)[index] = static_cast<uint32_t>(value);
         break;
-    case 13:
-        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Part2);
-        is_j2k = true;
+    case gdcm::PixelFormat::INT32:
+        reinterpret_cast<int32_t *>(p)[index] = static_cast<int32_t>(value);
         break;
-    case 14:
-        change.SetTransferSyntax(gdcm::TransferSyntax::RLELossless);
+    case gdcm::PixelFormat::FLOAT32:
+        reinterpret_cast<float *>(p)[index] = static_cast<float>(value);
         break;
-    case 15:
-        change.SetTransferSyntax(gdcm::TransferSyntax::MPEG2MainProfile);
+    case gdcm::PixelFormat::FLOAT64:
+        reinterpret_cast<double *>(p)[index] = value;
         break;
     default:
-        proc_resp.status = 0x03;
-        return proc_resp;
+        reinterpret_cast<uint8_t *>(p)[index] = static_cast<uint8_t>(value);
+        break;
     }
+}
 
-    // jpeg lossy
-    if (is_lossy && is_jpeg)
+// Number of samples in the decoded buffer, bits allocated may be 1 so the buffer length is not enough.
+static size_t get_sample_count(const gdcm::Pixmap &image)
+{
+    size_t count = image.GetPixelFormat().GetSamplesPerPixel();
+    for (unsigned int i = 0; i < image.GetNumberOfDimensions(); i++)
+        count *= image.GetDimension(i);
+    return count;
+}
+
+// Replace the pixel data with a decoded buffer.
+static void set_pixel_data(gdcm::Pixmap &image, const std::vector<char> &buffer)
+{
+    gdcm::DataElement pixeldata(gdcm::Tag(0x7fe0, 0x0010));
+    pixeldata.SetByteValue(buffer.data(), (uint32_t)buffer.size());
+    image.SetDataElement(pixeldata);
+
+    // The decoded buffer is native raw data
+    const gdcm::TransferSyntax &ts = image.GetTransferSyntax();
+    if (ts.IsEncapsulated() || ts == gdcm::TransferSyntax::ExplicitVRBigEndian)
+        image.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+}
+
+// Remove every (60xx,eeee) overlay group.
+static void remove_overlays(gdcm::Pixmap &image, gdcm::DataSet &ds)
+{
+    std::vector<gdcm::Tag> tags;
+    for (gdcm::DataSet::ConstIterator it = ds.Begin(); it != ds.End(); ++it)
     {
-        jpegcodec.SetLossless(false);
-        if (quality1)
-            jpegcodec.SetQuality(static_cast<double>(quality1));
-        change.SetUserCodec(&jpegcodec);
-        derive = true;
+        const gdcm::Tag &tag = it->GetTag();
+        if (tag.GetGroup() >= 0x6000 && tag.GetGroup() <= 0x601E && tag.GetGroup() % 2 == 0)
+            tags.push_back(tag);
     }
+    for (size_t i = 0; i < tags.size(); i++)
+        ds.Remove(tags[i]);
 
-    // jpegls lossy
-    if (is_lossy && is_jpegls)
+    image.SetNumberOfOverlays(0);
+}
+
+// Move overlays embedded in the unused bits of the pixel data to (60xx,3000) Overlay Data,
+// so they survive lossy compression.
+static void extract_overlays(gdcm::Pixmap &image, gdcm::DataSet &ds)
+{
+    for (size_t i = 0; i < image.GetNumberOfOverlays(); i++)
     {
-        jpeglscodec.SetLossless(false);
-        if (allow_error)
-            jpeglscodec.SetLossyError(allow_error);
-        change.SetUserCodec(&jpeglscodec);
-        derive = true;
+        const gdcm::Overlay &overlay = image.GetOverlay(i);
+        if (!overlay.IsInPixelData())
+            continue;
+
+        std::vector<char> unpacked(overlay.GetUnpackBufferLength());
+        if (!overlay.GetUnpackBuffer(unpacked.data(), unpacked.size()))
+            continue;
+
+        // One bit per pixel, even length
+        std::vector<char> packed((unpacked.size() + 15) / 16 * 2, 0);
+        for (size_t j = 0; j < unpacked.size(); j++)
+            if (unpacked[j])
+                packed[j / 8] |= static_cast<char>(1 << (j % 8));
+
+        unsigned short group = overlay.GetGroup();
+
+        gdcm::DataElement data(gdcm::Tag(group, 0x3000));
+        data.SetVR(gdcm::VR::OW);
+        data.SetByteValue(packed.data(), (uint32_t)packed.size());
+        ds.Replace(data);
+
+        gdcm::Attribute<0x6000, 0x0100> bits_allocated = {1};
+        gdcm::DataElement de = bits_allocated.GetAsDataElement();
+        de.SetTag(gdcm::Tag(group, 0x0100));
+        ds.Replace(de);
+
+        gdcm::Attribute<0x6000, 0x0102> bit_position = {0};
+        de = bit_position.GetAsDataElement();
+        de.SetTag(gdcm::Tag(group, 0x0102));
+        ds.Replace(de);
     }
+}
 
-    // jk2 lossy
-    if (is_lossy && is_j2k)
+// Rasterize overlays into the pixel data with the maximum pixel value, then remove them.
+static bool burn_in_overlays(gdcm::Pixmap &image, gdcm::DataSet &ds)
+{
+    if (image.GetNumberOfOverlays() == 0)
+        return true;
+
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
+
+    unsigned int columns = image.GetDimension(0);
+    unsigned int rows = image.GetDimension(1);
+    unsigned int samples = pf.GetSamplesPerPixel();
+    bool planar = image.GetPlanarConfiguration() == 1;
+    size_t pixels = (size_t)rows * columns;
+    size_t frames = pixels > 0 ? get_sample_count(image) / (pixels * samples) : 0;
+    double value = samples == 1 ? static_cast<double>(pf.GetMax()) : 255.0;
+
+    for (size_t i = 0; i < image.GetNumberOfOverlays(); i++)
     {
-        j2kcodec.SetLossyFlag(true);
-        if (quality1)
-            j2kcodec.SetQuality(0, static_cast<double>(quality1));
-        if (quality2)
-            j2kcodec.SetQuality(1, static_cast<double>(quality2));
-        if (quality3)
-            j2kcodec.SetQuality(2, static_cast<double>(quality3));
-        if (irreversible)
-        {
-            j2kcodec.SetReversible(false);
-        }
-        else
+        const gdcm::Overlay &overlay = image.GetOverlay(i);
+        std::vector<char> unpacked(overlay.GetUnpackBufferLength());
+        if (!overlay.GetUnpackBuffer(unpacked.data(), unpacked.size()))
+            return false;
+
+        // (60xx,0050) Overlay Origin is 1-based
+        const signed short *origin = overlay.GetOrigin();
+        for (unsigned int y = 0; y < overlay.GetRows(); y++)
         {
-            j2kcodec.SetReversible(true);
+            int row = origin[0] - 1 + (int)y;
+            if (row < 0 || row >= (int)rows)
+                continue;
+            for (unsigned int x = 0; x < overlay.GetColumns(); x++)
+            {
+                int column = origin[1] - 1 + (int)x;
+                if (column < 0 || column >= (int)columns || !unpacked[(size_t)y * overlay.GetColumns() + x])
+                    continue;
+
+                size_t pixel = (size_t)row * columns + column;
+                for (size_t f = 0; f < frames; f++)
+                    for (unsigned int k = 0; k < samples; k++)
+                        set_sample(buffer, pf, f * pixels * samples + (planar ? k * pixels + pixel : pixel * samples + k), value);
+            }
         }
-        change.SetUserCodec(&j2kcodec);
-        derive = true;
     }
 
-    change.SetInput(image);
-    if (!change.Change())
+    set_pixel_data(image, buffer);
+    remove_overlays(image, ds);
+    return true;
+}
+
+// Bake (0028,1052) Rescale Intercept and (0028,1053) Rescale Slope into the pixel data.
+// Images without the attributes are left untouched.
+static bool apply_modality_lut(gdcm::Pixmap &image, gdcm::DataSet &ds)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if (pf.GetSamplesPerPixel() != 1)
+        return true;
+    if (!ds.FindDataElement(gdcm::Tag(0x0028, 0x1052)) || !ds.FindDataElement(gdcm::Tag(0x0028, 0x1053)))
+        return true;
+
+    gdcm::Attribute<0x0028, 0x1052> intercept;
+    intercept.SetFromDataSet(ds);
+    gdcm::Attribute<0x0028, 0x1053> slope;
+    slope.SetFromDataSet(ds);
+
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
+
+    size_t count = get_sample_count(image);
+    std::vector<long long> values(count);
+    long long low = 0, high = 0;
+    for (size_t i = 0; i < count; i++)
     {
-        proc_resp.status = 0x04;
-        return proc_resp;
+        values[i] = llround(get_sample(buffer, pf, i) * slope.GetValue() + intercept.GetValue());
+        low = i == 0 ? values[i] : std::min(low, values[i]);
+        high = i == 0 ? values[i] : std::max(high, values[i]);
     }
 
-    // Derive image only for lossy
-    if (derive)
+    // Smallest integer type holding the output range
+    gdcm::PixelFormat out_pf;
+    std::vector<char> output;
+    if (low >= -32768 && high <= 32767)
     {
-        if (!gdcm::derives(reader.GetFile(), change.PixmapToPixmapFilter::GetOutput()))
-        {
-            proc_resp.status = 0x05;
-            return proc_resp;
-        }
+        out_pf = gdcm::PixelFormat(1, 16, 16, 15, 1);
+        std::vector<int16_t> t(values.begin(), values.end());
+        output.assign(reinterpret_cast<char *>(t.data()), reinterpret_cast<char *>(t.data() + t.size()));
+    }
+    else if (low >= 0 && high <= 65535)
+    {
+        out_pf = gdcm::PixelFormat(1, 16, 16, 15, 0);
+        std::vector<uint16_t> t(values.begin(), values.end());
+        output.assign(reinterpret_cast<char *>(t.data()), reinterpret_cast<char *>(t.data() + t.size()));
+    }
+    else
+    {
+        out_pf = gdcm::PixelFormat(1, 32, 32, 31, 1);
+        std::vector<int32_t> t(values.begin(), values.end());
+        output.assign(reinterpret_cast<char *>(t.data()), reinterpret_cast<char *>(t.data() + t.size()));
     }
 
-    gdcm::PixmapWriter writer;
-    writer.SetStream(dicomOutput);
-    writer.SetFile(reader.GetFile());
+    set_pixel_data(image, output);
+    image.SetPixelFormat(out_pf);
 
-    gdcm::File &file = writer.GetFile();
-    gdcm::FileMetaInformation &fmi = file.GetHeader();
-    fmi.Remove(gdcm::Tag(0x0002, 0x0100)); //  '   '    ' // PrivateInformationCreatorUID
-    fmi.Remove(gdcm::Tag(0x0002, 0x0102)); //  '   '    ' // PrivateInformation
+    // Rescale and pixel range attributes no longer apply
+    ds.Remove(gdcm::Tag(0x0028, 0x1052));
+    ds.Remove(gdcm::Tag(0x0028, 0x1053));
+    ds.Remove(gdcm::Tag(0x0028, 0x1054));
+    ds.Remove(gdcm::Tag(0x0028, 0x0106));
+    ds.Remove(gdcm::Tag(0x0028, 0x0107));
+    return true;
+}
 
-    const gdcm::Pixmap &pixout = change.PixmapToPixmapFilter::GetOutput();
-    writer.SetPixmap(pixout);
-    if (!writer.Write())
+// Apply the window at index (0028,1050) Window Center and (0028,1051) Window Width, producing
+// 8-bit MONOCHROME2 pixel data. Images without a window use the full pixel range.
+static bool apply_voi_lut(gdcm::Pixmap &image, gdcm::DataSet &ds, unsigned int index)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if (pf.GetSamplesPerPixel() != 1)
+        return false;
+
+    double slope = 1, intercept = 0;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x1052)) && ds.FindDataElement(gdcm::Tag(0x0028, 0x1053)))
     {
-        proc_resp.status = 0x06;
-        return proc_resp;
+        gdcm::Attribute<0x0028, 0x1052> at_intercept;
+        at_intercept.SetFromDataSet(ds);
+        gdcm::Attribute<0x0028, 0x1053> at_slope;
+        at_slope.SetFromDataSet(ds);
+        intercept = at_intercept.GetValue();
+        slope = at_slope.GetValue();
     }
 
-    proc_resp.status = 0x00;
-    proc_resp.image = dicomOutput.str();
-    return proc_resp;
-}
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
 
-struct OutputStruct c_convert(
+    size_t count = get_sample_count(image);
+    if (count == 0)
+        return false;
+
+    std::vector<double> values(count);
+    for (size_t i = 0; i < count; i++)
+        values[i] = get_sample(buffer, pf, i) * slope + intercept;
+
+    double center, width;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x1050)) && ds.FindDataElement(gdcm::Tag(0x0028, 0x1051)))
+    {
+        gdcm::Attribute<0x0028, 0x1050> at_center;
+        at_center.SetFromDataSet(ds);
+        gdcm::Attribute<0x0028, 0x1051> at_width;
+        at_width.SetFromDataSet(ds);
+        if (index >= at_center.GetNumberOfValues() || index >= at_width.GetNumberOfValues())
+            return false;
+        center = at_center.GetValue(index);
+        width = at_width.GetValue(index);
+    }
+    else if (index == 0)
+    {
+        double low = *std::min_element(values.begin(), values.end());
+        double high = *std::max_element(values.begin(), values.end());
+        center = (low + high + 1) / 2;
+        width = high - low + 1;
+    }
+    else
+    {
+        return false;
+    }
+
+    if (width < 1)
+        return false;
+
+    // Linear VOI LUT function (PS3.3 C.11.2.1.2.1)
+    bool invert = image.GetPhotometricInterpretation() == gdcm::PhotometricInterpretation::MONOCHROME1;
+    std::vector<char> output(count);
+    for (size_t i = 0; i < count; i++)
+    {
+        double v;
+        if (width == 1)
+            v = values[i] < center - 0.5 ? 0 : 255;
+        else
+            v = ((values[i] - (center - 0.5)) / (width - 1) + 0.5) * 255;
+        v = std::min(std::max(v, 0.0), 255.0);
+        output[i] = static_cast<char>(static_cast<unsigned char>(invert ? 255 - v : v));
+    }
+
+    set_pixel_data(image, output);
+    image.SetPixelFormat(gdcm::PixelFormat(1, 8, 8, 7, 0));
+    image.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::MONOCHROME2);
+
+    // Rescale, VOI and pixel range attributes no longer apply
+    ds.Remove(gdcm::Tag(0x0028, 0x1052));
+    ds.Remove(gdcm::Tag(0x0028, 0x1053));
+    ds.Remove(gdcm::Tag(0x0028, 0x1054));
+    ds.Remove(gdcm::Tag(0x0028, 0x1050));
+    ds.Remove(gdcm::Tag(0x0028, 0x1051));
+    ds.Remove(gdcm::Tag(0x0028, 0x1055));
+    ds.Remove(gdcm::Tag(0x0028, 0x1056));
+    ds.Remove(gdcm::Tag(0x0028, 0x3010));
+    ds.Remove(gdcm::Tag(0x0028, 0x0106));
+    ds.Remove(gdcm::Tag(0x0028, 0x0107));
+    return true;
+}
+
+// Switch between MONOCHROME1 and MONOCHROME2, inverting the stored values over their range and
+// moving the window centers so the image displays the same.
+static bool invert_monochrome(gdcm::Pixmap &image, gdcm::DataSet &ds, gdcm::PhotometricInterpretation::PIType target)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if (pf.GetSamplesPerPixel() != 1 || pf.GetScalarType() == gdcm::PixelFormat::FLOAT32
+        || pf.GetScalarType() == gdcm::PixelFormat::FLOAT64)
+        return false;
+
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
+
+    size_t count = get_sample_count(image);
+    if (count == 0)
+        return false;
+
+    // Signed ranges sum to -1, unsigned ones to the maximum
+    double range = static_cast<double>(pf.GetMin()) + static_cast<double>(pf.GetMax());
+    for (size_t i = 0; i < count; i++)
+        set_sample(buffer, pf, i, range - get_sample(buffer, pf, i));
+
+    set_pixel_data(image, buffer);
+    image.SetPhotometricInterpretation(target);
+
+    // Window centers are in rescaled values, m' = slope * range + 2 * intercept - m
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x1050)))
+    {
+        double slope = 1, intercept = 0;
+        if (ds.FindDataElement(gdcm::Tag(0x0028, 0x1052)) && ds.FindDataElement(gdcm::Tag(0x0028, 0x1053)))
+        {
+            gdcm::Attribute<0x0028, 0x1052> at_intercept;
+            at_intercept.SetFromDataSet(ds);
+            gdcm::Attribute<0x0028, 0x1053> at_slope;
+            at_slope.SetFromDataSet(ds);
+            intercept = at_intercept.GetValue();
+            slope = at_slope.GetValue();
+        }
+
+        gdcm::Attribute<0x0028, 0x1050> at_center;
+        at_center.SetFromDataSet(ds);
+        std::vector<double> centers;
+        for (unsigned int i = 0; i < at_center.GetNumberOfValues(); i++)
+            centers.push_back(slope * range + 2 * intercept - at_center.GetValue(i));
+        at_center.SetValues(centers.data(), (unsigned int)centers.size(), true);
+        ds.Replace(at_center.GetAsDataElement());
+    }
+
+    // A VOI LUT and the pixel value range no longer match the inverted values
+    ds.Remove(gdcm::Tag(0x0028, 0x3010));
+    ds.Remove(gdcm::Tag(0x0028, 0x0106));
+    ds.Remove(gdcm::Tag(0x0028, 0x0107));
+    return true;
+}
+
+// Round and clamp to an unsigned 8 bits sample.
+static char to_8bit(double value)
+{
+    return static_cast<char>(static_cast<unsigned char>(std::min(255.0, std::max(0.0, std::floor(value + 0.5)))));
+}
+
+// Index of a sample of interleaved or planar (one plane per frame) 3 samples pixel data.
+static size_t sample_index(size_t frame_pixels, bool planar, size_t pixel, unsigned int sample)
+{
+    if (!planar)
+        return pixel * 3 + sample;
+    return (pixel / frame_pixels * 3 + sample) * frame_pixels + pixel % frame_pixels;
+}
+
+// RGB or YBR_FULL to native YBR_FULL_422: each horizontal pair of pixels is stored as Y1 Y2 Cb Cr,
+// with the chroma averaged over the pair (PS 3.3 C.7.6.3.1.2). 8 bits samples and even columns.
+static bool subsample_422(gdcm::Pixmap &image)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if (pf.GetSamplesPerPixel() != 3 || pf.GetBitsAllocated() != 8 || image.GetDimension(0) % 2 == 1)
+        return false;
+
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
+
+    bool rgb = image.GetPhotometricInterpretation() == gdcm::PhotometricInterpretation::RGB;
+    bool planar = image.GetPlanarConfiguration() == 1;
+    size_t frame_pixels = (size_t)image.GetDimension(0) * image.GetDimension(1);
+    size_t pixels = buffer.size() / 3;
+    if (frame_pixels == 0)
+        return false;
+
+    // Pairs never span two rows, the columns are even
+    std::vector<char> subsampled(pixels * 2);
+    for (size_t i = 0; i + 1 < pixels; i += 2)
+    {
+        double y[2], cb = 0, cr = 0;
+        for (size_t j = 0; j < 2; j++)
+        {
+            double a = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i + j, 0)]);
+            double b = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i + j, 1)]);
+            double c = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i + j, 2)]);
+            if (rgb)
+            {
+                y[j] = 0.2990 * a + 0.5870 * b + 0.1140 * c;
+                cb += -0.1687 * a - 0.3313 * b + 0.5000 * c + 128;
+                cr += 0.5000 * a - 0.4187 * b - 0.0813 * c + 128;
+            }
+            else
+            {
+                y[j] = a;
+                cb += b;
+                cr += c;
+            }
+        }
+        subsampled[i * 2] = to_8bit(y[0]);
+        subsampled[i * 2 + 1] = to_8bit(y[1]);
+        subsampled[i * 2 + 2] = to_8bit(cb / 2);
+        subsampled[i * 2 + 3] = to_8bit(cr / 2);
+    }
+
+    set_pixel_data(image, subsampled);
+    image.SetPhotometricInterpretation(gdcm::PhotometricInterpretation::YBR_FULL_422);
+    image.SetPlanarConfiguration(0);
+    return true;
+}
+
+// Native YBR_FULL_422 to RGB or YBR_FULL, the chroma of a pair is used for both of its pixels.
+// Pixel data already holding 3 samples per pixel is only converted.
+static bool upsample_422(gdcm::Pixmap &image, gdcm::PhotometricInterpretation::PIType target)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    const gdcm::ByteValue *bv = image.GetDataElement().GetByteValue();
+    if (bv == NULL || pf.GetSamplesPerPixel() != 3 || pf.GetBitsAllocated() != 8 || image.GetDimension(0) % 2 == 1)
+        return false;
+
+    size_t pixels = get_sample_count(image) / 3;
+    bool full = bv->GetLength() >= pixels * 3;
+    if (pixels == 0 || bv->GetLength() < pixels * 2)
+        return false;
+
+    const unsigned char *data = reinterpret_cast<const unsigned char *>(bv->GetPointer());
+    std::vector<char> upsampled(pixels * 3);
+    for (size_t i = 0; i < pixels; i++)
+    {
+        double y, cb, cr;
+        if (full)
+        {
+            y = data[i * 3];
+            cb = data[i * 3 + 1];
+            cr = data[i * 3 + 2];
+        }
+        else
+        {
+            size_t pair = i / 2 * 4;
+            y = data[pair + i % 2];
+            cb = data[pair + 2];
+            cr = data[pair + 3];
+        }
+
+        if (target == gdcm::PhotometricInterpretation::RGB)
+        {
+            upsampled[i * 3] = to_8bit(y + 1.402 * (cr - 128));
+            upsampled[i * 3 + 1] = to_8bit(y - 0.344136 * (cb - 128) - 0.714136 * (cr - 128));
+            upsampled[i * 3 + 2] = to_8bit(y + 1.772 * (cb - 128));
+        }
+        else
+        {
+            upsampled[i * 3] = static_cast<char>(y);
+            upsampled[i * 3 + 1] = static_cast<char>(cb);
+            upsampled[i * 3 + 2] = static_cast<char>(cr);
+        }
+    }
+
+    set_pixel_data(image, upsampled);
+    image.SetPhotometricInterpretation(target);
+    image.SetPlanarConfiguration(0);
+    return true;
+}
+
+// Photometric interpretation of 8 bits RGB or YBR_FULL pixel data told from the samples, some
+// scanners label RGB pixel data as YBR_FULL. Gray RGB pixels read as YBR have chroma far from 128
+// and often fall out of gamut, gray YBR pixels have chroma close to 128. Up to 4096 pixels are
+// sampled, other images and undecided votes keep the declared interpretation.
+static gdcm::PhotometricInterpretation::PIType detect_photometric(const gdcm::Pixmap &image)
+{
+    gdcm::PhotometricInterpretation::PIType declared = image.GetPhotometricInterpretation();
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if ((declared != gdcm::PhotometricInterpretation::RGB && declared != gdcm::PhotometricInterpretation::YBR_FULL)
+        || pf.GetSamplesPerPixel() != 3 || pf.GetBitsAllocated() != 8)
+        return declared;
+
+    std::vector<char> buffer(image.GetBufferLength());
+    if (buffer.empty() || !image.GetBuffer(buffer.data()))
+        return declared;
+
+    bool planar = image.GetPlanarConfiguration() == 1;
+    size_t frame_pixels = (size_t)image.GetDimension(0) * image.GetDimension(1);
+    size_t pixels = buffer.size() / 3;
+    if (frame_pixels == 0)
+        return declared;
+
+    size_t step = std::max<size_t>(1, pixels / 4096);
+    size_t rgb_votes = 0, ybr_votes = 0;
+    for (size_t i = 0; i < pixels; i += step)
+    {
+        int a = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i, 0)]);
+        int b = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i, 1)]);
+        int c = static_cast<unsigned char>(buffer[sample_index(frame_pixels, planar, i, 2)]);
+
+        // Mid gray is neutral both ways and does not vote
+        bool gray = std::max(a, std::max(b, c)) - std::min(a, std::min(b, c)) <= 8;
+        bool neutral_chroma = std::abs(b - 128) <= 8 && std::abs(c - 128) <= 8;
+        if (gray && !neutral_chroma)
+            rgb_votes++;
+        else if (neutral_chroma && !gray)
+            ybr_votes++;
+
+        double red = a + 1.402 * (c - 128);
+        double green = a - 0.344136 * (b - 128) - 0.714136 * (c - 128);
+        double blue = a + 1.772 * (b - 128);
+        if (std::min(red, std::min(green, blue)) < -32 || std::max(red, std::max(green, blue)) > 287)
+            rgb_votes++;
+    }
+
+    if (rgb_votes == ybr_votes)
+        return declared;
+    return rgb_votes > ybr_votes ? gdcm::PhotometricInterpretation::RGB : gdcm::PhotometricInterpretation::YBR_FULL;
+}
+
+// Big endian number of an ICC profile.
+static uint32_t icc_u32(const std::string &icc, size_t offset)
+{
+    const unsigned char *p = reinterpret_cast<const unsigned char *>(icc.data()) + offset;
+    return (uint32_t)p[0] << 24 | (uint32_t)p[1] << 16 | (uint32_t)p[2] << 8 | p[3];
+}
+
+static double icc_s15fixed16(const std::string &icc, size_t offset)
+{
+    return static_cast<int32_t>(icc_u32(icc, offset)) / 65536.0;
+}
+
+// Offset and size of a tag of the ICC profile, false when missing or out of bounds.
+static bool icc_tag(const std::string &icc, const char *signature, size_t &offset, size_t &size)
+{
+    uint32_t count = icc_u32(icc, 128);
+    for (uint32_t i = 0; i < count && 132 + (size_t)i * 12 + 12 <= icc.size(); i++)
+    {
+        size_t entry = 132 + (size_t)i * 12;
+        if (icc.compare(entry, 4, signature) != 0)
+            continue;
+
+        offset = icc_u32(icc, entry + 4);
+        size = icc_u32(icc, entry + 8);
+        return size >= 12 && offset <= icc.size() && size <= icc.size() - offset;
+    }
+    return false;
+}
+
+// Tone reproduction curve of a matrix/TRC profile, from encoded to linear values in [0, 1].
+struct IccCurve
+{
+    std::vector<double> table;
+    int function;
+    double params[7];
+
+    double Eval(double x) const
+    {
+        if (!table.empty())
+        {
+            double position = x * (table.size() - 1);
+            size_t i = std::min((size_t)position, table.size() - 1);
+            size_t j = std::min(i + 1, table.size() - 1);
+            return table[i] + (table[j] - table[i]) * (position - i);
+        }
+
+        // ICC.1 10.18 parametricCurveType, 'curv' gammas are function 0
+        const double *p = params;
+        switch (function)
+        {
+        case 0:
+            return std::pow(x, p[0]);
+        case 1:
+            return x >= -p[2] / p[1] ? std::pow(p[1] * x + p[2], p[0]) : 0;
+        case 2:
+            return x >= -p[2] / p[1] ? std::pow(p[1] * x + p[2], p[0]) + p[3] : p[3];
+        case 3:
+            return x >= p[4] ? std::pow(p[1] * x + p[2], p[0]) : p[3] * x;
+        default:
+            return x >= p[4] ? std::pow(p[1] * x + p[2], p[0]) + p[5] : p[3] * x + p[6];
+        }
+    }
+};
+
+static bool icc_curve(const std::string &icc, const char *signature, IccCurve &curve)
+{
+    size_t offset, size;
+    if (!icc_tag(icc, signature, offset, size))
+        return false;
+
+    curve.function = 0;
+    curve.params[0] = 1;
+    if (icc.compare(offset, 4, "curv") == 0)
+    {
+        uint32_t count = icc_u32(icc, offset + 8);
+        if (count > (size - 12) / 2)
+            return false;
+
+        const unsigned char *p = reinterpret_cast<const unsigned char *>(icc.data()) + offset + 12;
+        if (count == 1)
+            curve.params[0] = (p[0] << 8 | p[1]) / 256.0;
+        for (uint32_t i = 0; count > 1 && i < count; i++)
+            curve.table.push_back((p[i * 2] << 8 | p[i * 2 + 1]) / 65535.0);
+        return true;
+    }
+    if (icc.compare(offset, 4, "para") == 0)
+    {
+        static const size_t counts[] = { 1, 3, 4, 5, 7 };
+        curve.function = icc_u32(icc, offset + 8) >> 16;
+        if (curve.function > 4 || size < 12 + counts[curve.function] * 4)
+            return false;
+
+        for (size_t i = 0; i < counts[curve.function]; i++)
+            curve.params[i] = icc_s15fixed16(icc, offset + 12 + i * 4);
+        return curve.function == 0 || curve.params[1] != 0;
+    }
+    return false;
+}
+
+// Column of the profile matrix, the XYZ of a primary in the D50 connection space.
+static bool icc_primary(const std::string &icc, const char *signature, double xyz[3])
+{
+    size_t offset, size;
+    if (!icc_tag(icc, signature, offset, size) || size < 20 || icc.compare(offset, 4, "XYZ ") != 0)
+        return false;
+
+    for (size_t i = 0; i < 3; i++)
+        xyz[i] = icc_s15fixed16(icc, offset + 8 + i * 4);
+    return true;
+}
+
+// Convert RGB pixel data described by a matrix/TRC ICC profile to sRGB: decode with the tone
+// curves, map to XYZ D50 with the primaries, then to sRGB with its Bradford adapted matrix.
+// Unsigned 8 or 16 bits samples only, LUT based profiles are not supported.
+static bool apply_icc_profile(gdcm::Pixmap &image, const std::string &icc)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    if (image.GetPhotometricInterpretation() != gdcm::PhotometricInterpretation::RGB
+        || pf.GetSamplesPerPixel() != 3 || pf.GetPixelRepresentation() != 0
+        || (pf.GetBitsAllocated() != 8 && pf.GetBitsAllocated() != 16))
+        return false;
+    if (icc.size() < 132 || icc.compare(16, 4, "RGB ") != 0 || icc.compare(20, 4, "XYZ ") != 0)
+        return false;
+
+    const char *trc[3] = { "rTRC", "gTRC", "bTRC" };
+    const char *primaries[3] = { "rXYZ", "gXYZ", "bXYZ" };
+    IccCurve curves[3];
+    double profile[3][3];
+    for (int k = 0; k < 3; k++)
+    {
+        double xyz[3];
+        if (!icc_curve(icc, trc[k], curves[k]) || !icc_primary(icc, primaries[k], xyz))
+            return false;
+        for (int i = 0; i < 3; i++)
+            profile[i][k] = xyz[i];
+    }
+
+    static const double srgb[3][3] = {
+        { 3.1338561, -1.6168667, -0.4906146 },
+        { -0.9787684, 1.9161415, 0.0334540 },
+        { 0.0719453, -0.2289914, 1.4052427 },
+    };
+    double matrix[3][3];
+    for (int i = 0; i < 3; i++)
+        for (int j = 0; j < 3; j++)
+            matrix[i][j] = srgb[i][0] * profile[0][j] + srgb[i][1] * profile[1][j] + srgb[i][2] * profile[2][j];
+
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+        return false;
+
+    // Decoded values of every sample value, per channel
+    double max = static_cast<double>(pf.GetMax());
+    std::vector<double> linear[3];
+    for (int k = 0; k < 3; k++)
+    {
+        linear[k].resize((size_t)max + 1);
+        for (size_t v = 0; v < linear[k].size(); v++)
+            linear[k][v] = curves[k].Eval(v / max);
+    }
+
+    bool planar = image.GetPlanarConfiguration() == 1;
+    size_t frame_pixels = (size_t)image.GetDimension(0) * image.GetDimension(1);
+    size_t pixels = get_sample_count(image) / 3;
+    if (frame_pixels == 0)
+        return false;
+
+    for (size_t i = 0; i < pixels; i++)
+    {
+        double in[3];
+        for (unsigned int k = 0; k < 3; k++)
+        {
+            double v = std::min(max, get_sample(buffer, pf, sample_index(frame_pixels, planar, i, k)));
+            in[k] = linear[k][(size_t)v];
+        }
+        for (unsigned int k = 0; k < 3; k++)
+        {
+            double v = matrix[k][0] * in[0] + matrix[k][1] * in[1] + matrix[k][2] * in[2];
+            v = std::min(1.0, std::max(0.0, v));
+            v = v <= 0.0031308 ? 12.92 * v : 1.055 * std::pow(v, 1 / 2.4) - 0.055;
+            set_sample(buffer, pf, sample_index(frame_pixels, planar, i, k), std::floor(v * max + 0.5));
+        }
+    }
+
+    set_pixel_data(image, buffer);
+    return true;
+}
+
+struct ProcResp
+{
+    unsigned int status;
+    std::string image;
+};
+
+// (7FE0,0008) Float Pixel Data or (7FE0,0009) Double Float Pixel Data.
+static bool has_float_pixel_data(const gdcm::DataSet &ds)
+{
+    return ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0008)) || ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0009));
+}
+
+// Transfer syntax of a pipeline id, TS_END when unknown.
+static gdcm::TransferSyntax transfer_syntax_from_id(int transfer_syntax)
+{
+    switch (transfer_syntax)
+    {
+    case 1: return gdcm::TransferSyntax::ImplicitVRLittleEndian;
+    case 2: return gdcm::TransferSyntax::ExplicitVRLittleEndian;
+    case 3: return gdcm::TransferSyntax::ExplicitVRBigEndian;
+    case 4: return gdcm::TransferSyntax::JPEGBaselineProcess1;
+    case 5: return gdcm::TransferSyntax::JPEGExtendedProcess2_4;
+    case 6: return gdcm::TransferSyntax::JPEGLosslessProcess14;
+    case 7: return gdcm::TransferSyntax::JPEGLosslessProcess14_1;
+    case 8: return gdcm::TransferSyntax::JPEGLSLossless;
+    case 9: return gdcm::TransferSyntax::JPEGLSNearLossless;
+    case 10: return gdcm::TransferSyntax::JPEG2000Lossless;
+    case 11: return gdcm::TransferSyntax::JPEG2000;
+    case 12: return gdcm::TransferSyntax::JPEG2000Part2Lossless;
+    case 13: return gdcm::TransferSyntax::JPEG2000Part2;
+    case 14: return gdcm::TransferSyntax::RLELossless;
+    case 15: return gdcm::TransferSyntax::MPEG2MainProfile;
+    case 16: return gdcm::TransferSyntax::HTJ2KLossless;
+    case 17: return gdcm::TransferSyntax::HTJ2KLosslessRPCL;
+    case 18: return gdcm::TransferSyntax::HTJ2K;
+    case 19: return gdcm::TransferSyntax::MPEG4AVCH264HighProfileLevel4_1;
+    case 20: return gdcm::TransferSyntax::MPEG4AVCH264BDcompatibleHighProfileLevel4_1;
+    case 21: return gdcm::TransferSyntax::DeflatedExplicitVRLittleEndian;
+    case 22: return gdcm::TransferSyntax::JPIPReferenced;
+    default: return gdcm::TransferSyntax::TS_END;
+    }
+}
+
+// Whether GDCM can encode a pipeline id. OpenJPEG decodes HTJ2K but has no HT block encoder, MPEG-2
+// and MPEG-4 video are only encapsulated as is, JPIP Referenced pixel data is a URL.
+static bool has_encoder(int transfer_syntax)
+{
+    return transfer_syntax != 15 && (transfer_syntax < 16 || transfer_syntax > 20) && transfer_syntax != 22;
+}
+
+// Photometric interpretation from its wrapper id, PI_END when unknown.
+static gdcm::PhotometricInterpretation::PIType photometric_from_id(int photometric_interpretation)
+{
+    switch (photometric_interpretation)
+    {
+    case 1: return gdcm::PhotometricInterpretation::MONOCHROME1;
+    case 2: return gdcm::PhotometricInterpretation::MONOCHROME2;
+    case 3: return gdcm::PhotometricInterpretation::PALETTE_COLOR;
+    case 4: return gdcm::PhotometricInterpretation::RGB;
+    case 5: return gdcm::PhotometricInterpretation::HSV;
+    case 6: return gdcm::PhotometricInterpretation::ARGB;
+    case 7: return gdcm::PhotometricInterpretation::CMYK;
+    case 8: return gdcm::PhotometricInterpretation::YBR_FULL;
+    case 9: return gdcm::PhotometricInterpretation::YBR_FULL_422;
+    case 10: return gdcm::PhotometricInterpretation::YBR_PARTIAL_422;
+    case 11: return gdcm::PhotometricInterpretation::YBR_PARTIAL_420;
+    case 12: return gdcm::PhotometricInterpretation::YBR_ICT;
+    case 13: return gdcm::PhotometricInterpretation::YBR_RCT;
+    default: return gdcm::PhotometricInterpretation::PI_END;
+    }
+}
+
+// Move the raw pixel data of an Explicit VR Little Endian file into fragments, one per frame,
+// and declare Encapsulated Uncompressed Explicit VR Little Endian.
+static bool encapsulate_uncompressed(std::string &image)
+{
+    std::istringstream dicomInput(image);
+    gdcm::PixmapReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    const gdcm::Pixmap &pixmap = reader.GetPixmap();
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::ByteValue *bv = pixel_data.GetByteValue();
+    if (bv == NULL)
+        return false;
+
+    const unsigned int *dims = pixmap.GetDimensions();
+    const gdcm::PixelFormat &pf = pixmap.GetPixelFormat();
+    size_t frames = pixmap.GetNumberOfDimensions() == 3 ? dims[2] : 1;
+    size_t frame_size = (size_t)dims[0] * dims[1] * pf.GetSamplesPerPixel() * (pf.GetBitsAllocated() / 8);
+    if (frame_size == 0 || frame_size * frames > bv->GetLength())
+        return false;
+
+    // Fragments have an even length, the Basic Offset Table stays empty
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> sq = new gdcm::SequenceOfFragments;
+    for (size_t i = 0; i < frames; i++)
+    {
+        std::string frame(bv->GetPointer() + i * frame_size, frame_size);
+        if (frame.size() % 2 == 1)
+            frame += '\0';
+        gdcm::Fragment fragment;
+        fragment.SetByteValue(frame.c_str(), (uint32_t)frame.size());
+        sq->AddFragment(fragment);
+    }
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(gdcm::VR::OB);
+    encapsulated.SetValue(*sq);
+    encapsulated.SetVLToUndefined();
+    ds.Replace(encapsulated);
+
+    // GDCM has no constant for this UID, the data set itself stays Explicit VR Little Endian
+    gdcm::FileMetaInformation &fmi = reader.GetFile().GetHeader();
+    std::string uid("1.2.840.10008.1.2.1.98");
+    gdcm::DataElement ts(gdcm::Tag(0x0002, 0x0010));
+    ts.SetVR(gdcm::VR::UI);
+    ts.SetByteValue(uid.c_str(), (uint32_t)uid.size());
+    fmi.Replace(ts);
+
+    // Recompute the group length
+    uint32_t length = 0;
+    for (gdcm::DataSet::ConstIterator it = fmi.Begin(); it != fmi.End(); ++it)
+    {
+        if (it->GetTag() != gdcm::Tag(0x0002, 0x0000))
+            length += it->GetLength<gdcm::ExplicitDataElement>();
+    }
+    gdcm::Attribute<0x0002, 0x0000> group_length;
+    group_length.SetValue(length);
+    fmi.Replace(group_length.GetAsDataElement());
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Write the frame offsets of encapsulated pixel data in an Extended Offset Table, the Basic
+// Offset Table is emptied. Requires one fragment per frame, otherwise the image is kept unless forced.
+static bool add_extended_offset_table(std::string &image, bool force)
+{
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+        return true;
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::SequenceOfFragments *sq = pixel_data.GetSequenceOfFragments();
+    if (sq == NULL)
+        return true;
+
+    unsigned int frames = 1;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0008)))
+    {
+        gdcm::Attribute<0x0028, 0x0008> at;
+        at.SetFromDataSet(ds);
+        frames = at.GetValue() > 0 ? at.GetValue() : 1;
+    }
+    if (sq->GetNumberOfFragments() != frames)
+        return !force;
+
+    // Offsets from the first byte of the first fragment item, 8 bytes of item header each
+    std::vector<uint64_t> offsets;
+    std::vector<uint64_t> lengths;
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> table = new gdcm::SequenceOfFragments;
+    uint64_t offset = 0;
+    for (unsigned int i = 0; i < frames; i++)
+    {
+        const gdcm::Fragment &fragment = sq->GetFragment(i);
+        uint64_t length = fragment.GetVL();
+        offsets.push_back(offset);
+        lengths.push_back(length);
+        offset += 8 + length;
+        table->AddFragment(fragment);
+    }
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(pixel_data.GetVR());
+    encapsulated.SetValue(*table);
+    encapsulated.SetVLToUndefined();
+    ds.Replace(encapsulated);
+
+    gdcm::DataElement eot(gdcm::Tag(0x7fe0, 0x0001));
+    eot.SetVR(gdcm::VR::OV);
+    eot.SetByteValue(reinterpret_cast<const char *>(offsets.data()), (uint32_t)(offsets.size() * 8));
+    ds.Replace(eot);
+
+    gdcm::DataElement eot_lengths(gdcm::Tag(0x7fe0, 0x0002));
+    eot_lengths.SetVR(gdcm::VR::OV);
+    eot_lengths.SetByteValue(reinterpret_cast<const char *>(lengths.data()), (uint32_t)(lengths.size() * 8));
+    ds.Replace(eot_lengths);
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Merge the JPEG 2000 fragments so that each frame is held by a single fragment, a frame starts
+// with a fragment beginning with the SOC marker. The Basic Offset Table gets one entry per frame.
+static bool single_fragment_per_frame(std::string &image)
+{
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+        return true;
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::SequenceOfFragments *sq = pixel_data.GetSequenceOfFragments();
+    if (sq == NULL)
+        return true;
+
+    std::vector<std::string> frames;
+    for (unsigned int i = 0; i < sq->GetNumberOfFragments(); i++)
+    {
+        const gdcm::ByteValue *bv = sq->GetFragment(i).GetByteValue();
+        if (bv == NULL)
+            return false;
+        const unsigned char *data = reinterpret_cast<const unsigned char *>(bv->GetPointer());
+        bool soc = bv->GetLength() >= 2 && data[0] == 0xFF && data[1] == 0x4F;
+        if (soc || frames.empty())
+            frames.push_back(std::string());
+        frames.back().append(bv->GetPointer(), bv->GetLength());
+    }
+
+    // Offsets from the first byte of the first fragment item, 8 bytes of item header each
+    std::vector<uint32_t> offsets;
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> merged = new gdcm::SequenceOfFragments;
+    uint32_t offset = 0;
+    for (size_t i = 0; i < frames.size(); i++)
+    {
+        if (frames[i].size() % 2 == 1)
+            frames[i] += '\0';
+        offsets.push_back(offset);
+        offset += 8 + (uint32_t)frames[i].size();
+        gdcm::Fragment fragment;
+        fragment.SetByteValue(frames[i].c_str(), (uint32_t)frames[i].size());
+        merged->AddFragment(fragment);
+    }
+    merged->GetTable().SetByteValue(reinterpret_cast<const char *>(offsets.data()), (uint32_t)(offsets.size() * 4));
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(pixel_data.GetVR());
+    encapsulated.SetValue(*merged);
+    encapsulated.SetVLToUndefined();
+    ds.Replace(encapsulated);
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Write the Basic Offset Table of encapsulated pixel data from its fragments. A frame starts with
+// a fragment beginning with a JPEG SOI or a JPEG 2000 SOC marker, unless there is one fragment
+// per frame. Fails when the frames can not be told apart.
+static bool rebuild_offset_table(std::string &image)
+{
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+        return true;
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::SequenceOfFragments *sq = pixel_data.GetSequenceOfFragments();
+    if (sq == NULL)
+        return true;
+
+    unsigned int frames = 1;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0008)))
+    {
+        gdcm::Attribute<0x0028, 0x0008> at;
+        at.SetFromDataSet(ds);
+        frames = at.GetValue() > 0 ? at.GetValue() : 1;
+    }
+
+    // Offsets from the first byte of the first fragment item, 8 bytes of item header each
+    std::vector<uint32_t> offsets;
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> table = new gdcm::SequenceOfFragments;
+    bool one_per_frame = sq->GetNumberOfFragments() == frames;
+    uint32_t offset = 0;
+    for (unsigned int i = 0; i < sq->GetNumberOfFragments(); i++)
+    {
+        const gdcm::Fragment &fragment = sq->GetFragment(i);
+        const gdcm::ByteValue *bv = fragment.GetByteValue();
+        if (bv == NULL)
+            return false;
+        const unsigned char *data = reinterpret_cast<const unsigned char *>(bv->GetPointer());
+        bool marker = bv->GetLength() >= 2 && data[0] == 0xFF && (data[1] == 0xD8 || data[1] == 0x4F);
+        if (one_per_frame || marker)
+            offsets.push_back(offset);
+        offset += 8 + bv->GetLength();
+        table->AddFragment(fragment);
+    }
+    if (offsets.size() != frames)
+        return false;
+    table->GetTable().SetByteValue(reinterpret_cast<const char *>(offsets.data()), (uint32_t)(offsets.size() * 4));
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(pixel_data.GetVR());
+    encapsulated.SetValue(*table);
+    encapsulated.SetVLToUndefined();
+    ds.Replace(encapsulated);
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Keep the first frames of a multi-frame image, before any decoding. Native pixel data is cut,
+// encapsulated frames are found as in rebuild_offset_table. (0028,0008) Number of Frames, the
+// (5200,9230) Per-frame Functional Groups and (0018,1065) Frame Time Vector follow.
+static bool truncate_frames(std::string &image, unsigned int max_frames)
+{
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    gdcm::File &file = reader.GetFile();
+    gdcm::DataSet &ds = file.GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x0028, 0x0008)) || !ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+        return true;
+    gdcm::Attribute<0x0028, 0x0008> number_of_frames;
+    number_of_frames.SetFromDataSet(ds);
+    if (number_of_frames.GetValue() <= 0 || (unsigned int)number_of_frames.GetValue() <= max_frames)
+        return true;
+    unsigned int frames = number_of_frames.GetValue();
+
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::SequenceOfFragments *sq = pixel_data.GetSequenceOfFragments();
+    if (sq == NULL)
+    {
+        const gdcm::ByteValue *bv = pixel_data.GetByteValue();
+        if (bv == NULL)
+            return false;
+
+        // 1-bit frames are not byte aligned, the last byte is kept whole
+        std::vector<unsigned int> dims = gdcm::ImageHelper::GetDimensionsValue(file);
+        gdcm::PixelFormat pf = gdcm::ImageHelper::GetPixelFormatValue(file);
+        size_t bits = (size_t)dims[0] * dims[1] * pf.GetSamplesPerPixel() * pf.GetBitsAllocated() * max_frames;
+        size_t length = (bits + 7) / 8;
+        if (length > bv->GetLength())
+            return false;
+
+        std::vector<char> pixels(bv->GetPointer(), bv->GetPointer() + length);
+        if (pixels.size() % 2 == 1)
+            pixels.push_back(0);
+        gdcm::DataElement native(gdcm::Tag(0x7fe0, 0x0010));
+        native.SetVR(pixel_data.GetVR());
+        native.SetByteValue(pixels.data(), (uint32_t)pixels.size());
+        ds.Replace(native);
+    }
+    else
+    {
+        // Offsets from the first byte of the first fragment item, 8 bytes of item header each
+        std::vector<uint32_t> offsets;
+        gdcm::SmartPointer<gdcm::SequenceOfFragments> kept = new gdcm::SequenceOfFragments;
+        bool one_per_frame = sq->GetNumberOfFragments() == frames;
+        uint32_t offset = 0;
+        for (unsigned int i = 0; i < sq->GetNumberOfFragments(); i++)
+        {
+            const gdcm::Fragment &fragment = sq->GetFragment(i);
+            const gdcm::ByteValue *bv = fragment.GetByteValue();
+            if (bv == NULL)
+                return false;
+            const unsigned char *data = reinterpret_cast<const unsigned char *>(bv->GetPointer());
+            bool marker = bv->GetLength() >= 2 && data[0] == 0xFF && (data[1] == 0xD8 || data[1] == 0x4F);
+            if (one_per_frame || marker || i == 0)
+            {
+                if (offsets.size() == max_frames)
+                    break;
+                offsets.push_back(offset);
+            }
+            offset += 8 + bv->GetLength();
+            kept->AddFragment(fragment);
+        }
+        if (offsets.size() != max_frames)
+            return false;
+        kept->GetTable().SetByteValue(reinterpret_cast<const char *>(offsets.data()), (uint32_t)(offsets.size() * 4));
+
+        gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+        encapsulated.SetVR(pixel_data.GetVR());
+        encapsulated.SetValue(*kept);
+        encapsulated.SetVLToUndefined();
+        ds.Replace(encapsulated);
+
+        // Written again by add_extended_offset_table when needed
+        ds.Remove(gdcm::Tag(0x7fe0, 0x0001));
+        ds.Remove(gdcm::Tag(0x7fe0, 0x0002));
+    }
+
+    number_of_frames.SetValue(max_frames);
+    ds.Replace(number_of_frames.GetAsDataElement());
+
+    if (ds.FindDataElement(gdcm::Tag(0x5200, 0x9230)))
+    {
+        gdcm::DataElement per_frame = ds.GetDataElement(gdcm::Tag(0x5200, 0x9230));
+        gdcm::SmartPointer<gdcm::SequenceOfItems> items = per_frame.GetValueAsSQ();
+        if (items && items->GetNumberOfItems() > max_frames)
+        {
+            gdcm::SmartPointer<gdcm::SequenceOfItems> first = new gdcm::SequenceOfItems;
+            first->SetLengthToUndefined();
+            for (unsigned int i = 1; i <= max_frames; i++)
+                first->AddItem(items->GetItem(i));
+            per_frame.SetValue(*first);
+            per_frame.SetVLToUndefined();
+            ds.Replace(per_frame);
+        }
+    }
+
+    if (ds.FindDataElement(gdcm::Tag(0x0018, 0x1065)))
+    {
+        gdcm::DataElement frame_time = ds.GetDataElement(gdcm::Tag(0x0018, 0x1065));
+        const gdcm::ByteValue *bv = frame_time.GetByteValue();
+        if (bv != NULL)
+        {
+            std::string values(bv->GetPointer(), bv->GetLength());
+            size_t end = 0;
+            for (unsigned int i = 0; i < max_frames && end != std::string::npos; i++)
+                end = values.find('\\', i == 0 ? 0 : end + 1);
+            if (end != std::string::npos)
+                values.erase(end);
+            if (values.size() % 2 == 1)
+                values += ' ';
+            frame_time.SetByteValue(values.c_str(), (uint32_t)values.size());
+            ds.Replace(frame_time);
+        }
+    }
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(file);
+    writer.CheckFileMetaInformationOff();
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Make the data set VRs follow PS 3.6 for an explicit transfer syntax. When the structure is
+// preserved, a data set read as explicit VR is kept as it is.
+static bool make_explicit(gdcm::File &file, const OptionsStruct *options)
+{
+    if (options != NULL && options->preserve_structure
+        && file.GetHeader().GetDataSetTransferSyntax().IsExplicit())
+        return true;
+
+    gdcm::FileExplicitFilter toExplicit;
+    toExplicit.SetChangePrivateTags(false);
+    toExplicit.SetFile(file);
+    return toExplicit.Change();
+}
+
+// Change only the data set encoding, the pixel data bytes are kept as they are. Encapsulated
+// pixel data keeps its transfer syntax, raw pixel data moves between little endian syntaxes.
+static ProcResp change_header_transfer(int transfer_syntax, gdcm::File &file, const OptionsStruct *options)
+{
+    struct ProcResp proc_resp;
+    std::ostringstream dicomOutput;
+
+    gdcm::TransferSyntax ts = transfer_syntax_from_id(transfer_syntax);
+    if (ts == gdcm::TransferSyntax::TS_END)
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    const gdcm::TransferSyntax &source = file.GetHeader().GetDataSetTransferSyntax();
+    bool source_raw_le = source == gdcm::TransferSyntax::ImplicitVRLittleEndian
+        || source == gdcm::TransferSyntax::ExplicitVRLittleEndian
+        || source == gdcm::TransferSyntax::DeflatedExplicitVRLittleEndian;
+    bool target_raw_le = transfer_syntax == 1 || transfer_syntax == 2 || transfer_syntax == 21;
+    if (ts != source && !(source_raw_le && target_raw_le))
+    {
+        proc_resp.status = 0x08;
+        return proc_resp;
+    }
+
+    if (transfer_syntax != 1 && !make_explicit(file, options))
+    {
+        proc_resp.status = 0x02;
+        return proc_resp;
+    }
+
+    gdcm::FileMetaInformation &fmi = file.GetHeader();
+    fmi.SetDataSetTransferSyntax(ts);
+    fmi.Remove(gdcm::Tag(0x0002, 0x0010)); // Filled again from the data set transfer syntax
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(file);
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x06;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Modify Photometric Interpretation from incoming stream.
+ProcResp change_photometric(
+    int photometric_interpretation,
+    const OptionsStruct *options,
+    std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::PixmapReader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = has_float_pixel_data(reader.GetFile().GetDataSet()) ? 0x0A : 0x01;
+        return proc_resp;
+    }
+
+    gdcm::Pixmap &image = reader.GetPixmap();
+
+    // Keep (0028,2000) ICC Profile and (0028,2002) Color Space
+    const gdcm::DataSet &source_ds = reader.GetFile().GetDataSet();
+    std::vector<gdcm::DataElement> icc;
+    if (source_ds.FindDataElement(gdcm::Tag(0x0028, 0x2000)))
+        icc.push_back(source_ds.GetDataElement(gdcm::Tag(0x0028, 0x2000)));
+    if (source_ds.FindDataElement(gdcm::Tag(0x0028, 0x2002)))
+        icc.push_back(source_ds.GetDataElement(gdcm::Tag(0x0028, 0x2002)));
+
+    // Apply Modality LUT
+    if (options != NULL && options->apply_rescale)
+    {
+        if (!apply_modality_lut(image, reader.GetFile().GetDataSet()))
+        {
+            proc_resp.status = 0x06;
+            return proc_resp;
+        }
+    }
+
+    // Apply VOI LUT
+    if (options != NULL && options->bake_voi_lut)
+    {
+        if (!apply_voi_lut(image, reader.GetFile().GetDataSet(), options->voi_lut_index))
+        {
+            proc_resp.status = 0x07;
+            return proc_resp;
+        }
+    }
+
+    // Downshift to 8-bit with the VOI window
+    if (options != NULL && options->target_bits == 8 && image.GetPixelFormat().GetBitsAllocated() > 8)
+    {
+        if (image.GetPixelFormat().GetSamplesPerPixel() != 1)
+        {
+            proc_resp.status = 0x0A;
+            return proc_resp;
+        }
+        if (!apply_voi_lut(image, reader.GetFile().GetDataSet(), options->voi_lut_index))
+        {
+            proc_resp.status = 0x07;
+            return proc_resp;
+        }
+    }
+
+    // Strip or burn in overlays
+    if (options != NULL && options->overlays == 1)
+    {
+        remove_overlays(image, reader.GetFile().GetDataSet());
+    }
+    else if (options != NULL && options->overlays == 2)
+    {
+        if (!burn_in_overlays(image, reader.GetFile().GetDataSet()))
+        {
+            proc_resp.status = 0x08;
+            return proc_resp;
+        }
+    }
+
+    gdcm::PixmapWriter writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+
+    gdcm::PhotometricInterpretation::PIType source_pi = image.GetPhotometricInterpretation();
+    bool invert = (photometric_interpretation == 1 && source_pi == gdcm::PhotometricInterpretation::MONOCHROME2)
+        || (photometric_interpretation == 2 && source_pi == gdcm::PhotometricInterpretation::MONOCHROME1);
+
+    if (photometric_interpretation == 0) {
+        writer.SetPixmap(image);
+    } else if (invert) {
+        gdcm::PhotometricInterpretation::PIType target = photometric_interpretation == 1
+            ? gdcm::PhotometricInterpretation::MONOCHROME1
+            : gdcm::PhotometricInterpretation::MONOCHROME2;
+        if (!invert_monochrome(image, reader.GetFile().GetDataSet(), target))
+        {
+            proc_resp.status = 0x03;
+            return proc_resp;
+        }
+
+        writer.SetPixmap(image);
+    } else if(image.GetPhotometricInterpretation() != gdcm::PhotometricInterpretation::PALETTE_COLOR) {
+        gdcm::PhotometricInterpretation::PIType target = photometric_from_id(photometric_interpretation);
+        if (target == gdcm::PhotometricInterpretation::PI_END)
+        {
+            proc_resp.status = 0x02;
+            return proc_resp;
+        }
+
+        // GDCM only relabels 4:2:2, the chroma of native pixel data is subsampled or expanded here
+        bool to_422 = target == gdcm::PhotometricInterpretation::YBR_FULL_422
+            && (source_pi == gdcm::PhotometricInterpretation::RGB || source_pi == gdcm::PhotometricInterpretation::YBR_FULL);
+        bool from_422 = source_pi == gdcm::PhotometricInterpretation::YBR_FULL_422
+            && (target == gdcm::PhotometricInterpretation::RGB || target == gdcm::PhotometricInterpretation::YBR_FULL)
+            && image.GetDataElement().GetByteValue() != NULL;
+
+        gdcm::ImageChangePhotometricInterpretation change;
+        if (to_422 || from_422)
+        {
+            if (!(to_422 ? subsample_422(image) : upsample_422(image, target)))
+            {
+                proc_resp.status = 0x03;
+                return proc_resp;
+            }
+
+            writer.SetPixmap(image);
+        }
+        else
+        {
+            change.SetInput(image);
+            change.SetPhotometricInterpretation(target);
+            if (!change.Change())
+            {
+                proc_resp.status = 0x03;
+                return proc_resp;
+            }
+
+            writer.SetPixmap(change.PixmapToPixmapFilter::GetOutput());
+        }
+    } else if (photometric_interpretation == 3) {
+        writer.SetPixmap(image);
+    } else {
+        gdcm::PhotometricInterpretation::PIType target = photometric_from_id(photometric_interpretation);
+        if (target == gdcm::PhotometricInterpretation::PI_END)
+        {
+            proc_resp.status = 0x02;
+            return proc_resp;
+        }
+
+        // Expand the palette to RGB, then to the target
+        gdcm::ImageApplyLookupTable lut;
+        lut.SetInput(image);
+        if (!lut.Apply())
+        {
+            proc_resp.status = 0x04;
+            return proc_resp;
+        }
+
+        gdcm::ImageChangePhotometricInterpretation change;
+        if (target != gdcm::PhotometricInterpretation::RGB)
+        {
+            change.SetInput(lut.PixmapToPixmapFilter::GetOutput());
+            change.SetPhotometricInterpretation(target);
+            if (!change.Change())
+            {
+                proc_resp.status = 0x03;
+                return proc_resp;
+            }
+            writer.SetPixmap(change.PixmapToPixmapFilter::GetOutput());
+        }
+        else
+        {
+            writer.SetPixmap(lut.PixmapToPixmapFilter::GetOutput());
+        }
+
+        // Remove the palette descriptors, data, UID and segmented data
+        const uint16_t palette_elements[] = { 0x1101, 0x1102, 0x1103, 0x1199, 0x1201, 0x1202, 0x1203, 0x1221, 0x1222, 0x1223 };
+        for (size_t i = 0; i < sizeof(palette_elements) / sizeof(palette_elements[0]); i++)
+            writer.GetFile().GetDataSet().Remove(gdcm::Tag(0x0028, palette_elements[i]));
+    }
+
+    // Force planar configuration of RGB output
+    gdcm::ImageChangePlanarConfiguration planar;
+    if (photometric_interpretation == 4 && writer.GetPixmap().GetPixelFormat().GetSamplesPerPixel() == 3)
+    {
+        unsigned int planar_configuration = options != NULL ? options->planar_configuration : 0;
+        planar.SetPlanarConfiguration(planar_configuration);
+        planar.SetInput(writer.GetPixmap());
+        if (planar_configuration > 1 || !planar.Change())
+        {
+            proc_resp.status = 0x09;
+            return proc_resp;
+        }
+
+        writer.SetPixmap(planar.PixmapToPixmapFilter::GetOutput());
+    }
+
+    // Preserve, strip or apply the ICC profile
+    gdcm::DataSet &ds = writer.GetFile().GetDataSet();
+    if (options != NULL && options->icc_profile == 1)
+    {
+        ds.Remove(gdcm::Tag(0x0028, 0x2000));
+        ds.Remove(gdcm::Tag(0x0028, 0x2002));
+    }
+    else if (options != NULL && options->icc_profile == 2 && !icc.empty() && icc[0].GetTag() == gdcm::Tag(0x0028, 0x2000))
+    {
+        // The output pixmap is owned by the reader or a filter of this function
+        const gdcm::ByteValue *bv = icc[0].GetByteValue();
+        gdcm::Pixmap &output = const_cast<gdcm::Pixmap &>(writer.GetPixmap());
+        if (bv == NULL || !apply_icc_profile(output, std::string(bv->GetPointer(), bv->GetLength())))
+        {
+            proc_resp.status = 0x0D;
+            return proc_resp;
+        }
+
+        ds.Remove(gdcm::Tag(0x0028, 0x2000));
+        gdcm::Attribute<0x0028, 0x2002> color_space = {"SRGB"};
+        ds.Replace(color_space.GetAsDataElement());
+    }
+    else
+    {
+        for (size_t i = 0; i < icc.size(); i++)
+            ds.Replace(icc[i]);
+    }
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x05;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Whether JPEG-LS options are set, GDCM keeps its own interleave mode and coding parameters.
+static bool has_jpegls_options(const OptionsStruct *options)
+{
+    return options != NULL && (options->jpegls_interleave_mode >= 0 || options->jpegls_t1 || options->jpegls_t2
+        || options->jpegls_t3 || options->jpegls_reset);
+}
+
+// Encode raw pixel data to JPEG-LS with CharLS, one fragment per frame, with the interleave mode
+// and the preset coding parameters of the options. 0 keeps the CharLS default of a parameter.
+static bool encode_jpegls(gdcm::Pixmap &image, int allow_error, const OptionsStruct *options, const gdcm::TransferSyntax &ts)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    const gdcm::ByteValue *bv = image.GetDataElement().GetByteValue();
+    unsigned int samples = pf.GetSamplesPerPixel();
+    if (bv == NULL || (pf.GetBitsAllocated() != 8 && pf.GetBitsAllocated() != 16) || (samples != 1 && samples != 3))
+        return false;
+
+    size_t pixels = (size_t)image.GetDimension(0) * image.GetDimension(1);
+    size_t frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    size_t bytes = pf.GetBitsAllocated() / 8;
+    size_t frame_size = pixels * samples * bytes;
+    if (frame_size == 0 || frame_size * frames > bv->GetLength())
+        return false;
+
+    // CharLS takes separated planes without interleaving, interleaved samples otherwise
+    int mode = samples == 1 ? 0 : (options->jpegls_interleave_mode >= 0 ? options->jpegls_interleave_mode : 2);
+    bool planar = samples == 3 && image.GetPlanarConfiguration() == 1;
+    bool reorder = samples == 3 && planar != (mode == 0);
+
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> sq = new gdcm::SequenceOfFragments;
+    std::vector<char> source(frame_size);
+    std::vector<char> encoded(frame_size * 2 + 1024);
+    for (size_t i = 0; i < frames; i++)
+    {
+        const char *frame = bv->GetPointer() + i * frame_size;
+        if (!reorder)
+            memcpy(source.data(), frame, frame_size);
+        for (size_t p = 0; reorder && p < pixels; p++)
+            for (unsigned int k = 0; k < 3; k++)
+                memcpy(&source[(planar ? p * 3 + k : k * pixels + p) * bytes], frame + (planar ? k * pixels + p : p * 3 + k) * bytes, bytes);
+
+        JlsParameters params = JlsParameters();
+        params.width = (int)image.GetDimension(0);
+        params.height = (int)image.GetDimension(1);
+        params.bitsPerSample = pf.GetBitsStored();
+        params.components = (int)samples;
+        params.allowedLossyError = allow_error;
+        params.interleaveMode = static_cast<CharlsInterleaveModeType>(mode);
+        params.custom.Threshold1 = (int)options->jpegls_t1;
+        params.custom.Threshold2 = (int)options->jpegls_t2;
+        params.custom.Threshold3 = (int)options->jpegls_t3;
+        params.custom.ResetValue = (int)options->jpegls_reset;
+
+        size_t written = 0;
+        CharlsApiResultType result = JpegLsEncode(encoded.data(), encoded.size(), &written, source.data(), source.size(), &params, NULL);
+        if (result != CharlsApiResultType::OK)
+            return false;
+
+        // Fragments have an even length
+        if (written % 2 == 1)
+            encoded[written++] = '\0';
+        gdcm::Fragment fragment;
+        fragment.SetByteValue(encoded.data(), (uint32_t)written);
+        sq->AddFragment(fragment);
+    }
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(gdcm::VR::OB);
+    encapsulated.SetValue(*sq);
+    encapsulated.SetVLToUndefined();
+    image.SetDataElement(encapsulated);
+    image.SetTransferSyntax(ts);
+    image.SetPlanarConfiguration(0);
+    return true;
+}
+
+// Whether JPEG 2000 options GDCM does not expose are set, the codeblock size or the progression order.
+static bool has_j2k_coding_options(const OptionsStruct *options)
+{
+    return options != NULL && (options->j2k_codeblock_width || options->j2k_codeblock_height
+        || options->j2k_progression_order >= 0);
+}
+
+// Codestream written by OpenJPEG in memory.
+struct J2kBuffer
+{
+    std::vector<char> data;
+    size_t position;
+};
+
+static OPJ_SIZE_T j2k_write(void *buffer, OPJ_SIZE_T size, void *user)
+{
+    J2kBuffer *out = static_cast<J2kBuffer *>(user);
+    if (out->position + size > out->data.size())
+        out->data.resize(out->position + size);
+    if (size > 0)
+        memcpy(&out->data[out->position], buffer, size);
+    out->position += size;
+    return size;
+}
+
+static OPJ_OFF_T j2k_skip(OPJ_OFF_T size, void *user)
+{
+    J2kBuffer *out = static_cast<J2kBuffer *>(user);
+    if ((OPJ_OFF_T)out->position + size < 0)
+        return -1;
+    out->position += size;
+    if (out->position > out->data.size())
+        out->data.resize(out->position);
+    return size;
+}
+
+static OPJ_BOOL j2k_seek(OPJ_OFF_T position, void *user)
+{
+    J2kBuffer *out = static_cast<J2kBuffer *>(user);
+    if (position < 0)
+        return OPJ_FALSE;
+    out->position = (size_t)position;
+    if (out->position > out->data.size())
+        out->data.resize(out->position);
+    return OPJ_TRUE;
+}
+
+// Encode raw pixel data to JPEG 2000 with OpenJPEG, one codestream fragment per frame, with the
+// parameters of the GDCM codec plus the codeblock size and the progression order of the options.
+static bool encode_j2k(gdcm::Pixmap &image, char is_lossy, const int quality[3], char irreversible, const OptionsStruct *options, const gdcm::TransferSyntax &ts)
+{
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    const gdcm::ByteValue *bv = image.GetDataElement().GetByteValue();
+    unsigned int samples = pf.GetSamplesPerPixel();
+    if (bv == NULL || (pf.GetBitsAllocated() != 8 && pf.GetBitsAllocated() != 16) || (samples != 1 && samples != 3))
+        return false;
+
+    unsigned int width = image.GetDimension(0);
+    unsigned int height = image.GetDimension(1);
+    size_t pixels = (size_t)width * height;
+    size_t frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    size_t bytes = pf.GetBitsAllocated() / 8;
+    size_t frame_size = pixels * samples * bytes;
+    if (frame_size == 0 || frame_size * frames > bv->GetLength())
+        return false;
+
+    bool planar = samples == 3 && image.GetPlanarConfiguration() == 1;
+    bool is_signed = pf.GetPixelRepresentation() == 1;
+    unsigned int precision = pf.GetBitsStored();
+
+    // One lossless layer, or one layer per quality as the GDCM codec
+    opj_cparameters_t params;
+    opj_set_default_encoder_parameters(&params);
+    params.tcp_numlayers = 1;
+    params.tcp_rates[0] = 0;
+    params.cp_disto_alloc = 1;
+    if (is_lossy)
+    {
+        for (int i = 0; i < 3; i++)
+        {
+            if (!quality[i])
+                continue;
+            params.tcp_distoratio[i] = static_cast<float>(quality[i]);
+            params.tcp_numlayers = i + 1;
+            params.cp_disto_alloc = 0;
+            params.cp_fixed_quality = 1;
+        }
+        params.irreversible = irreversible ? 1 : 0;
+    }
+
+    // OpenJPEG needs at least one sample in the lowest resolution
+    if (options->j2k_resolutions)
+        params.numresolution = (int)options->j2k_resolutions;
+    else
+        while (params.numresolution > 1 && (std::min(width, height) >> (params.numresolution - 1)) == 0)
+            params.numresolution--;
+    if (options->j2k_tile_width && options->j2k_tile_height)
+    {
+        params.tile_size_on = OPJ_TRUE;
+        params.cp_tdx = (int)options->j2k_tile_width;
+        params.cp_tdy = (int)options->j2k_tile_height;
+    }
+    if (options->j2k_codeblock_width)
+        params.cblockw_init = (int)options->j2k_codeblock_width;
+    if (options->j2k_codeblock_height)
+        params.cblockh_init = (int)options->j2k_codeblock_height;
+    if (options->j2k_progression_order >= 0)
+        params.prog_order = static_cast<OPJ_PROG_ORDER>(options->j2k_progression_order);
+    params.tcp_mct = image.GetPhotometricInterpretation() == gdcm::PhotometricInterpretation::RGB ? 1 : 0;
+
+    std::vector<opj_image_cmptparm_t> components(samples);
+    for (unsigned int k = 0; k < samples; k++)
+    {
+        memset(&components[k], 0, sizeof(opj_image_cmptparm_t));
+        components[k].dx = 1;
+        components[k].dy = 1;
+        components[k].w = width;
+        components[k].h = height;
+        components[k].prec = precision;
+        components[k].sgnd = is_signed ? 1 : 0;
+    }
+
+    gdcm::SmartPointer<gdcm::SequenceOfFragments> sq = new gdcm::SequenceOfFragments;
+    for (size_t i = 0; i < frames; i++)
+    {
+        opj_image_t *j2k_image = opj_image_create(samples, components.data(), samples == 1 ? OPJ_CLRSPC_GRAY : OPJ_CLRSPC_SRGB);
+        if (j2k_image == NULL)
+            return false;
+        j2k_image->x1 = width;
+        j2k_image->y1 = height;
+
+        // Samples to 32 bits, the bits above the stored ones are dropped
+        const char *frame = bv->GetPointer() + i * frame_size;
+        unsigned int shift = 32 - precision;
+        for (unsigned int k = 0; k < samples; k++)
+        {
+            for (size_t p = 0; p < pixels; p++)
+            {
+                const char *sample = frame + (planar ? k * pixels + p : p * samples + k) * bytes;
+                uint16_t value = (unsigned char)sample[0];
+                if (bytes == 2)
+                    value |= (uint16_t)((unsigned char)sample[1] << 8);
+                uint32_t bits = (uint32_t)value << shift;
+                j2k_image->comps[k].data[p] = is_signed ? (OPJ_INT32)((int32_t)bits >> shift) : (OPJ_INT32)(bits >> shift);
+            }
+        }
+
+        J2kBuffer out = {std::vector<char>(), 0};
+        opj_cparameters_t frame_params = params;
+        opj_codec_t *codec = opj_create_compress(OPJ_CODEC_J2K);
+        opj_stream_t *stream = opj_stream_create(OPJ_J2K_STREAM_CHUNK_SIZE, OPJ_FALSE);
+        bool encoded = codec != NULL && stream != NULL;
+        if (encoded)
+        {
+            opj_stream_set_user_data(stream, &out, NULL);
+            opj_stream_set_write_function(stream, j2k_write);
+            opj_stream_set_skip_function(stream, j2k_skip);
+            opj_stream_set_seek_function(stream, j2k_seek);
+            encoded = opj_setup_encoder(codec, &frame_params, j2k_image)
+                && opj_start_compress(codec, j2k_image, stream)
+                && opj_encode(codec, stream)
+                && opj_end_compress(codec, stream);
+        }
+        if (stream != NULL)
+            opj_stream_destroy(stream);
+        if (codec != NULL)
+            opj_destroy_codec(codec);
+        opj_image_destroy(j2k_image);
+        if (!encoded || out.data.empty())
+            return false;
+
+        // Fragments have an even length
+        if (out.data.size() % 2 == 1)
+            out.data.push_back('\0');
+        gdcm::Fragment fragment;
+        fragment.SetByteValue(out.data.data(), (uint32_t)out.data.size());
+        sq->AddFragment(fragment);
+    }
+
+    gdcm::DataElement encapsulated(gdcm::Tag(0x7fe0, 0x0010));
+    encapsulated.SetVR(gdcm::VR::OB);
+    encapsulated.SetValue(*sq);
+    encapsulated.SetVLToUndefined();
+    image.SetDataElement(encapsulated);
+    image.SetTransferSyntax(ts);
+    image.SetPlanarConfiguration(0);
+    return true;
+}
+
+ProcResp change_transfer(
+    int transfer_syntax,
+    char is_lossy,
+    int quality1,
+    int quality2,
+    int quality3,
+    char irreversible,
+    int allow_error,
+    const OptionsStruct *options,
+    std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    bool is_jpeg = false;
+    bool is_jpegls = false;
+    bool is_j2k = false;
+    bool derive = false;
+    const char *lossy_method = NULL;
+
+    // Header only, never decode the pixel data
+    if (options != NULL && options->header_only)
+    {
+        gdcm::Reader header_reader;
+        header_reader.SetStream(dicomInput);
+        if (!header_reader.Read())
+        {
+            proc_resp.status = 0x01;
+            return proc_resp;
+        }
+        return change_header_transfer(transfer_syntax, header_reader.GetFile(), options);
+    }
+
+    gdcm::PixmapReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        // Float Pixel Data is not an image for GDCM, pass it through raw syntaxes
+        if (has_float_pixel_data(reader.GetFile().GetDataSet()))
+        {
+            if (transfer_syntax != 1 && transfer_syntax != 2 && transfer_syntax != 21)
+            {
+                proc_resp.status = 0x07;
+                return proc_resp;
+            }
+            return change_header_transfer(transfer_syntax, reader.GetFile(), options);
+        }
+
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+    gdcm::Pixmap &image = reader.GetPixmap();
+
+    // 1 bit pixel data (e.g. Segmentation) keeps its packed bytes between little endian raw
+    // syntaxes, only the data set is transcoded
+    bool target_raw_le = transfer_syntax == 1 || transfer_syntax == 2 || transfer_syntax == 21;
+    if (image.GetPixelFormat().GetBitsAllocated() == 1 && target_raw_le
+        && !reader.GetFile().GetHeader().GetDataSetTransferSyntax().IsEncapsulated())
+        return change_header_transfer(transfer_syntax, reader.GetFile(), options);
+
+    // Make sure the DICOM attributes follows PS 3.6 rules,
+    // when converting to an explicit little transfer syntax.
+    if (transfer_syntax != 1 && !make_explicit(reader.GetFile(), options))
+    {
+        proc_resp.status = 0x02;
+        return proc_resp;
+    }
+
+    if (!has_encoder(transfer_syntax))
+    {
+        proc_resp.status = 0x0A;
+        return proc_resp;
+    }
+
+    gdcm::JPEGCodec jpegcodec;
+    gdcm::JPEGLSCodec jpeglscodec;
+    gdcm::JPEG2000Codec j2kcodec;
+    gdcm::ImageChangeTransferSyntax change;
+
+    switch (transfer_syntax)
+    {
+    case 1:
+        change.SetTransferSyntax(gdcm::TransferSyntax::ImplicitVRLittleEndian);
+        break;
+    case 2:
+        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+        break;
+    case 3:
+        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRBigEndian);
+        break;
+    case 4:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGBaselineProcess1);
+        is_jpeg = true;
+        // Always lossy
+        derive = true;
+        break;
+    case 5:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGExtendedProcess2_4);
+        is_jpeg = true;
+        // Always lossy
+        derive = true;
+        break;
+    case 6:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLosslessProcess14);
+        is_jpeg = true;
+        break;
+    case 7:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLosslessProcess14_1);
+        is_jpeg = true;
+        break;
+    case 8:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLSLossless);
+        is_jpegls = true;
+        break;
+    case 9:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEGLSNearLossless);
+        is_jpegls = true;
+        break;
+    case 10:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Lossless);
+        is_j2k = true;
+        break;
+    case 11:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000);
+        is_j2k = true;
+        break;
+    case 12:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Part2Lossless);
+        is_j2k = true;
+        break;
+    case 13:
+        change.SetTransferSyntax(gdcm::TransferSyntax::JPEG2000Part2);
+        is_j2k = true;
+        break;
+    case 14:
+        change.SetTransferSyntax(gdcm::TransferSyntax::RLELossless);
+        break;
+    case 21:
+        change.SetTransferSyntax(gdcm::TransferSyntax::DeflatedExplicitVRLittleEndian);
+        break;
+    case 23:
+        // Written as Explicit VR Little Endian, encapsulated after the last stage
+        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+        break;
+    default:
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    // CharLS encodes the raw output of the change with the JPEG-LS options
+    const gdcm::TransferSyntax target_ts = change.GetTransferSyntax();
+    bool jpegls_options = is_jpegls && has_jpegls_options(options);
+    if (jpegls_options)
+        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+    // Same with OpenJPEG for the JPEG 2000 options GDCM does not expose
+    bool j2k_options = is_j2k && has_j2k_coding_options(options);
+    if (j2k_options)
+        change.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+    // Lossy Image Compression Method
+    if (is_jpeg)
+        lossy_method = "ISO_10918_1";
+    if (is_jpegls)
+        lossy_method = "ISO_14495_1";
+    if (is_j2k)
+        lossy_method = "ISO_15444_1";
+
+    // jpeg lossy
+    if (is_lossy && is_jpeg)
+    {
+        jpegcodec.SetLossless(false);
+        if (quality1)
+            jpegcodec.SetQuality(static_cast<double>(quality1));
+        change.SetUserCodec(&jpegcodec);
+        derive = true;
+    }
+
+    // jpegls lossy
+    if (is_lossy && is_jpegls)
+    {
+        jpeglscodec.SetLossless(false);
+        if (allow_error)
+            jpeglscodec.SetLossyError(allow_error);
+        if (!jpegls_options)
+            change.SetUserCodec(&jpeglscodec);
+        derive = true;
+    }
+
+    // jk2 lossy
+    if (is_lossy && is_j2k)
+    {
+        j2kcodec.SetLossyFlag(true);
+        if (quality1)
+            j2kcodec.SetQuality(0, static_cast<double>(quality1));
+        if (quality2)
+            j2kcodec.SetQuality(1, static_cast<double>(quality2));
+        if (quality3)
+            j2kcodec.SetQuality(2, static_cast<double>(quality3));
+        if (irreversible)
+        {
+            j2kcodec.SetReversible(false);
+        }
+        else
+        {
+            j2kcodec.SetReversible(true);
+        }
+        if (!j2k_options)
+            change.SetUserCodec(&j2kcodec);
+        derive = true;
+    }
+
+    // j2k resolutions & tiles
+    if (is_j2k && options != NULL && (options->j2k_resolutions || options->j2k_tile_width || options->j2k_tile_height))
+    {
+        if (!is_lossy)
+        {
+            j2kcodec.SetLossyFlag(false);
+            j2kcodec.SetReversible(true);
+        }
+        if (options->j2k_resolutions)
+            j2kcodec.SetNumberOfResolutions(options->j2k_resolutions);
+        if (options->j2k_tile_width && options->j2k_tile_height)
+            j2kcodec.SetTileSize(options->j2k_tile_width, options->j2k_tile_height);
+        if (!j2k_options)
+            change.SetUserCodec(&j2kcodec);
+    }
+
+    // Keep overlays stored in the pixel data
+    if (options == NULL || options->overlays == 0)
+        extract_overlays(image, reader.GetFile().GetDataSet());
+
+    // Codecs support a limited bit depth, only raw syntaxes take anything
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+    bool is_raw = transfer_syntax <= 3 || transfer_syntax == 21 || transfer_syntax == 23;
+    bool is_rle = transfer_syntax == 14;
+    if ((!is_raw && (pf.GetBitsAllocated() == 1 || pf.GetBitsAllocated() > (is_rle ? 32 : 16)))
+        || (transfer_syntax == 23 && pf.GetBitsAllocated() == 1))
+    {
+        proc_resp.status = 0x07;
+        return proc_resp;
+    }
+
+    // The RLE encoder splits interleaved samples into one segment per byte plane,
+    // separated planes must be interleaved first or multi-frame color segments get mixed
+    const gdcm::Pixmap *input = &image;
+    gdcm::ImageChangePlanarConfiguration interleave;
+    if (is_rle && pf.GetSamplesPerPixel() > 1 && image.GetPlanarConfiguration() == 1)
+    {
+        interleave.SetPlanarConfiguration(0);
+        interleave.SetInput(image);
+        if (!interleave.Change())
+        {
+            proc_resp.status = 0x04;
+            return proc_resp;
+        }
+        input = &interleave.PixmapToPixmapFilter::GetOutput();
+    }
+
+    // Report progress by frame
+    gdcm::SmartPointer<ProgressObserver> progress = new ProgressObserver;
+    progress->options = options;
+    progress->total = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    change.AddObserver(gdcm::ProgressEvent(), progress);
+
+    change.SetInput(*input);
+    if (!change.Change())
+    {
+        proc_resp.status = 0x04;
+        return proc_resp;
+    }
+    progress->Report(progress->total);
+
+    // The output pixmap is owned by the change
+    if (jpegls_options)
+    {
+        gdcm::Pixmap &output = const_cast<gdcm::Pixmap &>(change.PixmapToPixmapFilter::GetOutput());
+        if (!encode_jpegls(output, is_lossy ? allow_error : 0, options, target_ts))
+        {
+            proc_resp.status = 0x04;
+            return proc_resp;
+        }
+    }
+    if (j2k_options)
+    {
+        gdcm::Pixmap &output = const_cast<gdcm::Pixmap &>(change.PixmapToPixmapFilter::GetOutput());
+        const int quality[3] = {quality1, quality2, quality3};
+        if (!encode_j2k(output, is_lossy, quality, irreversible, options, target_ts))
+        {
+            proc_resp.status = 0x04;
+            return proc_resp;
+        }
+    }
+
+    // Cancelled while changing, the caller reports it
+    if (is_cancelled(options))
+    {
+        proc_resp.status = 0x00;
+        return proc_resp;
+    }
+
+    // Keep the original SOP Instance UID, derivation removes it
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    std::string sop_instance_uid;
+    if (ds.FindDataElement(gdcm::Tag(0x0008, 0x0018)))
+    {
+        gdcm::Attribute<0x0008, 0x0018> at;
+        at.SetFromDataSet(ds);
+        sop_instance_uid = at.GetValue();
+    }
+
+    // (0008,2111) Derivation Description, given by the caller or generated for lossy
+    std::string description;
+    if (options != NULL && options->derivation_note != NULL)
+    {
+        description = options->derivation_note;
+    }
+    else
+    {
+        const char *source_uid = reader.GetFile().GetHeader().GetDataSetTransferSyntax().GetString();
+        std::ostringstream text;
+        text << "Lossy compression from " << (source_uid != NULL ? source_uid : "unknown")
+             << " to " << target_ts.GetString();
+        if (is_jpeg && quality1)
+            text << ", quality " << quality1;
+        if (is_jpegls && allow_error)
+            text << ", allowed error " << allow_error;
+        if (is_j2k && (quality1 || quality2 || quality3))
+            text << ", quality " << quality1 << "/" << quality2 << "/" << quality3;
+        if (is_j2k && irreversible)
+            text << ", irreversible";
+        description = text.str();
+    }
+
+    // Derive image only for lossy
+    if (derive)
+    {
+        if (!gdcm::derives(reader.GetFile(), change.PixmapToPixmapFilter::GetOutput(), lossy_method, description.c_str()))
+        {
+            proc_resp.status = 0x05;
+            return proc_resp;
+        }
+    }
+    else if (options != NULL && options->derivation_note != NULL)
+    {
+        gdcm::Attribute<0x0008, 0x2111> at;
+        at.SetValue(description);
+        ds.Replace(at.GetAsDataElement());
+    }
+
+    // Regenerate or restore SOP Instance UID
+    if (options != NULL && options->regenerate_sop_instance_uid)
+    {
+        gdcm::UIDGenerator uid;
+        gdcm::Attribute<0x0008, 0x0018> at;
+        at.SetValue(uid.Generate());
+        ds.Replace(at.GetAsDataElement());
+    }
+    else if (!sop_instance_uid.empty())
+    {
+        gdcm::Attribute<0x0008, 0x0018> at;
+        at.SetValue(sop_instance_uid);
+        ds.Replace(at.GetAsDataElement());
+    }
+
+    gdcm::PixmapWriter writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+
+    gdcm::File &file = writer.GetFile();
+    gdcm::FileMetaInformation &fmi = file.GetHeader();
+    fmi.Remove(gdcm::Tag(0x0002, 0x0100)); //  '   '    ' // PrivateInformationCreatorUID
+    fmi.Remove(gdcm::Tag(0x0002, 0x0102)); //  '   '    ' // PrivateInformation
+    fmi.Remove(gdcm::Tag(0x0002, 0x0003)); // Filled again from SOP Instance UID
+
+    const gdcm::Pixmap &pixout = change.PixmapToPixmapFilter::GetOutput();
+    writer.SetPixmap(pixout);
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x06;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Decode bytes of a character set to UTF-8, appending to out.
+static bool decode_charset(const char *charset, const std::string &in, std::string &out)
+{
+    if (in.empty())
+        return true;
+#ifdef _WIN32
+    // No iconv available
+    return false;
+#else
+    iconv_t cd = iconv_open("UTF-8", charset);
+    if (cd == (iconv_t)-1)
+        return false;
+
+    std::vector<char> buffer(in.size() * 4 + 4);
+    char *inbuf = const_cast<char *>(in.data());
+    size_t inleft = in.size();
+    char *outbuf = buffer.data();
+    size_t outleft = buffer.size();
+    size_t ret = iconv(cd, &inbuf, &inleft, &outbuf, &outleft);
+    iconv_close(cd);
+
+    if (ret == (size_t)-1 || inleft > 0)
+        return false;
+
+    out.append(buffer.data(), buffer.size() - outleft);
+    return true;
+#endif
+}
+
+// Character set of the G1 (high bit) bytes for a (0008,0005) defined term, NULL for ASCII.
+static const char *g1_charset(std::string term)
+{
+    if (term.compare(0, 7, "ISO_IR ") == 0)
+        term = term.substr(7);
+    else if (term.compare(0, 12, "ISO 2022 IR ") == 0)
+        term = term.substr(12);
+
+    if (term == "100") return "ISO-8859-1";
+    if (term == "101") return "ISO-8859-2";
+    if (term == "109") return "ISO-8859-3";
+    if (term == "110") return "ISO-8859-4";
+    if (term == "144") return "ISO-8859-5";
+    if (term == "127") return "ISO-8859-6";
+    if (term == "126") return "ISO-8859-7";
+    if (term == "138") return "ISO-8859-8";
+    if (term == "148") return "ISO-8859-9";
+    if (term == "203") return "ISO-8859-15";
+    if (term == "166") return "TIS-620";
+    if (term == "13") return "SHIFT_JIS";
+    if (term == "149") return "EUC-KR";
+    if (term == "58") return "EUC-CN";
+    return NULL;
+}
+
+// Decode a string value to UTF-8, following ISO 2022 code extensions (PS3.5 6.1.2.5).
+static bool decode_value(const std::vector<std::string> &charsets, const std::string &in, std::string &out)
+{
+    const std::string first = charsets.empty() ? "" : charsets[0];
+
+    // Character sets without code extensions
+    if (first == "ISO_IR 192")
+    {
+        out = in;
+        return true;
+    }
+    if (first == "GB18030" || first == "GBK")
+        return decode_charset(first.c_str(), in, out);
+
+    // G0: 0 ASCII, 1 JIS X 0208, 2 JIS X 0212
+    const char *initial_g1 = g1_charset(first);
+    const char *g1 = initial_g1;
+    int g0 = 0;
+
+    std::string run;
+    const char *run_charset = NULL;
+
+    for (size_t i = 0; i < in.size(); i++)
+    {
+        unsigned char c = in[i];
+
+        // Escape sequences switch G0 or G1
+        if (c == 0x1B)
+        {
+            std::string esc = in.substr(i + 1, 3);
+            size_t len;
+            if (esc.compare(0, 2, "(B") == 0 || esc.compare(0, 2, "(J") == 0)
+                g0 = 0, len = 2;
+            else if (esc.compare(0, 2, "$B") == 0 || esc.compare(0, 2, "$@") == 0)
+                g0 = 1, len = 2;
+            else if (esc.compare(0, 3, "$(D") == 0)
+                g0 = 2, len = 3;
+            else if (esc.compare(0, 2, ")I") == 0)
+                g1 = "SHIFT_JIS", len = 2;
+            else if (esc.compare(0, 3, "$)C") == 0)
+                g1 = "EUC-KR", len = 3;
+            else if (esc.compare(0, 3, "$)A") == 0)
+                g1 = "EUC-CN", len = 3;
+            else if (esc.size() >= 2 && esc[0] == '-')
+            {
+                switch (esc[1])
+                {
+                case 'A': g1 = "ISO-8859-1"; break;
+                case 'B': g1 = "ISO-8859-2"; break;
+                case 'C': g1 = "ISO-8859-3"; break;
+                case 'D': g1 = "ISO-8859-4"; break;
+                case 'L': g1 = "ISO-8859-5"; break;
+                case 'G': g1 = "ISO-8859-6"; break;
+                case 'F': g1 = "ISO-8859-7"; break;
+                case 'H': g1 = "ISO-8859-8"; break;
+                case 'M': g1 = "ISO-8859-9"; break;
+                case 'b': g1 = "ISO-8859-15"; break;
+                case 'T': g1 = "TIS-620"; break;
+                default: return false;
+                }
+                len = 2;
+            }
+            else
+                return false;
+
+            i += len;
+            continue;
+        }
+
+        // Bytes of the next run and its character set
+        std::string bytes;
+        const char *charset;
+        if (c >= 0x80)
+        {
+            if (g1 == NULL)
+                return false;
+            charset = g1;
+            bytes += (char)c;
+        }
+        else if (g0 != 0)
+        {
+            if (i + 1 >= in.size())
+                return false;
+            charset = "EUC-JP";
+            if (g0 == 2)
+                bytes += (char)0x8F;
+            bytes += (char)(c | 0x80);
+            bytes += (char)(in[++i] | 0x80);
+        }
+        else
+        {
+            charset = NULL;
+            bytes += (char)c;
+        }
+
+        if (charset != run_charset)
+        {
+            if (run_charset == NULL)
+                out += run;
+            else if (!decode_charset(run_charset, run, out))
+                return false;
+            run.clear();
+            run_charset = charset;
+        }
+        run += bytes;
+
+        // Delimiters reset G1 to the initial character set
+        if (g0 == 0 && (c == '^' || c == '=' || c == '\\' || c == '\r' || c == '\n' || c == '\t' || c == '\f'))
+            g1 = initial_g1;
+    }
+
+    if (run_charset == NULL)
+        out += run;
+    else if (!decode_charset(run_charset, run, out))
+        return false;
+
+    return true;
+}
+
+// Re-encode the string values of a data set to UTF-8, nested items included.
+static bool convert_dataset_charset(gdcm::DataSet &ds, std::vector<std::string> charsets)
+{
+    // Items may define their own character set
+    if (ds.FindDataElement(gdcm::Tag(0x0008, 0x0005)))
+    {
+        const gdcm::ByteValue *bv = ds.GetDataElement(gdcm::Tag(0x0008, 0x0005)).GetByteValue();
+        charsets.clear();
+        if (bv != NULL)
+        {
+            std::string value(bv->GetPointer(), bv->GetLength());
+            size_t start = 0;
+            while (true)
+            {
+                size_t end = value.find('\\', start);
+                std::string term = value.substr(start, end == std::string::npos ? std::string::npos : end - start);
+                size_t first = term.find_first_not_of(' ');
+                size_t last = term.find_last_not_of(std::string(" \0", 2));
+                charsets.push_back(first == std::string::npos ? "" : term.substr(first, last - first + 1));
+                if (end == std::string::npos)
+                    break;
+                start = end + 1;
+            }
+        }
+    }
+
+    const gdcm::Dicts &dicts = gdcm::Global::GetInstance().GetDicts();
+    std::vector<gdcm::DataElement> changed;
+
+    for (gdcm::DataSet::ConstIterator it = ds.Begin(); it != ds.End(); ++it)
+    {
+        gdcm::DataElement de = *it;
+        gdcm::VR vr = de.GetVR();
+        if (vr == gdcm::VR::INVALID || vr == gdcm::VR::UN)
+            vr = dicts.GetDictEntry(de.GetTag()).GetVR();
+
+        if (vr == gdcm::VR::SQ)
+        {
+            gdcm::SmartPointer<gdcm::SequenceOfItems> sq = de.GetValueAsSQ();
+            if (!sq)
+                continue;
+            for (gdcm::SequenceOfItems::SizeType i = 1; i <= sq->GetNumberOfItems(); i++)
+            {
+                if (!convert_dataset_charset(sq->GetItem(i).GetNestedDataSet(), charsets))
+                    return false;
+            }
+            de.SetValue(*sq);
+            de.SetVLToUndefined();
+            changed.push_back(de);
+        }
+        else if (vr & (gdcm::VR::SH | gdcm::VR::LO | gdcm::VR::ST | gdcm::VR::LT | gdcm::VR::UC | gdcm::VR::UT | gdcm::VR::PN))
+        {
+            const gdcm::ByteValue *bv = de.GetByteValue();
+            if (bv == NULL || de.GetTag().IsPrivateCreator())
+                continue;
+
+            std::string value(bv->GetPointer(), bv->GetLength());
+            std::string utf8;
+            if (!decode_value(charsets, value, utf8))
+                return false;
+
+            // Even length, padded with space
+            if (utf8.size() % 2 == 1)
+                utf8 += ' ';
+            de.SetByteValue(utf8.c_str(), (uint32_t)utf8.size());
+            changed.push_back(de);
+        }
+    }
+
+    for (size_t i = 0; i < changed.size(); i++)
+        ds.Replace(changed[i]);
+
+    if (ds.FindDataElement(gdcm::Tag(0x0008, 0x0005)))
+    {
+        gdcm::DataElement de(gdcm::Tag(0x0008, 0x0005));
+        de.SetVR(gdcm::VR::CS);
+        de.SetByteValue("ISO_IR 192", 10);
+        ds.Replace(de);
+    }
+
+    return true;
+}
+
+// Re-encode (0008,0005) Specific Character Set and string values to UTF-8 (ISO_IR 192).
+// Set or replace the elements given by the caller. The VR comes from the dictionary, private and
+// unknown tags are written as UN. Numbers of binary VRs are encoded from their string form, other
+// binary values, sequences, the pixel data and the file meta information are rejected.
+static ProcResp override_tags(const OptionsStruct *options, std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    const gdcm::Dicts &dicts = gdcm::Global::GetInstance().GetDicts();
+    gdcm::StringFilter filter;
+    filter.SetFile(reader.GetFile());
+    for (size_t i = 0; i < options->tag_overrides_len; i++)
+    {
+        gdcm::Tag tag((uint16_t)(options->tag_overrides[i] >> 16), (uint16_t)(options->tag_overrides[i] & 0xFFFF));
+        std::string value = options->tag_override_values[i];
+
+        gdcm::VR vr = tag.IsPrivate() ? gdcm::VR::UN : dicts.GetDictEntry(tag).GetVR();
+        if (vr == gdcm::VR::INVALID)
+            vr = gdcm::VR::UN;
+        else if (vr == gdcm::VR::US_SS)
+            vr = gdcm::VR::US;
+        if (tag.GetGroup() == 0x0002 || tag == gdcm::Tag(0x7fe0, 0x0010) || vr == gdcm::VR::SQ
+            || (vr & (gdcm::VR::OB | gdcm::VR::OW | gdcm::VR::OD | gdcm::VR::OF | gdcm::VR::OL)))
+        {
+            proc_resp.status = 0x04;
+            return proc_resp;
+        }
+
+        if (vr & (gdcm::VR::US | gdcm::VR::SS | gdcm::VR::UL | gdcm::VR::SL | gdcm::VR::FL | gdcm::VR::FD | gdcm::VR::AT))
+            value = filter.FromString(tag, value.c_str(), value.size());
+        else if (value.size() % 2 == 1)
+            value += vr == gdcm::VR::UI || vr == gdcm::VR::UN ? '\0' : ' ';
+
+        gdcm::DataElement de(tag);
+        de.SetVR(vr);
+        de.SetByteValue(value.c_str(), (uint32_t)value.size());
+        ds.Replace(de);
+    }
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+ProcResp convert_charset(std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (ds.FindDataElement(gdcm::Tag(0x0008, 0x0005)))
+    {
+        if (!convert_dataset_charset(ds, std::vector<std::string>()))
+        {
+            proc_resp.status = 0x02;
+            return proc_resp;
+        }
+    }
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Set (0002,0012) Implementation Class UID and (0002,0013) Implementation Version Name,
+// empty values are left as written by GDCM.
+ProcResp set_implementation(
+    const std::string &class_uid,
+    const std::string &version_name,
+    std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    gdcm::FileMetaInformation &fmi = reader.GetFile().GetHeader();
+    if (!class_uid.empty())
+    {
+        gdcm::DataElement de(gdcm::Tag(0x0002, 0x0012));
+        de.SetVR(gdcm::VR::UI);
+        std::string value = class_uid;
+        if (value.size() % 2 == 1)
+            value += '\0';
+        de.SetByteValue(value.c_str(), (uint32_t)value.size());
+        fmi.Replace(de);
+    }
+    if (!version_name.empty())
+    {
+        gdcm::DataElement de(gdcm::Tag(0x0002, 0x0013));
+        de.SetVR(gdcm::VR::SH);
+        std::string value = version_name;
+        if (value.size() % 2 == 1)
+            value += ' ';
+        de.SetByteValue(value.c_str(), (uint32_t)value.size());
+        fmi.Replace(de);
+    }
+
+    // Recompute the group length, then write the header as is
+    fmi.FillFromDataSet(reader.GetFile().GetDataSet());
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Restore (0028,0030) Pixel Spacing and (0018,0050) Slice Thickness of the source, the pixel
+// spacing is scaled when the rows or columns changed.
+ProcResp restore_spatial(const gdcm::DataSet &spatial, std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (spatial.FindDataElement(gdcm::Tag(0x0028, 0x0030)))
+    {
+        gdcm::Attribute<0x0028, 0x0030> spacing;
+        spacing.SetFromDataSet(spatial);
+
+        // Row spacing follows the rows, column spacing the columns
+        gdcm::Attribute<0x0028, 0x0010> source_rows, rows;
+        gdcm::Attribute<0x0028, 0x0011> source_columns, columns;
+        source_rows.SetFromDataSet(spatial);
+        source_columns.SetFromDataSet(spatial);
+        rows.SetFromDataSet(ds);
+        columns.SetFromDataSet(ds);
+        if (rows.GetValue() > 0 && source_rows.GetValue() > 0 && rows.GetValue() != source_rows.GetValue())
+            spacing.SetValue(spacing.GetValue(0) * source_rows.GetValue() / rows.GetValue(), 0);
+        if (columns.GetValue() > 0 && source_columns.GetValue() > 0 && columns.GetValue() != source_columns.GetValue())
+            spacing.SetValue(spacing.GetValue(1) * source_columns.GetValue() / columns.GetValue(), 1);
+
+        ds.Replace(spacing.GetAsDataElement());
+    }
+    if (spatial.FindDataElement(gdcm::Tag(0x0018, 0x0050)))
+        ds.Replace(spatial.GetDataElement(gdcm::Tag(0x0018, 0x0050)));
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Private creator of the block holding the pixel data hash.
+static const char PIXEL_HASH_CREATOR[] = "GDCM_CONV";
+
+// Element of the pixel data hash in group 0x0099, given the private creator slot.
+static gdcm::Tag pixel_hash_tag(uint16_t slot)
+{
+    return gdcm::Tag(0x0099, (uint16_t)((slot << 8) | 0x01));
+}
+
+// Private creator slot of the pixel data hash block, 0 when missing.
+static uint16_t find_pixel_hash_slot(const gdcm::DataSet &ds)
+{
+    for (uint16_t slot = 0x10; slot <= 0xFF; slot++)
+    {
+        gdcm::Tag tag(0x0099, slot);
+        if (!ds.FindDataElement(tag))
+            continue;
+        const gdcm::ByteValue *bv = ds.GetDataElement(tag).GetByteValue();
+        if (bv == NULL)
+            continue;
+        std::string creator(bv->GetPointer(), bv->GetLength());
+        creator.erase(creator.find_last_not_of(std::string(" \0", 2)) + 1);
+        if (creator == PIXEL_HASH_CREATOR)
+            return slot;
+    }
+    return 0;
+}
+
+// Store the hash of the decoded pixel data in the GDCM_CONV private block of group 0x0099.
+ProcResp set_pixel_hash(const char *hash, std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    // Reuse the block or take the first free creator slot
+    gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    uint16_t slot = find_pixel_hash_slot(ds);
+    for (uint16_t t = 0x10; slot == 0 && t <= 0xFF; t++)
+    {
+        if (ds.FindDataElement(gdcm::Tag(0x0099, t)))
+            continue;
+        std::string creator(PIXEL_HASH_CREATOR);
+        if (creator.size() % 2 == 1)
+            creator += ' ';
+        gdcm::DataElement de(gdcm::Tag(0x0099, t));
+        de.SetVR(gdcm::VR::LO);
+        de.SetByteValue(creator.c_str(), (uint32_t)creator.size());
+        ds.Insert(de);
+        slot = t;
+    }
+    if (slot == 0)
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    std::string value(hash);
+    if (value.size() % 2 == 1)
+        value += ' ';
+    gdcm::DataElement de(pixel_hash_tag(slot));
+    de.SetVR(gdcm::VR::LO);
+    de.SetByteValue(value.c_str(), (uint32_t)value.size());
+    ds.Replace(de);
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    writer.CheckFileMetaInformationOff();
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+// Read a string element of the file meta information, without padding.
+static std::string get_meta_string(const gdcm::FileMetaInformation &fmi, const gdcm::Tag &tag)
+{
+    std::string value;
+    if (fmi.FindDataElement(tag))
+    {
+        const gdcm::ByteValue *bv = fmi.GetDataElement(tag).GetByteValue();
+        if (bv != NULL)
+            value.assign(bv->GetPointer(), bv->GetLength());
+    }
+    value.erase(value.find_last_not_of(std::string(" \0", 2)) + 1);
+    return value;
+}
+
+// Reject what GDCM tolerates when built with GDCM_SUPPORT_BROKEN_IMPLEMENTATION: a missing preamble
+// or file meta information, a data set not encoded with the declared transfer syntax, odd value
+// lengths and inconsistent pixel attributes.
+static bool check_conformance(const std::string &src)
+{
+    if (src.size() < 132 || src.compare(128, 4, "DICM") != 0)
+        return false;
+
+    std::istringstream dicomInput(src);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    const gdcm::FileMetaInformation &fmi = reader.GetFile().GetHeader();
+    if (!fmi.FindDataElement(gdcm::Tag(0x0002, 0x0010)))
+        return false;
+    std::string declared = get_meta_string(fmi, gdcm::Tag(0x0002, 0x0010));
+    const char *used = fmi.GetDataSetTransferSyntax().GetString();
+    if (used == NULL || declared != used)
+        return false;
+
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    for (gdcm::DataSet::ConstIterator it = ds.Begin(); it != ds.End(); ++it)
+    {
+        const gdcm::VL &vl = it->GetVL();
+        if (!vl.IsUndefined() && vl.IsOdd())
+            return false;
+    }
+
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0100)) && ds.FindDataElement(gdcm::Tag(0x0028, 0x0101)))
+    {
+        gdcm::Attribute<0x0028, 0x0100> bits_allocated;
+        bits_allocated.SetFromDataSet(ds);
+        gdcm::Attribute<0x0028, 0x0101> bits_stored;
+        bits_stored.SetFromDataSet(ds);
+        if (bits_stored.GetValue() == 0 || bits_stored.GetValue() > bits_allocated.GetValue())
+            return false;
+        if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0102)))
+        {
+            gdcm::Attribute<0x0028, 0x0102> high_bit;
+            high_bit.SetFromDataSet(ds);
+            if (high_bit.GetValue() + 1 != bits_stored.GetValue())
+                return false;
+        }
+    }
+
+    return true;
+}
+
+// Rewrite a data set stored without the preamble and "DICM" prefix (NEMA / raw streams) as a
+// Part 10 file, the file meta information is filled from the data set.
+static bool normalize_raw_dataset(std::string &image, bool force)
+{
+    if (!force && image.size() >= 132 && image.compare(128, 4, "DICM") == 0)
+        return true;
+
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    gdcm::File &file = reader.GetFile();
+    gdcm::FileMetaInformation &fmi = file.GetHeader();
+    if (fmi.GetPreamble().IsEmpty())
+        fmi.GetPreamble().Create();
+    fmi.FillFromDataSet(file.GetDataSet());
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(file);
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Replace (0028,0004) with the interpretation told from the samples, see detect_photometric.
+static bool repair_photometric(std::string &image)
+{
+    std::istringstream dicomInput(image);
+    gdcm::PixmapReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    const gdcm::Pixmap &pixmap = reader.GetPixmap();
+    gdcm::PhotometricInterpretation::PIType detected = detect_photometric(pixmap);
+    if (detected == pixmap.GetPhotometricInterpretation())
+        return true;
+
+    gdcm::Attribute<0x0028, 0x0004> photometric;
+    photometric.SetValue(gdcm::PhotometricInterpretation::GetPIString(detected));
+    reader.GetFile().GetDataSet().Replace(photometric.GetAsDataElement());
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(reader.GetFile());
+    if (!writer.Write())
+        return false;
+
+    image = dicomOutput.str();
+    return true;
+}
+
+// Check that the pixel data is encoded as declared by (0002,0010): raw with a defined length for
+// native syntaxes, fragments for encapsulated ones, starting with the codestream marker of the codec.
+static bool check_output_encoding(const std::string &image)
+{
+    std::istringstream dicomInput(image);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+        return false;
+
+    const gdcm::FileMetaInformation &fmi = reader.GetFile().GetHeader();
+    const gdcm::TransferSyntax &ts = fmi.GetDataSetTransferSyntax();
+    const char *used = ts.GetString();
+    if (used == NULL || get_meta_string(fmi, gdcm::Tag(0x0002, 0x0010)) != used)
+        return false;
+
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+        return true;
+    const gdcm::DataElement &pixel_data = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+    const gdcm::SequenceOfFragments *sq = pixel_data.GetSequenceOfFragments();
+    if (!ts.IsEncapsulated())
+        return sq == NULL && !pixel_data.GetVL().IsUndefined();
+    if (sq == NULL || sq->GetNumberOfFragments() == 0)
+        return false;
+
+    const gdcm::ByteValue *bv = sq->GetFragment(0).GetByteValue();
+    if (bv == NULL || bv->GetLength() < 2)
+        return false;
+    const unsigned char *data = reinterpret_cast<const unsigned char *>(bv->GetPointer());
+
+    // JPEG and JPEG-LS start with SOI, JPEG 2000 and HTJ2K with SOC or a JP2 signature box
+    gdcm::JPEGCodec jpeg;
+    gdcm::JPEGLSCodec jpegls;
+    gdcm::JPEG2000Codec j2k;
+    if (jpeg.CanDecode(ts) || jpegls.CanDecode(ts))
+        return data[0] == 0xFF && data[1] == 0xD8;
+    if (j2k.CanDecode(ts))
+        return (data[0] == 0xFF && data[1] == 0x4F) || (bv->GetLength() >= 8 && memcmp(data + 4, "jP  ", 4) == 0);
+    return true;
+}
+
+// Initialize global state shared by all threads.
+void c_init()
+{
+    // Load dictionaries before any concurrent access
+    gdcm::Global::GetInstance();
+
+    // Capture warnings, see TraceScope
+    static TraceBuffer trace_buffer;
+    static std::ostream trace_stream(&trace_buffer);
+    gdcm::Trace::SetWarningStream(trace_stream);
+    gdcm::Trace::SetErrorStream(trace_stream);
+
+    gdcm::FileMetaInformation::SetImplementationVersionName("Idria Software");
+    gdcm::FileMetaInformation::SetSourceApplicationEntityTitle("PROTEUS");
+}
+
+// Remove private tags, keeping the blocks of the creators listed in the options.
+static bool strip_private_tags(const OptionsStruct *options, gdcm::DataSet &ds, gdcm::Anonymizer &anon)
+{
+    if (options->keep_private_creators_len == 0)
+        return anon.RemovePrivateTags();
+
+    std::vector<gdcm::Tag> tags;
+    for (gdcm::DataSet::ConstIterator it = ds.Begin(); it != ds.End(); ++it)
+    {
+        const gdcm::Tag &tag = it->GetTag();
+        if (!tag.IsPrivate())
+            continue;
+
+        // Creator of the block, or the creator itself
+        std::string creator;
+        if (tag.IsPrivateCreator())
+        {
+            const gdcm::ByteValue *bv = it->GetByteValue();
+            if (bv != NULL)
+                creator.assign(bv->GetPointer(), bv->GetLength());
+        }
+        else
+        {
+            creator = ds.GetPrivateCreator(tag);
+        }
+        creator.erase(creator.find_last_not_of(std::string(" \0", 2)) + 1);
+
+        bool keep = false;
+        for (size_t i = 0; i < options->keep_private_creators_len; i++)
+            keep = keep || creator == options->keep_private_creators[i];
+
+        if (!keep)
+            tags.push_back(tag);
+    }
+
+    for (size_t i = 0; i < tags.size(); i++)
+        ds.Remove(tags[i]);
+
+    return true;
+}
+
+// Anonymize incoming stream.
+ProcResp anonymize(
+    const OptionsStruct *options,
+    std::string &src)
+{
+    struct ProcResp proc_resp;
+
+    std::istringstream dicomInput(src);
+    std::ostringstream dicomOutput;
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+
+    if (!reader.Read())
+    {
+        proc_resp.status = 0x01;
+        return proc_resp;
+    }
+
+    gdcm::Anonymizer anon;
+    anon.SetFile(reader.GetFile());
+
+    if (options->remove_private_tags && !anon.RemovePrivateTags())
+    {
+        proc_resp.status = 0x02;
+        return proc_resp;
+    }
+
+    if (options->strip_private && !strip_private_tags(options, reader.GetFile().GetDataSet(), anon))
+    {
+        proc_resp.status = 0x02;
+        return proc_resp;
+    }
+
+    if (options->patient_name != NULL && !anon.Replace(gdcm::Tag(0x0010, 0x0010), options->patient_name))
+    {
+        proc_resp.status = 0x02;
+        return proc_resp;
+    }
+
+    if (options->regenerate_uids)
+    {
+        gdcm::UIDGenerator uid;
+        const gdcm::Tag uids[] = {
+            gdcm::Tag(0x0008, 0x0018), // SOP Instance UID
+            gdcm::Tag(0x0020, 0x000D), // Study Instance UID
+            gdcm::Tag(0x0020, 0x000E), // Series Instance UID
+            gdcm::Tag(0x0020, 0x0052), // Frame of Reference UID
+        };
+        for (size_t i = 0; i < sizeof(uids) / sizeof(uids[0]); i++)
+        {
+            if (reader.GetFile().GetDataSet().FindDataElement(uids[i]) && !anon.Replace(uids[i], uid.Generate()))
+            {
+                proc_resp.status = 0x02;
+                return proc_resp;
+            }
+        }
+    }
+
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(anon.GetFile());
+
+    if (!writer.Write())
+    {
+        proc_resp.status = 0x03;
+        return proc_resp;
+    }
+
+    proc_resp.status = 0x00;
+    proc_resp.image = dicomOutput.str();
+    return proc_resp;
+}
+
+struct OutputStruct c_convert(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    size_t max_size,
+    int transfer_syntax_pre,
+    int transfer_syntax_post,
+    int photometric_interpretation,
+    char is_lossy,
+    int quality1,
+    int quality2,
+    int quality3,
+    char irreversible,
+    int allow_error,
+    const OptionsStruct *options)
+try
+{
+    struct OutputStruct resp;
+    struct ProcResp proc_resp;
+    TraceScope trace(options);
+
+    // Use memory map as input & output
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+    
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+
+    // Strict mode, reject non-conformant sources
+    if (options != NULL && options->strict && !check_conformance(inputString))
+    {
+        resp.status = 0x0D;
+        return resp;
+    }
+
+    // Datasets without preamble and file meta information
+    if (!normalize_raw_dataset(inputString, options != NULL && options->assume_raw_dataset))
+    {
+        resp.status = 0x11;
+        return resp;
+    }
+
+    // Preview of the first frames
+    if (options != NULL && options->max_frames > 0 && !truncate_frames(inputString, options->max_frames))
+    {
+        resp.status = 0x14;
+        return resp;
+    }
+
+    // RGB pixel data labelled YBR_FULL by the modality, relabelled before any decoding
+    if (options != NULL && options->trust_detected_photometric && !repair_photometric(inputString))
+    {
+        resp.status = 0x23;
+        return resp;
+    }
+
+    // Copy for process pipeline
+    proc_resp.image = inputString;
+
+    // Implementation of the output, given or kept from the source
+    std::string class_uid;
+    std::string version_name;
+    if (options != NULL && options->preserve_implementation)
+    {
+        std::istringstream headerInput(inputString);
+        gdcm::Reader header_reader;
+        header_reader.SetStream(headerInput);
+        if (header_reader.ReadUpToTag(gdcm::Tag(0x0008, 0x0000)))
+        {
+            const gdcm::FileMetaInformation &fmi = header_reader.GetFile().GetHeader();
+            class_uid = get_meta_string(fmi, gdcm::Tag(0x0002, 0x0012));
+            version_name = get_meta_string(fmi, gdcm::Tag(0x0002, 0x0013));
+        }
+    }
+    if (options != NULL && options->implementation_class_uid != NULL)
+        class_uid = options->implementation_class_uid;
+    if (options != NULL && options->implementation_version_name != NULL)
+        version_name = options->implementation_version_name;
+
+    // Spatial attributes of the source, restored after the last stage
+    gdcm::DataSet spatial;
+    bool preserve_spatial = false;
+    if (options != NULL && options->preserve_spatial)
+    {
+        std::istringstream headerInput(inputString);
+        gdcm::Reader header_reader;
+        header_reader.SetStream(headerInput);
+        if (header_reader.ReadUpToTag(gdcm::Tag(0x0028, 0x0031)))
+        {
+            const gdcm::DataSet &ds = header_reader.GetFile().GetDataSet();
+            const gdcm::Tag tags[] = {
+                gdcm::Tag(0x0018, 0x0050), gdcm::Tag(0x0028, 0x0010),
+                gdcm::Tag(0x0028, 0x0011), gdcm::Tag(0x0028, 0x0030)};
+            for (size_t i = 0; i < sizeof(tags) / sizeof(tags[0]); i++)
+            {
+                if (ds.FindDataElement(tags[i]))
+                    spatial.Insert(ds.GetDataElement(tags[i]));
+            }
+            preserve_spatial = spatial.FindDataElement(gdcm::Tag(0x0028, 0x0030))
+                || spatial.FindDataElement(gdcm::Tag(0x0018, 0x0050));
+        }
+    }
+
+    // Change transfer syntax pre
+    if (transfer_syntax_pre > 0)
+    {
+        proc_resp = change_transfer(
+            transfer_syntax_pre,
+            is_lossy,
+            quality1,
+            quality2,
+            quality3,
+            irreversible,
+            allow_error,
+            options,
+            proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x10;
+            return resp;
+        }
+    }
+
+    if (is_cancelled(options))
+    {
+        resp.status = 0x0C;
+        return resp;
+    }
+
+    // Change photometric interpretation
+    if (photometric_interpretation > 0 || (options != NULL && (options->apply_rescale || options->bake_voi_lut || options->overlays > 0 || options->icc_profile > 0 || options->target_bits > 0)))
+    {
+        proc_resp = change_photometric(
+            photometric_interpretation,
+            options,
+            proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x20;
+            return resp;
+        }
+    }
+
+    if (is_cancelled(options))
+    {
+        resp.status = 0x0C;
+        return resp;
+    }
+
+    // Anonymize
+    if (options != NULL && (options->anonymize || options->strip_private))
+    {
+        proc_resp = anonymize(
+            options,
+            proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x40;
+            return resp;
+        }
+    }
+
+    if (is_cancelled(options))
+    {
+        resp.status = 0x0C;
+        return resp;
+    }
+
+    // Character set to UTF-8
+    if (options != NULL && options->to_utf8)
+    {
+        proc_resp = convert_charset(proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x50;
+            return resp;
+        }
+    }
+
+    if (is_cancelled(options))
+    {
+        resp.status = 0x0C;
+        return resp;
+    }
+
+    // Elements set by the caller
+    if (options != NULL && options->tag_overrides_len > 0)
+    {
+        proc_resp = override_tags(options, proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x60;
+            return resp;
+        }
+    }
+
+    // Change transfer syntax post
+    if (transfer_syntax_post > 0)
+    {
+        proc_resp = change_transfer(
+            transfer_syntax_post,
+            is_lossy,
+            quality1,
+            quality2,
+            quality3,
+            irreversible,
+            allow_error,
+            options,
+            proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x30;
+            return resp;
+        }
+    }
+
+    if (is_cancelled(options))
+    {
+        resp.status = 0x0C;
+        return resp;
+    }
+
+    // Restore spatial attributes
+    if (preserve_spatial)
+    {
+        proc_resp = restore_spatial(spatial, proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x60;
+            return resp;
+        }
+    }
+
+    // Store the pixel data hash
+    if (options != NULL && options->pixel_hash != NULL)
+    {
+        proc_resp = set_pixel_hash(options->pixel_hash, proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x60;
+            return resp;
+        }
+    }
+
+    // Set implementation
+    if (!class_uid.empty() || !version_name.empty())
+    {
+        proc_resp = set_implementation(
+            class_uid,
+            version_name,
+            proc_resp.image);
+        if (proc_resp.status > 0)
+        {
+            resp.status = proc_resp.status + 0x60;
+            return resp;
+        }
+    }
+
+    // Encapsulate uncompressed pixel data, GDCM can not read it back in another stage
+    int transfer_syntax_last = transfer_syntax_post > 0 ? transfer_syntax_post : transfer_syntax_pre;
+    if (transfer_syntax_last == 23 && !encapsulate_uncompressed(proc_resp.image))
+    {
+        resp.status = (transfer_syntax_post > 0 ? 0x30 : 0x10) + 0x06;
+        return resp;
+    }
+
+    // One fragment per frame for JPEG 2000 outputs
+    bool j2k_last = (transfer_syntax_last >= 10 && transfer_syntax_last <= 13)
+        || (transfer_syntax_last >= 16 && transfer_syntax_last <= 18);
+    if (j2k_last && options != NULL && options->single_fragment_per_frame
+        && !single_fragment_per_frame(proc_resp.image))
+    {
+        resp.status = (transfer_syntax_post > 0 ? 0x30 : 0x10) + 0x06;
+        return resp;
+    }
+
+    // Basic Offset Table of the output, e.g. repairing an empty or corrupt one of the source
+    if (options != NULL && options->rebuild_offset_table && !rebuild_offset_table(proc_resp.image))
+    {
+        resp.status = (transfer_syntax_post > 0 ? 0x30 : 0x10) + 0x06;
+        return resp;
+    }
+
+    // A Basic Offset Table holds 32 bits offsets, larger outputs need the extended one
+    bool extended_offsets = options != NULL && options->force_extended_offset_table;
+    if ((extended_offsets || proc_resp.image.size() > 0xFFFFFFFFULL) && !add_extended_offset_table(proc_resp.image, extended_offsets))
+    {
+        resp.status = (transfer_syntax_post > 0 ? 0x30 : 0x10) + 0x06;
+        return resp;
+    }
+
+    // The declared transfer syntax must match the pixel data, the wrapper writes .98 itself
+    if (transfer_syntax_last != 23 && !check_output_encoding(proc_resp.image))
+    {
+        resp.status = (transfer_syntax_post > 0 ? 0x30 : 0x10) + 0x09;
+        return resp;
+    }
+
+    // Dry run, do not write output
+    if (options != NULL && options->dry_run)
+    {
+        resp.status = 0x00;
+    }
+    else if (max_size >= proc_resp.image.size())
+    {
+        memcpy(i_buffer_ptr, proc_resp.image.c_str(), proc_resp.image.size());
+        resp.status = 0x00;
+    }
+    else
+    {
+        resp.status = 0xFF;
+    }
+
+    resp.size = proc_resp.image.size();
+    return resp;
+}
+catch (...)
+{
+    // GDCM throws on some malformed inputs, never let an exception cross the FFI boundary
+    struct OutputStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+struct InfoStruct c_image_info(
+    char *i_buffer_ptr,
+    size_t i_buffer_len)
+try
+{
+    struct InfoStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    // Read only the header, stop before pixel data
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.ReadUpToTag(gdcm::Tag(0x7fe0, 0x0010)))
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+
+    if (!ds.FindDataElement(gdcm::Tag(0x0028, 0x0010)) || !ds.FindDataElement(gdcm::Tag(0x0028, 0x0011)) ||
+        !ds.FindDataElement(gdcm::Tag(0x0028, 0x0100)))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    gdcm::Attribute<0x0028, 0x0010> rows;
+    rows.SetFromDataSet(ds);
+    gdcm::Attribute<0x0028, 0x0011> columns;
+    columns.SetFromDataSet(ds);
+    gdcm::Attribute<0x0028, 0x0100> bits_allocated;
+    bits_allocated.SetFromDataSet(ds);
+
+    // Number of frames is optional for single frame images
+    unsigned int number_of_frames = 1;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0008)))
+    {
+        gdcm::Attribute<0x0028, 0x0008> frames;
+        frames.SetFromDataSet(ds);
+        if (frames.GetValue() > 0)
+            number_of_frames = frames.GetValue();
+    }
+
+    unsigned int bits_stored = bits_allocated.GetValue();
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0101)))
+    {
+        gdcm::Attribute<0x0028, 0x0101> bits;
+        bits.SetFromDataSet(ds);
+        bits_stored = bits.GetValue();
+    }
+
+    unsigned int samples_per_pixel = 1;
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0002)))
+    {
+        gdcm::Attribute<0x0028, 0x0002> samples;
+        samples.SetFromDataSet(ds);
+        samples_per_pixel = samples.GetValue();
+    }
+
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0004)))
+    {
+        gdcm::Attribute<0x0028, 0x0004> photometric;
+        photometric.SetFromDataSet(ds);
+        std::string pi = photometric.GetValue().Trim();
+        strncpy(resp.photometric_interpretation, pi.c_str(), sizeof(resp.photometric_interpretation) - 1);
+    }
+
+    resp.status = 0x00;
+    resp.rows = rows.GetValue();
+    resp.columns = columns.GetValue();
+    resp.number_of_frames = number_of_frames;
+    resp.bits_allocated = bits_allocated.GetValue();
+    resp.bits_stored = bits_stored;
+    resp.samples_per_pixel = samples_per_pixel;
+    return resp;
+}
+catch (...)
+{
+    struct InfoStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+unsigned int c_transfer_syntax(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    char *uid_ptr,
+    size_t uid_len)
+try
+{
+    if (i_buffer_ptr == NULL || uid_ptr == NULL || uid_len == 0) {
+        return 0x0F;
+    }
+    if (i_buffer_len == 0) {
+        return 0x1F;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    // Read only the file meta information
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.ReadUpToTag(gdcm::Tag(0x0008, 0x0000)))
+    {
+        return 0x01;
+    }
+
+    const gdcm::TransferSyntax &ts = reader.GetFile().GetHeader().GetDataSetTransferSyntax();
+    const char *uid = ts.GetString();
+    if (uid == NULL)
+    {
+        return 0x02;
+    }
+
+    strncpy(uid_ptr, uid, uid_len - 1);
+    uid_ptr[uid_len - 1] = '\0';
+    return 0x00;
+}
+catch (...)
+{
+    return 0x0E;
+}
+
+unsigned int c_sop_class_uid(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    char *uid_ptr,
+    size_t uid_len)
+try
+{
+    if (i_buffer_ptr == NULL || uid_ptr == NULL || uid_len == 0) {
+        return 0x0F;
+    }
+    if (i_buffer_len == 0) {
+        return 0x1F;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    // Read up to (0008,0016) SOP Class UID
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.ReadUpToTag(gdcm::Tag(0x0008, 0x0017)))
+    {
+        return 0x01;
+    }
+
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (!ds.FindDataElement(gdcm::Tag(0x0008, 0x0016)))
+    {
+        return 0x02;
+    }
+    const gdcm::ByteValue *bv = ds.GetDataElement(gdcm::Tag(0x0008, 0x0016)).GetByteValue();
+    if (bv == NULL)
+    {
+        return 0x02;
+    }
+
+    std::string uid(bv->GetPointer(), bv->GetLength());
+    uid.erase(uid.find_last_not_of(std::string(" \0", 2)) + 1);
+    strncpy(uid_ptr, uid.c_str(), uid_len - 1);
+    uid_ptr[uid_len - 1] = '\0';
+    return 0x00;
+}
+catch (...)
+{
+    return 0x0E;
+}
+
+struct OutputStruct c_read_tag(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned short group,
+    unsigned short element,
+    char *value_ptr,
+    size_t max_size)
+try
+{
+    struct OutputStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    // Read up to the element after the requested one
+    gdcm::Tag tag(group, element);
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    bool read = element < 0xFFFF
+        ? reader.ReadUpToTag(gdcm::Tag(group, element + 1))
+        : reader.ReadUpToTag(gdcm::Tag(group + 1, 0x0000));
+    if (!read)
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::File &file = reader.GetFile();
+    const gdcm::DataSet &ds = group == 0x0002 ? file.GetHeader() : file.GetDataSet();
+    if (!ds.FindDataElement(tag))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    // Binary values and sequences have no string form
+    gdcm::VR vr = ds.GetDataElement(tag).GetVR();
+    if (vr == gdcm::VR::INVALID || vr == gdcm::VR::UN)
+        vr = gdcm::Global::GetInstance().GetDicts().GetDictEntry(tag).GetVR();
+    if (vr == gdcm::VR::INVALID || vr == gdcm::VR::UN || vr == gdcm::VR::SQ
+        || (vr & (gdcm::VR::OB | gdcm::VR::OW | gdcm::VR::OD | gdcm::VR::OF | gdcm::VR::OL)))
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    // Numbers of binary VRs are printed, multiple values joined with backslashes
+    gdcm::StringFilter filter;
+    filter.SetFile(file);
+    std::string value = filter.ToString(tag);
+    value.erase(value.find_last_not_of(std::string(" \0", 2)) + 1);
+
+    resp.size = value.size();
+    if (value_ptr == NULL || max_size < resp.size)
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    memcpy(value_ptr, value.data(), value.size());
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct OutputStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+unsigned int c_pixel_data_length(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    size_t *pixel_data_len)
+try
+{
+    if (i_buffer_ptr == NULL || pixel_data_len == NULL) {
+        return 0x0F;
+    }
+    if (i_buffer_len == 0) {
+        return 0x1F;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        return 0x01;
+    }
+
+    // Value of (7FE0,0010), with the item headers of encapsulated fragments
+    *pixel_data_len = 0;
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (ds.FindDataElement(gdcm::Tag(0x7fe0, 0x0010)))
+    {
+        const gdcm::DataElement &de = ds.GetDataElement(gdcm::Tag(0x7fe0, 0x0010));
+        const gdcm::SequenceOfFragments *sq = de.GetSequenceOfFragments();
+        if (sq != NULL)
+            *pixel_data_len = sq->ComputeLength();
+        else if (!de.GetVL().IsUndefined())
+            *pixel_data_len = de.GetVL();
+    }
+
+    return 0x00;
+}
+catch (...)
+{
+    return 0x0E;
+}
+
+unsigned int c_detect_photometric(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    int *photometric_interpretation)
+try
+{
+    if (i_buffer_ptr == NULL || photometric_interpretation == NULL) {
+        return 0x0F;
+    }
+    if (i_buffer_len == 0) {
+        return 0x1F;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::PixmapReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        return 0x01;
+    }
+
+    // Wrapper id of the interpretation, 0 when unknown
+    gdcm::PhotometricInterpretation::PIType detected = detect_photometric(reader.GetPixmap());
+    *photometric_interpretation = 0;
+    for (int id = 1; photometric_from_id(id) != gdcm::PhotometricInterpretation::PI_END; id++)
+    {
+        if (photometric_from_id(id) == detected)
+            *photometric_interpretation = id;
+    }
+
+    return 0x00;
+}
+catch (...)
+{
+    return 0x0E;
+}
+
+unsigned int c_frame_count(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned int *number_of_frames)
+try
+{
+    if (i_buffer_ptr == NULL || number_of_frames == NULL) {
+        return 0x0F;
+    }
+    if (i_buffer_len == 0) {
+        return 0x1F;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    // Stop before (0028,0010) Rows, the pixel data is never read
+    gdcm::Reader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.ReadUpToTag(gdcm::Tag(0x0028, 0x0010)))
+    {
+        return 0x01;
+    }
+
+    *number_of_frames = 1;
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    if (ds.FindDataElement(gdcm::Tag(0x0028, 0x0008)))
+    {
+        gdcm::Attribute<0x0028, 0x0008> at;
+        at.SetFromDataSet(ds);
+        if (at.GetValue() > 0)
+            *number_of_frames = at.GetValue();
+    }
+
+    return 0x00;
+}
+catch (...)
+{
+    return 0x0E;
+}
+
+struct FrameStruct c_extract_frame(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned int frame_index,
+    char *o_buffer_ptr,
+    size_t max_size)
+try
+{
+    struct FrameStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::ImageReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::Image &image = reader.GetImage();
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+
+    resp.rows = image.GetDimension(1);
+    resp.columns = image.GetDimension(0);
+    resp.number_of_frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    resp.samples_per_pixel = pf.GetSamplesPerPixel();
+    resp.bits_allocated = pf.GetBitsAllocated();
+    resp.bits_stored = pf.GetBitsStored();
+
+    if (frame_index >= resp.number_of_frames)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    // Decode all frames, then keep only the requested one
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    size_t frame_len = buffer.size() / resp.number_of_frames;
+    resp.size = frame_len;
+
+    if (o_buffer_ptr == NULL || max_size < frame_len)
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    memcpy(o_buffer_ptr, buffer.data() + frame_len * frame_index, frame_len);
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct FrameStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+struct FrameStruct c_transcode_frame(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned int frame_index,
+    int transfer_syntax,
+    char *o_buffer_ptr,
+    size_t max_size)
+try
+{
+    struct FrameStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    // Raw little endian or an encapsulated syntax, one frame can not be deflated
+    gdcm::TransferSyntax ts = transfer_syntax_from_id(transfer_syntax);
+    if (ts == gdcm::TransferSyntax::TS_END || transfer_syntax == 3 || transfer_syntax == 21 || !has_encoder(transfer_syntax))
+    {
+        resp.status = 0x05;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::ImageRegionReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.ReadInformation())
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::File &file = reader.GetFile();
+    std::vector<unsigned int> dims = gdcm::ImageHelper::GetDimensionsValue(file);
+    gdcm::PixelFormat pf = gdcm::ImageHelper::GetPixelFormatValue(file);
+
+    resp.rows = dims[1];
+    resp.columns = dims[0];
+    resp.number_of_frames = dims.size() > 2 && dims[2] > 0 ? dims[2] : 1;
+    resp.samples_per_pixel = pf.GetSamplesPerPixel();
+    resp.bits_allocated = pf.GetBitsAllocated();
+    resp.bits_stored = pf.GetBitsStored();
+
+    if (frame_index >= resp.number_of_frames)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    // Decode only the requested frame
+    gdcm::BoxRegion box;
+    box.SetDomain(0, dims[0] - 1, 0, dims[1] - 1, frame_index, frame_index);
+    reader.SetRegion(box);
+    std::vector<char> buffer(reader.ComputeBufferLength());
+    if (buffer.empty() || !reader.ReadIntoBuffer(buffer.data(), buffer.size()))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    std::string frame;
+    if (!ts.IsEncapsulated())
+    {
+        frame.assign(buffer.data(), buffer.size());
+    }
+    else
+    {
+        gdcm::Image image;
+        image.SetNumberOfDimensions(2);
+        image.SetDimension(0, dims[0]);
+        image.SetDimension(1, dims[1]);
+        image.SetPixelFormat(pf);
+        image.SetPhotometricInterpretation(gdcm::ImageHelper::GetPhotometricInterpretationValue(file));
+        image.SetPlanarConfiguration(gdcm::ImageHelper::GetPlanarConfigurationValue(file));
+        image.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+        gdcm::DataElement pixeldata(gdcm::Tag(0x7fe0, 0x0010));
+        pixeldata.SetByteValue(buffer.data(), (uint32_t)buffer.size());
+        image.SetDataElement(pixeldata);
+
+        gdcm::ImageChangeTransferSyntax change;
+        change.SetTransferSyntax(ts);
+        change.SetInput(image);
+        if (!change.Change())
+        {
+            resp.status = 0x04;
+            return resp;
+        }
+
+        // Fragments of the single frame
+        const gdcm::SequenceOfFragments *sq = change.GetOutput().GetDataElement().GetSequenceOfFragments();
+        if (sq == NULL)
+        {
+            resp.status = 0x04;
+            return resp;
+        }
+        for (size_t i = 0; i < sq->GetNumberOfFragments(); i++)
+        {
+            const gdcm::ByteValue *bv = sq->GetFragment(i).GetByteValue();
+            if (bv != NULL)
+                frame.append(bv->GetPointer(), bv->GetLength());
+        }
+    }
+
+    resp.size = frame.size();
+    if (o_buffer_ptr == NULL || max_size < resp.size)
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    memcpy(o_buffer_ptr, frame.data(), frame.size());
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct FrameStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+// Enhanced multi-frame SOP classes and their classic single-frame counterparts
+static const char *const CLASSIC_SOP_CLASSES[][2] = {
+    {"1.2.840.10008.5.1.4.1.1.2.1", "1.2.840.10008.5.1.4.1.1.2"},     // Enhanced CT
+    {"1.2.840.10008.5.1.4.1.1.2.2", "1.2.840.10008.5.1.4.1.1.2"},     // Legacy Converted Enhanced CT
+    {"1.2.840.10008.5.1.4.1.1.4.1", "1.2.840.10008.5.1.4.1.1.4"},     // Enhanced MR
+    {"1.2.840.10008.5.1.4.1.1.4.4", "1.2.840.10008.5.1.4.1.1.4"},     // Legacy Converted Enhanced MR
+    {"1.2.840.10008.5.1.4.1.1.128.1", "1.2.840.10008.5.1.4.1.1.128"}, // Legacy Converted Enhanced PET
+    {"1.2.840.10008.5.1.4.1.1.130", "1.2.840.10008.5.1.4.1.1.128"},   // Enhanced PET
+};
+
+// Copy the attributes of the functional group macros of a (5200,9229) or (5200,9230) item into
+// the top level data set, e.g. (0028,0030) Pixel Spacing of the Pixel Measures Sequence.
+static void flatten_functional_groups(const gdcm::Item &item, gdcm::DataSet &ds)
+{
+    const gdcm::DataSet &groups = item.GetNestedDataSet();
+    for (gdcm::DataSet::ConstIterator it = groups.Begin(); it != groups.End(); ++it)
+    {
+        gdcm::SmartPointer<gdcm::SequenceOfItems> sq = it->GetValueAsSQ();
+        if (!sq || sq->GetNumberOfItems() == 0)
+            continue;
+        const gdcm::DataSet &macro = sq->GetItem(1).GetNestedDataSet();
+        for (gdcm::DataSet::ConstIterator mit = macro.Begin(); mit != macro.End(); ++mit)
+            ds.Replace(*mit);
+    }
+}
+
+struct FrameStruct c_split_frames(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    char *o_buffer_ptr,
+    size_t max_size,
+    size_t *frame_sizes,
+    size_t frame_sizes_len)
+try
+{
+    struct FrameStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::ImageReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::Image &image = reader.GetImage();
+    const gdcm::PixelFormat &pf = image.GetPixelFormat();
+
+    resp.rows = image.GetDimension(1);
+    resp.columns = image.GetDimension(0);
+    resp.number_of_frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    resp.samples_per_pixel = pf.GetSamplesPerPixel();
+    resp.bits_allocated = pf.GetBitsAllocated();
+    resp.bits_stored = pf.GetBitsStored();
+
+    if (resp.number_of_frames < 2)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    // Decode all frames once, each output holds a slice of the buffer
+    std::vector<char> buffer(image.GetBufferLength());
+    if (!image.GetBuffer(buffer.data()))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+    size_t frame_len = buffer.size() / resp.number_of_frames;
+
+    gdcm::File &file = reader.GetFile();
+    if (!make_explicit(file, NULL))
+    {
+        resp.status = 0x04;
+        return resp;
+    }
+
+    // Data set common to the frames, without the functional groups and the pixel data
+    gdcm::DataSet shared = file.GetDataSet();
+    shared.Remove(gdcm::Tag(0x7fe0, 0x0010));
+    shared.Remove(gdcm::Tag(0x5200, 0x9229));
+    shared.Remove(gdcm::Tag(0x5200, 0x9230));
+
+    const gdcm::DataSet &ds = file.GetDataSet();
+    if (ds.FindDataElement(gdcm::Tag(0x5200, 0x9229)))
+    {
+        gdcm::SmartPointer<gdcm::SequenceOfItems> sq = ds.GetDataElement(gdcm::Tag(0x5200, 0x9229)).GetValueAsSQ();
+        if (sq && sq->GetNumberOfItems() > 0)
+            flatten_functional_groups(sq->GetItem(1), shared);
+    }
+    gdcm::SmartPointer<gdcm::SequenceOfItems> per_frame;
+    if (ds.FindDataElement(gdcm::Tag(0x5200, 0x9230)))
+        per_frame = ds.GetDataElement(gdcm::Tag(0x5200, 0x9230)).GetValueAsSQ();
+
+    // Enhanced objects become classic ones, other multi-frame objects keep their SOP Class
+    bool classic = false;
+    if (shared.FindDataElement(gdcm::Tag(0x0008, 0x0016)))
+    {
+        gdcm::Attribute<0x0008, 0x0016> sop_class;
+        sop_class.SetFromDataSet(shared);
+        std::string uid = sop_class.GetValue().Trim();
+        for (size_t i = 0; i < sizeof(CLASSIC_SOP_CLASSES) / sizeof(CLASSIC_SOP_CLASSES[0]); i++)
+        {
+            if (uid == CLASSIC_SOP_CLASSES[i][0])
+            {
+                sop_class.SetValue(CLASSIC_SOP_CLASSES[i][1]);
+                shared.Replace(sop_class.GetAsDataElement());
+                classic = true;
+                break;
+            }
+        }
+    }
+    if (classic)
+    {
+        shared.Remove(gdcm::Tag(0x0028, 0x0008));
+        shared.Remove(gdcm::Tag(0x0028, 0x0009));
+    }
+    else
+    {
+        gdcm::Attribute<0x0028, 0x0008> number_of_frames;
+        number_of_frames.SetValue(1);
+        shared.Replace(number_of_frames.GetAsDataElement());
+    }
+
+    // The pixel data is written decoded
+    gdcm::Attribute<0x0028, 0x0004> photometric;
+    photometric.SetValue(image.GetPhotometricInterpretation().GetString());
+    shared.Replace(photometric.GetAsDataElement());
+    if (resp.samples_per_pixel > 1)
+    {
+        gdcm::Attribute<0x0028, 0x0006> planar;
+        planar.SetValue(static_cast<unsigned short>(image.GetPlanarConfiguration()));
+        shared.Replace(planar.GetAsDataElement());
+    }
+
+    std::string outputString;
+    std::vector<size_t> sizes;
+    gdcm::UIDGenerator uid;
+    for (unsigned int i = 0; i < resp.number_of_frames; i++)
+    {
+        gdcm::DataSet frame_ds = shared;
+        if (per_frame && per_frame->GetNumberOfItems() > i)
+            flatten_functional_groups(per_frame->GetItem(i + 1), frame_ds);
+
+        gdcm::Attribute<0x0008, 0x0018> sop_instance;
+        sop_instance.SetValue(uid.Generate());
+        frame_ds.Replace(sop_instance.GetAsDataElement());
+        gdcm::Attribute<0x0020, 0x0013> instance_number;
+        instance_number.SetValue(i + 1);
+        frame_ds.Replace(instance_number.GetAsDataElement());
+
+        std::vector<char> pixels(buffer.begin() + frame_len * i, buffer.begin() + frame_len * (i + 1));
+        if (pixels.size() % 2 == 1)
+            pixels.push_back(0);
+        gdcm::DataElement pixeldata(gdcm::Tag(0x7fe0, 0x0010));
+        pixeldata.SetVR(resp.bits_allocated > 8 ? gdcm::VR::OW : gdcm::VR::OB);
+        pixeldata.SetByteValue(pixels.data(), (uint32_t)pixels.size());
+        frame_ds.Replace(pixeldata);
+
+        gdcm::File single;
+        single.SetDataSet(frame_ds);
+        single.GetHeader().GetPreamble().Create();
+        single.GetHeader().SetDataSetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+        std::ostringstream dicomOutput;
+        gdcm::Writer writer;
+        writer.SetStream(dicomOutput);
+        writer.SetFile(single);
+        if (!writer.Write())
+        {
+            resp.status = 0x04;
+            return resp;
+        }
+
+        std::string frame = dicomOutput.str();
+        sizes.push_back(frame.size());
+        outputString += frame;
+    }
+
+    resp.size = outputString.size();
+    if (o_buffer_ptr == NULL || max_size < resp.size || frame_sizes == NULL || frame_sizes_len < sizes.size())
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    memcpy(o_buffer_ptr, outputString.data(), outputString.size());
+    memcpy(frame_sizes, sizes.data(), sizes.size() * sizeof(size_t));
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct FrameStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+// Classic single-frame SOP classes and their legacy converted enhanced multi-frame counterparts
+static const char *const LEGACY_ENHANCED_SOP_CLASSES[][2] = {
+    {"1.2.840.10008.5.1.4.1.1.2", "1.2.840.10008.5.1.4.1.1.2.2"},     // CT
+    {"1.2.840.10008.5.1.4.1.1.4", "1.2.840.10008.5.1.4.1.1.4.4"},     // MR
+    {"1.2.840.10008.5.1.4.1.1.128", "1.2.840.10008.5.1.4.1.1.128.1"}, // PET
+};
+
+// Functional group macros written for a legacy converted object and the attributes they hold
+struct FunctionalGroup
+{
+    gdcm::Tag macro;
+    gdcm::Tag tags[2];
+    size_t tags_len;
+    bool shared;
+};
+
+static const FunctionalGroup LEGACY_FUNCTIONAL_GROUPS[] = {
+    {gdcm::Tag(0x0028, 0x9110), {gdcm::Tag(0x0028, 0x0030), gdcm::Tag(0x0018, 0x0050)}, 2, true},  // Pixel Measures
+    {gdcm::Tag(0x0020, 0x9116), {gdcm::Tag(0x0020, 0x0037)}, 1, true},                             // Plane Orientation
+    {gdcm::Tag(0x0020, 0x9113), {gdcm::Tag(0x0020, 0x0032)}, 1, false},                            // Plane Position
+    {gdcm::Tag(0x0028, 0x9132), {gdcm::Tag(0x0028, 0x1050), gdcm::Tag(0x0028, 0x1051)}, 2, false}, // Frame VOI LUT
+    {gdcm::Tag(0x0028, 0x9145), {gdcm::Tag(0x0028, 0x1052), gdcm::Tag(0x0028, 0x1053)}, 2, false}, // Pixel Value Transformation
+};
+
+// Add a functional group macro to a (5200,9229) or (5200,9230) item with the attributes found in
+// the source data set, nothing when none is present.
+static void add_functional_group(gdcm::DataSet &groups, const FunctionalGroup &group, const gdcm::DataSet &source)
+{
+    gdcm::Item item;
+    item.SetVLToUndefined();
+    gdcm::DataSet &macro = item.GetNestedDataSet();
+    for (size_t i = 0; i < group.tags_len; i++)
+    {
+        if (source.FindDataElement(group.tags[i]))
+            macro.Insert(source.GetDataElement(group.tags[i]));
+    }
+    if (macro.IsEmpty())
+        return;
+
+    gdcm::SmartPointer<gdcm::SequenceOfItems> sq = new gdcm::SequenceOfItems;
+    sq->SetLengthToUndefined();
+    sq->AddItem(item);
+    gdcm::DataElement de(group.macro);
+    de.SetVR(gdcm::VR::SQ);
+    de.SetValue(*sq);
+    de.SetVLToUndefined();
+    groups.Replace(de);
+}
+
+struct OutputStruct c_combine_frames(
+    const char *const *frame_ptrs,
+    const size_t *frame_lens,
+    size_t frame_count,
+    char *o_buffer_ptr,
+    size_t max_size)
+try
+{
+    struct OutputStruct resp = {};
+
+    if (frame_ptrs == NULL || frame_lens == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (frame_count == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    const size_t groups_len = sizeof(LEGACY_FUNCTIONAL_GROUPS) / sizeof(LEGACY_FUNCTIONAL_GROUPS[0]);
+
+    // Decoded frames of every input, the first one gives the data set and the pixel attributes
+    std::vector<char> pixels;
+    std::vector<gdcm::DataSet> frame_headers;
+    gdcm::DataSet base;
+    unsigned int rows = 0, columns = 0, planar = 0;
+    gdcm::PixelFormat pf;
+    gdcm::PhotometricInterpretation::PIType pi = gdcm::PhotometricInterpretation::UNKNOWN;
+    for (size_t i = 0; i < frame_count; i++)
+    {
+        if (frame_ptrs[i] == NULL) {
+            resp.status = 0x0F;
+            return resp;
+        }
+        if (frame_lens[i] == 0) {
+            resp.status = 0x1F;
+            return resp;
+        }
+
+        std::string inputString(frame_ptrs[i], frame_lens[i]);
+        std::istringstream dicomInput(inputString);
+
+        gdcm::ImageReader reader;
+        reader.SetStream(dicomInput);
+        if (!reader.Read())
+        {
+            resp.status = 0x01;
+            return resp;
+        }
+
+        const gdcm::Image &image = reader.GetImage();
+        unsigned int frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+        if (i == 0)
+        {
+            columns = image.GetDimension(0);
+            rows = image.GetDimension(1);
+            planar = image.GetPlanarConfiguration();
+            pf = image.GetPixelFormat();
+            pi = image.GetPhotometricInterpretation();
+            if (!make_explicit(reader.GetFile(), NULL))
+            {
+                resp.status = 0x04;
+                return resp;
+            }
+            base = reader.GetFile().GetDataSet();
+        }
+        else if (image.GetDimension(0) != columns || image.GetDimension(1) != rows
+            || image.GetPlanarConfiguration() != planar || !(image.GetPixelFormat() == pf)
+            || (gdcm::PhotometricInterpretation::PIType)image.GetPhotometricInterpretation() != pi)
+        {
+            resp.status = 0x05;
+            return resp;
+        }
+
+        size_t offset = pixels.size();
+        pixels.resize(offset + image.GetBufferLength());
+        if (!image.GetBuffer(pixels.data() + offset))
+        {
+            resp.status = 0x02;
+            return resp;
+        }
+
+        // Attributes which differ between slices, e.g. (0020,0032) Image Position (Patient)
+        gdcm::DataSet header;
+        const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+        for (size_t g = 0; g < groups_len; g++)
+        {
+            for (size_t t = 0; t < LEGACY_FUNCTIONAL_GROUPS[g].tags_len; t++)
+            {
+                if (!LEGACY_FUNCTIONAL_GROUPS[g].shared && ds.FindDataElement(LEGACY_FUNCTIONAL_GROUPS[g].tags[t]))
+                    header.Insert(ds.GetDataElement(LEGACY_FUNCTIONAL_GROUPS[g].tags[t]));
+            }
+        }
+        frame_headers.insert(frame_headers.end(), frames, header);
+    }
+
+    base.Remove(gdcm::Tag(0x7fe0, 0x0010));
+
+    // Classic CT, MR and PET become legacy converted enhanced objects with functional groups,
+    // other SOP classes only get the number of frames
+    bool enhanced = false;
+    if (base.FindDataElement(gdcm::Tag(0x0008, 0x0016)))
+    {
+        gdcm::Attribute<0x0008, 0x0016> sop_class;
+        sop_class.SetFromDataSet(base);
+        std::string uid = sop_class.GetValue().Trim();
+        for (size_t i = 0; i < sizeof(LEGACY_ENHANCED_SOP_CLASSES) / sizeof(LEGACY_ENHANCED_SOP_CLASSES[0]); i++)
+        {
+            if (uid == LEGACY_ENHANCED_SOP_CLASSES[i][0])
+            {
+                sop_class.SetValue(LEGACY_ENHANCED_SOP_CLASSES[i][1]);
+                base.Replace(sop_class.GetAsDataElement());
+                enhanced = true;
+                break;
+            }
+        }
+    }
+    if (enhanced)
+    {
+        gdcm::Item shared_item;
+        shared_item.SetVLToUndefined();
+        for (size_t g = 0; g < groups_len; g++)
+        {
+            if (LEGACY_FUNCTIONAL_GROUPS[g].shared)
+                add_functional_group(shared_item.GetNestedDataSet(), LEGACY_FUNCTIONAL_GROUPS[g], base);
+        }
+
+        gdcm::SmartPointer<gdcm::SequenceOfItems> shared_sq = new gdcm::SequenceOfItems;
+        shared_sq->SetLengthToUndefined();
+        shared_sq->AddItem(shared_item);
+        gdcm::DataElement shared(gdcm::Tag(0x5200, 0x9229));
+        shared.SetVR(gdcm::VR::SQ);
+        shared.SetValue(*shared_sq);
+        shared.SetVLToUndefined();
+        base.Replace(shared);
+
+        gdcm::SmartPointer<gdcm::SequenceOfItems> per_frame_sq = new gdcm::SequenceOfItems;
+        per_frame_sq->SetLengthToUndefined();
+        for (size_t i = 0; i < frame_headers.size(); i++)
+        {
+            gdcm::Item item;
+            item.SetVLToUndefined();
+            for (size_t g = 0; g < groups_len; g++)
+            {
+                if (!LEGACY_FUNCTIONAL_GROUPS[g].shared)
+                    add_functional_group(item.GetNestedDataSet(), LEGACY_FUNCTIONAL_GROUPS[g], frame_headers[i]);
+            }
+            per_frame_sq->AddItem(item);
+        }
+        gdcm::DataElement per_frame(gdcm::Tag(0x5200, 0x9230));
+        per_frame.SetVR(gdcm::VR::SQ);
+        per_frame.SetValue(*per_frame_sq);
+        per_frame.SetVLToUndefined();
+        base.Replace(per_frame);
+
+        // The attributes now live in the functional groups
+        for (size_t g = 0; g < groups_len; g++)
+        {
+            for (size_t t = 0; t < LEGACY_FUNCTIONAL_GROUPS[g].tags_len; t++)
+                base.Remove(LEGACY_FUNCTIONAL_GROUPS[g].tags[t]);
+        }
+    }
+
+    gdcm::Attribute<0x0028, 0x0008> number_of_frames;
+    number_of_frames.SetValue((int)frame_headers.size());
+    base.Replace(number_of_frames.GetAsDataElement());
+
+    // The pixel data is written decoded
+    gdcm::Attribute<0x0028, 0x0004> photometric;
+    photometric.SetValue(gdcm::PhotometricInterpretation::GetPIString(pi));
+    base.Replace(photometric.GetAsDataElement());
+    if (pf.GetSamplesPerPixel() > 1)
+    {
+        gdcm::Attribute<0x0028, 0x0006> planar_configuration;
+        planar_configuration.SetValue(static_cast<unsigned short>(planar));
+        base.Replace(planar_configuration.GetAsDataElement());
+    }
+
+    gdcm::UIDGenerator uid;
+    gdcm::Attribute<0x0008, 0x0018> sop_instance;
+    sop_instance.SetValue(uid.Generate());
+    base.Replace(sop_instance.GetAsDataElement());
+
+    if (pixels.size() % 2 == 1)
+        pixels.push_back(0);
+    gdcm::DataElement pixeldata(gdcm::Tag(0x7fe0, 0x0010));
+    pixeldata.SetVR(pf.GetBitsAllocated() > 8 ? gdcm::VR::OW : gdcm::VR::OB);
+    pixeldata.SetByteValue(pixels.data(), (uint32_t)pixels.size());
+    base.Replace(pixeldata);
+
+    gdcm::File combined;
+    combined.SetDataSet(base);
+    combined.GetHeader().GetPreamble().Create();
+    combined.GetHeader().SetDataSetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+    std::ostringstream dicomOutput;
+    gdcm::Writer writer;
+    writer.SetStream(dicomOutput);
+    writer.SetFile(combined);
+    if (!writer.Write())
+    {
+        resp.status = 0x04;
+        return resp;
+    }
+
+    std::string outputString = dicomOutput.str();
+    resp.size = outputString.size();
+    if (o_buffer_ptr == NULL || max_size < resp.size)
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    memcpy(o_buffer_ptr, outputString.data(), outputString.size());
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct OutputStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+struct PixelDataStruct c_pixel_data(
     char *i_buffer_ptr,
     size_t i_buffer_len,
-    size_t max_size,
-    int transfer_syntax_pre,
-    int transfer_syntax_post,
-    int photometric_interpretation,
-    char is_lossy,
-    int quality1,
-    int quality2,
-    int quality3,
-    char irreversible,
-    int allow_error)
+    char *o_buffer_ptr,
+    size_t max_size)
+try
 {
-    struct OutputStruct resp;
-    struct ProcResp proc_resp;
+    struct PixelDataStruct resp = {};
 
-    // Use memory map as input & output
     if (i_buffer_ptr == NULL) {
         resp.status = 0x0F;
         return resp;
@@ -422,73 +4681,404 @@ struct OutputStruct c_convert(
         resp.status = 0x1F;
         return resp;
     }
-    
+
     std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
 
-    // Copy for process pipeline
-    proc_resp.image = inputString;
+    gdcm::ImageReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        resp.status = 0x01;
+        return resp;
+    }
 
-    // Change transfer syntax pre
-    if (transfer_syntax_pre > 0)
+    // Stored hash, if any
+    const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+    uint16_t slot = find_pixel_hash_slot(ds);
+    if (slot != 0 && ds.FindDataElement(pixel_hash_tag(slot)))
     {
-        proc_resp = change_transfer(
-            transfer_syntax_pre,
-            is_lossy,
-            quality1,
-            quality2,
-            quality3,
-            irreversible,
-            allow_error,
-            proc_resp.image);
-        if (proc_resp.status > 0)
+        const gdcm::ByteValue *bv = ds.GetDataElement(pixel_hash_tag(slot)).GetByteValue();
+        if (bv != NULL)
         {
-            resp.status = proc_resp.status + 0x10;
-            return resp;
+            std::string hash(bv->GetPointer(), bv->GetLength());
+            hash.erase(hash.find_last_not_of(' ') + 1);
+            strncpy(resp.pixel_hash, hash.c_str(), sizeof(resp.pixel_hash) - 1);
         }
     }
 
-    // Change photometric interpretation
-    if (photometric_interpretation > 0)
+    const gdcm::Image &image = reader.GetImage();
+    resp.size = image.GetBufferLength();
+
+    if (o_buffer_ptr == NULL || max_size < resp.size)
     {
-        proc_resp = change_photometric(
-            photometric_interpretation,
-            proc_resp.image);
-        if (proc_resp.status > 0)
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    if (!image.GetBuffer(o_buffer_ptr))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct PixelDataStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+// Render a frame as 8-bit interleaved samples. Palette color is expanded and YBR converted to RGB,
+// monochrome is rescaled then windowed with the given center and width, else the first window of
+// the data set, else the full pixel range.
+static bool render_frame(
+    const gdcm::ImageReader &reader,
+    unsigned int frame_index,
+    const double *window,
+    std::vector<unsigned char> &frame,
+    unsigned int &columns,
+    unsigned int &rows,
+    unsigned int &samples)
+{
+    // Expand palette color to RGB
+    gdcm::ImageApplyLookupTable lut;
+    const gdcm::Pixmap *pixmap = &reader.GetImage();
+    if (pixmap->GetPhotometricInterpretation() == gdcm::PhotometricInterpretation::PALETTE_COLOR)
+    {
+        lut.SetInput(*pixmap);
+        if (!lut.Apply())
+            return false;
+        pixmap = &lut.PixmapToPixmapFilter::GetOutput();
+    }
+
+    const gdcm::PixelFormat &pf = pixmap->GetPixelFormat();
+    const gdcm::PhotometricInterpretation &pi = pixmap->GetPhotometricInterpretation();
+    columns = pixmap->GetDimension(0);
+    rows = pixmap->GetDimension(1);
+    samples = pf.GetSamplesPerPixel();
+
+    std::vector<char> buffer(pixmap->GetBufferLength());
+    if (columns == 0 || rows == 0 || (samples != 1 && samples != 3) || !pixmap->GetBuffer(buffer.data()))
+        return false;
+
+    size_t pixels = (size_t)rows * columns;
+    size_t base = (size_t)frame_index * pixels * samples;
+    frame.assign(pixels * samples, 0);
+
+    if (samples == 1)
+    {
+        const gdcm::DataSet &ds = reader.GetFile().GetDataSet();
+        double slope = reader.GetImage().GetSlope();
+        double intercept = reader.GetImage().GetIntercept();
+
+        std::vector<double> values(pixels);
+        for (size_t i = 0; i < pixels; i++)
+            values[i] = get_sample(buffer, pf, base + i) * slope + intercept;
+
+        // Given window, else the first one, full range when missing
+        double low = *std::min_element(values.begin(), values.end());
+        double high = *std::max_element(values.begin(), values.end());
+        if (window != NULL && window[1] > 0)
         {
-            resp.status = proc_resp.status + 0x20;
-            return resp;
+            low = window[0] - window[1] / 2;
+            high = window[0] + window[1] / 2;
+        }
+        else if (ds.FindDataElement(gdcm::Tag(0x0028, 0x1050)) && ds.FindDataElement(gdcm::Tag(0x0028, 0x1051)))
+        {
+            gdcm::Attribute<0x0028, 0x1050> center;
+            center.SetFromDataSet(ds);
+            gdcm::Attribute<0x0028, 0x1051> width;
+            width.SetFromDataSet(ds);
+            if (center.GetNumberOfValues() > 0 && width.GetNumberOfValues() > 0 && width.GetValue(0) > 1)
+            {
+                low = center.GetValue(0) - width.GetValue(0) / 2;
+                high = center.GetValue(0) + width.GetValue(0) / 2;
+            }
+        }
+
+        bool invert = pi == gdcm::PhotometricInterpretation::MONOCHROME1;
+        double range = high > low ? high - low : 1;
+        for (size_t i = 0; i < pixels; i++)
+        {
+            double v = std::min(std::max((values[i] - low) / range, 0.0), 1.0) * 255.0;
+            frame[i] = static_cast<unsigned char>(invert ? 255.0 - v : v);
         }
     }
+    else
+    {
+        bool planar = pixmap->GetPlanarConfiguration() == 1;
+        bool ybr = pi == gdcm::PhotometricInterpretation::YBR_FULL || pi == gdcm::PhotometricInterpretation::YBR_FULL_422;
+        double max = pf.GetBitsStored() > 8 ? (double)((1 << pf.GetBitsStored()) - 1) : 255.0;
 
-    // Change transfer syntax post
-    if (transfer_syntax_post > 0)
+        for (size_t i = 0; i < pixels; i++)
+        {
+            double c[3];
+            for (unsigned int k = 0; k < 3; k++)
+                c[k] = get_sample(buffer, pf, base + (planar ? k * pixels + i : i * 3 + k)) * 255.0 / max;
+
+            if (ybr)
+            {
+                double y = c[0], cb = c[1] - 128.0, cr = c[2] - 128.0;
+                c[0] = y + 1.402 * cr;
+                c[1] = y - 0.344136 * cb - 0.714136 * cr;
+                c[2] = y + 1.772 * cb;
+            }
+
+            for (unsigned int k = 0; k < 3; k++)
+                frame[i * 3 + k] = static_cast<unsigned char>(std::min(std::max(c[k], 0.0), 255.0));
+        }
+    }
+
+    return true;
+}
+
+struct OutputStruct c_thumbnail(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned int max_dim,
+    int quality,
+    char *o_buffer_ptr,
+    size_t max_size)
+try
+{
+    struct OutputStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::ImageReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
     {
-        proc_resp = change_transfer(
-            transfer_syntax_post,
-            is_lossy,
-            quality1,
-            quality2,
-            quality3,
-            irreversible,
-            allow_error,
-            proc_resp.image);
-        if (proc_resp.status > 0)
+        resp.status = 0x01;
+        return resp;
+    }
+
+    // First frame to 8-bit interleaved samples
+    std::vector<unsigned char> frame;
+    unsigned int columns, rows, samples;
+    if (!render_frame(reader, 0, NULL, frame, columns, rows, samples))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    // Downsample with a box filter to fit max_dim
+    unsigned int out_columns = columns;
+    unsigned int out_rows = rows;
+    if (max_dim > 0 && (columns > max_dim || rows > max_dim))
+    {
+        double scale = (double)std::max(columns, rows) / max_dim;
+        out_columns = std::max(1u, (unsigned int)(columns / scale));
+        out_rows = std::max(1u, (unsigned int)(rows / scale));
+    }
+
+    std::vector<char> thumb((size_t)out_rows * out_columns * samples);
+    for (unsigned int y = 0; y < out_rows; y++)
+    {
+        unsigned int y0 = (unsigned int)((size_t)y * rows / out_rows);
+        unsigned int y1 = std::max(y0 + 1, (unsigned int)((size_t)(y + 1) * rows / out_rows));
+        for (unsigned int x = 0; x < out_columns; x++)
         {
-            resp.status = proc_resp.status + 0x30;
-            return resp;
+            unsigned int x0 = (unsigned int)((size_t)x * columns / out_columns);
+            unsigned int x1 = std::max(x0 + 1, (unsigned int)((size_t)(x + 1) * columns / out_columns));
+            for (unsigned int k = 0; k < samples; k++)
+            {
+                double sum = 0;
+                for (unsigned int yy = y0; yy < y1; yy++)
+                    for (unsigned int xx = x0; xx < x1; xx++)
+                        sum += frame[((size_t)yy * columns + xx) * samples + k];
+                thumb[((size_t)y * out_columns + x) * samples + k] = static_cast<char>(sum / ((y1 - y0) * (x1 - x0)));
+            }
         }
     }
 
-    if (max_size >= proc_resp.image.size())
+    // Encode as baseline JPEG
+    gdcm::Image out;
+    out.SetNumberOfDimensions(2);
+    out.SetDimension(0, out_columns);
+    out.SetDimension(1, out_rows);
+    out.SetPixelFormat(gdcm::PixelFormat(samples, 8, 8, 7, 0));
+    out.SetPhotometricInterpretation(samples == 1 ? gdcm::PhotometricInterpretation::MONOCHROME2 : gdcm::PhotometricInterpretation::RGB);
+    out.SetPlanarConfiguration(0);
+    out.SetTransferSyntax(gdcm::TransferSyntax::ExplicitVRLittleEndian);
+
+    gdcm::DataElement pixeldata(gdcm::Tag(0x7fe0, 0x0010));
+    pixeldata.SetByteValue(thumb.data(), (uint32_t)thumb.size());
+    out.SetDataElement(pixeldata);
+
+    gdcm::JPEGCodec jpegcodec;
+    jpegcodec.SetLossless(false);
+    if (quality)
+        jpegcodec.SetQuality(static_cast<double>(quality));
+
+    gdcm::ImageChangeTransferSyntax change;
+    change.SetTransferSyntax(gdcm::TransferSyntax::JPEGBaselineProcess1);
+    change.SetUserCodec(&jpegcodec);
+    change.SetInput(out);
+    if (!change.Change())
     {
-        memcpy(i_buffer_ptr, proc_resp.image.c_str(), proc_resp.image.size());
-        resp.status = 0x00;
+        resp.status = 0x03;
+        return resp;
     }
-    else
+
+    // The first fragment is the JPEG stream
+    const gdcm::SequenceOfFragments *sqf = change.PixmapToPixmapFilter::GetOutput().GetDataElement().GetSequenceOfFragments();
+    if (sqf == NULL || sqf->GetNumberOfFragments() == 0)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    const gdcm::ByteValue *bv = sqf->GetFragment(0).GetByteValue();
+    if (bv == NULL)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    // Drop the DICOM even length padding
+    size_t len = bv->GetLength();
+    const char *jpeg = bv->GetPointer();
+    if (len > 2 && jpeg[len - 1] == 0 && (unsigned char)jpeg[len - 2] == 0xD9)
+        len--;
+
+    resp.size = len;
+    if (o_buffer_ptr == NULL || max_size < len)
     {
         resp.status = 0xFF;
+        return resp;
     }
 
-    resp.size = proc_resp.image.size();
+    memcpy(o_buffer_ptr, jpeg, len);
+    resp.status = 0x00;
+    return resp;
+}
+catch (...)
+{
+    struct OutputStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+struct FrameStruct c_render_rgb(
+    char *i_buffer_ptr,
+    size_t i_buffer_len,
+    unsigned int frame_index,
+    char has_window,
+    double window_center,
+    double window_width,
+    char *o_buffer_ptr,
+    size_t max_size)
+try
+{
+    struct FrameStruct resp = {};
+
+    if (i_buffer_ptr == NULL) {
+        resp.status = 0x0F;
+        return resp;
+    }
+    if (i_buffer_len == 0) {
+        resp.status = 0x1F;
+        return resp;
+    }
+
+    std::string inputString(i_buffer_ptr, i_buffer_len);
+    std::istringstream dicomInput(inputString);
+
+    gdcm::ImageReader reader;
+    reader.SetStream(dicomInput);
+    if (!reader.Read())
+    {
+        resp.status = 0x01;
+        return resp;
+    }
+
+    const gdcm::Image &image = reader.GetImage();
+    resp.number_of_frames = image.GetNumberOfDimensions() == 3 ? image.GetDimension(2) : 1;
+    if (frame_index >= resp.number_of_frames)
+    {
+        resp.status = 0x03;
+        return resp;
+    }
+
+    std::vector<unsigned char> frame;
+    unsigned int columns, rows, samples;
+    double window[2] = { window_center, window_width };
+    if (!render_frame(reader, frame_index, has_window ? window : NULL, frame, columns, rows, samples))
+    {
+        resp.status = 0x02;
+        return resp;
+    }
+
+    resp.rows = rows;
+    resp.columns = columns;
+    resp.samples_per_pixel = 3;
+    resp.bits_allocated = 8;
+    resp.bits_stored = 8;
+    resp.size = (size_t)rows * columns * 3;
+
+    if (o_buffer_ptr == NULL || max_size < resp.size)
+    {
+        resp.status = 0xFF;
+        return resp;
+    }
+
+    // Grayscale replicated to the three channels
+    if (samples == 1)
+    {
+        for (size_t i = 0; i < frame.size(); i++)
+            memset(o_buffer_ptr + i * 3, frame[i], 3);
+    }
+    else
+    {
+        memcpy(o_buffer_ptr, frame.data(), resp.size);
+    }
+    resp.status = 0x00;
     return resp;
 }
+catch (...)
+{
+    struct FrameStruct resp = {};
+    resp.status = 0x0E;
+    return resp;
+}
+
+char c_supported_transfer_syntax(int transfer_syntax)
+{
+    // Encapsulated by the wrapper
+    if (transfer_syntax == 23)
+        return 1;
+
+    gdcm::TransferSyntax ts = transfer_syntax_from_id(transfer_syntax);
+    if (ts == gdcm::TransferSyntax::TS_END || !has_encoder(transfer_syntax))
+        return 0;
+
+    // Raw syntaxes are written by the data set writer
+    if (!ts.IsEncapsulated())
+        return 1;
+
+    gdcm::JPEGCodec jpeg;
+    gdcm::JPEGLSCodec jpegls;
+    gdcm::JPEG2000Codec j2k;
+    gdcm::RLECodec rle;
+    return jpeg.CanCode(ts) || jpegls.CanCode(ts) || j2k.CanCode(ts) || rle.CanCode(ts);
+}
+
+const char *c_gdcm_version()
+{
+    return gdcm::Version::GetVersion();
+}
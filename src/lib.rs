@@ -14,6 +14,20 @@
 //!
 //! Download CMake directly from [www.cmake.org/download](https://cmake.org/download/) page.
 //!
+//! ### System GDCM:
+//!
+//! With the `system-gdcm` feature, a GDCM 3.0.23 or newer found with `pkg-config` is linked instead of
+//! building the bundled one, older releases lack the HTJ2K transfer syntaxes. The bundled build is used
+//! when none is found or the system one is older. System packages are usually built
+//! without `GDCM_SUPPORT_BROKEN_IMPLEMENTATION`, so some broken files the bundled build reads are rejected.
+//! Without `GDCM_ALWAYS_TRACE_MACRO`, their release builds log no warnings to `ConversionReport::warnings`.
+//!
+//! ### Dynamic linking:
+//!
+//! With the `dynamic` feature, the bundled GDCM is built as shared libraries and linked dynamically, e.g.
+//! for toolchains that can not link the static libraries. The GDCM shared libraries (DLLs on Windows) must
+//! then be found at runtime, `cargo run` and `cargo test` add their build directory to the search path.
+//!
 //! ## Quickstart
 //!
 //! Copy this code and make sure you have a DICOM file to test ([DICOM file samples](https://support.dcmtk.org/redmine/projects/dcmtk/wiki/DICOM_images)).
@@ -58,8 +72,11 @@
 //! The gdcm_conv library takes as input the content of the DICOM file. It reuse the source vector allocating an estimated
 //! size to avoid cloned memory. The default estimad length is 3 times the input file size, the worst case, changing from
 //! a compressed image (like JPEG2000) to raw. Is recommended to use an estimated calculation, to minimize memory allocation.
+//!
+//! The estimated length is the total size of the output buffer, not an additional size over the input length.
 //! 
-//! If the allocated size is not enough, the library will re-allocate to the correct size and execute the FFI function again.
+//! If the allocated size is not enough, the library will re-allocate to the correct size and execute the FFI function again (up to 3 times). With the
+//! `log` feature enabled, the re-allocation is reported as a warning through the `log` crate.
 //!
 //! To estimate the output length you could use this aproximation:
 //!
@@ -73,10 +90,8 @@
 //! // MAX HEADER SIZE
 //! const MAX_HEADER_SIZE: usize = 5000;
 //!
-//! let a = match bits_allocated {
-//!     8 => 1,
-//!     16 => 2,
-//! };
+//! // 1, 8, 16, 32 or 64
+//! let a = bits_allocated;
 //!
 //! let b = match photometric_interpretation {
 //!     "MONOCHROME1" => 1,
@@ -84,8 +99,14 @@
 //!     _ => 3,
 //! };
 //!
-//! let estimad_length = (a * b * rows * columns * number_of_frames) + MAX_HEADER_SIZE;
+//! let estimad_length = (a * b * rows * columns * number_of_frames + 7) / 8 + MAX_HEADER_SIZE;
 //! ```
+//!
+//! The `estimate_output_length` function reads these tags from the input and applies this approximation for you. For
+//! multi-frame images, `estimate_output_length_per_frame` computes the total from your own estimation of a single frame.
+//!
+//! For very large multi-frame files, `FrameTranscoder` encodes one frame at a time from the borrowed input, so only
+//! a single decoded frame is held in memory.
 //! 
 //! The library works as a pipeline with a first transfer syntax conversion (PRE-TRANSFER), a photometric conversion 
 //! and a final transfer syntax conversion (POST-TRANSFER). If you set to None it don't execute the step. 
@@ -97,24 +118,200 @@
 //! lossles, you need to change to Explicit Little Endian transfer syntax, then to an RGB photometric interpretation and
 //! finally to JPG2000, to avoid GDCM color interpretation issue.
 //!
+//! The pipeline inserts these steps on its own when it targets JPEG 2000 from such a source without a
+//! photometric conversion, `convert_ybr_jpeg_to_j2k` is a shortcut for the lossless case.
+//!
+//! ## Thread safety
+//!
+//! GDCM global state (dictionaries and file meta information defaults) is initialized once, before the first
+//! conversion. After that, every conversion uses its own readers, writers and codecs, so the library can be
+//! called from many threads at the same time.
+//!
+//! With the `async` feature, `pipeline_async` runs the conversion on the blocking thread pool of the Tokio
+//! runtime, so async servers do not stall while GDCM works.
+//!
 
-use libc::{c_char, c_int, c_uchar, c_uint, size_t};
+use libc::{c_char, c_double, c_int, c_uchar, c_uint, c_void, size_t};
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io::{Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Once};
+use std::thread;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum GDCMError {
-    #[error("Unknown error.")]
-    Unknown,
+    #[error("Unknown error (0x{0:02X}).")]
+    Unknown(u32),
     #[error("Input buffer pointer is NULL.")]
     PointerNULL,
     #[error("Empty input buffer.")]
     EmptyBuffer,
+    #[error("Output size exceeds the allocated buffer.")]
+    OversizedResult,
+    #[error("Could not allocate the output buffer, {0} bytes needed.")]
+    BufferTooSmall(usize),
+    #[error("Conversion cancelled.")]
+    Cancelled,
+    #[error("Conversion timed out.")]
+    Timeout,
+    #[error("Source is not conformant to the DICOM standard.")]
+    NonConformant,
+    #[error("GDCM raised an exception, the source is malformed.")]
+    Exception,
+    #[error("Frame {0} out of range, the image has {1} frames.")]
+    FrameOutOfRange(u32, u32),
+    #[error("Unknown transfer syntax: {0}")]
+    UnknownTransferSyntax(String),
+    #[error(
+        "Retired transfer syntax: {0}, enable the `deprecated_syntaxes` feature to produce it."
+    )]
+    RetiredTransferSyntax(TransferSyntax),
+    #[error("[GDCM READ] {0}")]
+    Read(Error),
+    #[error("[IO] {0}")]
+    Io(#[from] std::io::Error),
     #[error("[GDCM PRE] {0}")]
     Pre(Error),
     #[error("[GDCM PHOTO] {0}")]
     Photo(Error),
     #[error("[GDCM POST] {0}")]
     Post(Error),
+    #[error("[GDCM ANON] {0}")]
+    Anon(Error),
+    #[error("[GDCM THUMBNAIL] {0}")]
+    Thumbnail(Error),
+    #[error("[GDCM FRAMES] {0}")]
+    Frames(Error),
+    #[error("[GDCM CHARSET] {0}")]
+    Charset(Error),
+    #[error("[GDCM META] {0}")]
+    Meta(Error),
+}
+
+// Errors are boxed into `anyhow::Error` or moved across threads and async tasks,
+// fail to compile if a variant breaks the bounds.
+const _: () = {
+    fn assert_send_sync<T: std::error::Error + Send + Sync + 'static>() {}
+
+    #[allow(dead_code)]
+    fn assert_errors() {
+        assert_send_sync::<GDCMError>();
+        assert_send_sync::<Error>();
+    }
+};
+
+impl GDCMError {
+    /// Raw status code returned by the C layer, `None` for errors raised on the Rust side.
+    pub fn raw_code(&self) -> Option<u32> {
+        match self {
+            GDCMError::Unknown(t) => Some(*t),
+            GDCMError::Cancelled => Some(0x0C),
+            GDCMError::NonConformant => Some(0x0D),
+            GDCMError::Exception => Some(0x0E),
+            GDCMError::PointerNULL => Some(0x0F),
+            GDCMError::EmptyBuffer => Some(0x1F),
+            GDCMError::Read(Error::ReadStream) => Some(0x01),
+            GDCMError::Read(Error::InvalidTransferSyntax) => Some(0x02),
+            GDCMError::Read(Error::MissingAttributes) => Some(0x02),
+            GDCMError::Read(Error::Decode) => Some(0x02),
+            GDCMError::Read(Error::NotAString) => Some(0x03),
+            GDCMError::FrameOutOfRange(_, _) => Some(0x03),
+            GDCMError::Pre(t) => t.transfer_code().map(|t| t + 0x10),
+            GDCMError::Photo(t) => t.photometric_code().map(|t| t + 0x20),
+            GDCMError::Post(t) => t.transfer_code().map(|t| t + 0x30),
+            GDCMError::Anon(t) => t.anon_code().map(|t| t + 0x40),
+            GDCMError::Charset(t) => t.charset_code().map(|t| t + 0x50),
+            GDCMError::Meta(t) => t.meta_code().map(|t| t + 0x60),
+            GDCMError::Thumbnail(Error::ReadStream) => Some(0x01),
+            GDCMError::Thumbnail(Error::Decode) => Some(0x02),
+            GDCMError::Thumbnail(Error::Encode) => Some(0x03),
+            GDCMError::Frames(Error::ReadStream) => Some(0x01),
+            GDCMError::Frames(Error::Decode) => Some(0x02),
+            GDCMError::Frames(Error::NotMultiFrame) => Some(0x03),
+            GDCMError::Frames(Error::WriteStream) => Some(0x04),
+            GDCMError::Frames(Error::MismatchedFrames) => Some(0x05),
+            _ => None,
+        }
+    }
+
+    /// Pipeline stage that failed, `None` for errors outside of the three stages.
+    pub fn stage(&self) -> Option<Stage> {
+        match self {
+            GDCMError::Pre(_) => Some(Stage::Pre),
+            GDCMError::Photo(_) => Some(Stage::Photo),
+            GDCMError::Post(_) => Some(Stage::Post),
+            _ => None,
+        }
+    }
+
+    /// Underlying reason of a GDCM step error.
+    pub fn reason(&self) -> Option<&Error> {
+        match self {
+            GDCMError::Read(t)
+            | GDCMError::Pre(t)
+            | GDCMError::Photo(t)
+            | GDCMError::Post(t)
+            | GDCMError::Anon(t)
+            | GDCMError::Thumbnail(t)
+            | GDCMError::Frames(t)
+            | GDCMError::Charset(t)
+            | GDCMError::Meta(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+/// Stages of the conversion pipeline, see `GDCMError::stage`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// First transfer syntax conversion.
+    Pre,
+    /// Photometric interpretation conversion.
+    Photo,
+    /// Last transfer syntax conversion.
+    Post,
+}
+
+/// Keeps the `GDCMError` as the source of the `std::io::Error`, with its message. Buffer and argument
+/// errors are `InvalidInput`, the GDCM stage errors `InvalidData`.
+impl From<GDCMError> for std::io::Error {
+    fn from(e: GDCMError) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match e {
+            GDCMError::Io(t) => return t,
+            GDCMError::PointerNULL
+            | GDCMError::EmptyBuffer
+            | GDCMError::OversizedResult
+            | GDCMError::BufferTooSmall(_)
+            | GDCMError::FrameOutOfRange(_, _)
+            | GDCMError::UnknownTransferSyntax(_)
+            | GDCMError::RetiredTransferSyntax(_) => ErrorKind::InvalidInput,
+            GDCMError::Cancelled => ErrorKind::Interrupted,
+            GDCMError::Timeout => ErrorKind::TimedOut,
+            GDCMError::Unknown(_) => ErrorKind::Other,
+            GDCMError::NonConformant
+            | GDCMError::Exception
+            | GDCMError::Read(_)
+            | GDCMError::Pre(_)
+            | GDCMError::Photo(_)
+            | GDCMError::Post(_)
+            | GDCMError::Anon(_)
+            | GDCMError::Thumbnail(_)
+            | GDCMError::Frames(_)
+            | GDCMError::Charset(_)
+            | GDCMError::Meta(_) => ErrorKind::InvalidData,
+        };
+
+        std::io::Error::new(kind, e)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -135,25 +332,147 @@ pub enum Error {
     InvalidTransferSyntax,
     #[error("Could not derive file.")]
     DeriveFile,
+    #[error("Missing image attributes.")]
+    MissingAttributes,
+    #[error("Could not anonymize.")]
+    Anonymize,
+    #[error("Could not decode pixel data.")]
+    Decode,
+    #[error("Could not encode pixel data.")]
+    Encode,
+    #[error("Could not apply rescale.")]
+    Rescale,
+    #[error("Could not apply VOI LUT.")]
+    VOILUT,
+    #[error("Could not burn in overlays.")]
+    BurnIn,
+    #[error("Could not change planar configuration.")]
+    PlanarConfiguration,
+    #[error("Unsupported bit depth.")]
+    UnsupportedBitDepth,
+    #[error("Could not convert character set.")]
+    CharacterSet,
+    #[error("Pixel data encoding is not compatible with the transfer syntax.")]
+    IncompatibleEncoding,
+    #[error("Incompatible options.")]
+    IncompatibleOptions,
+    #[error("Photometric interpretation is not allowed with the transfer syntax.")]
+    InvalidCombination,
+    #[error("Quality parameter out of range.")]
+    InvalidQuality,
+    #[error("Output transfer syntax does not match its pixel data encoding.")]
+    InconsistentOutput,
+    #[error("Source is not multi-frame.")]
+    NotMultiFrame,
+    #[error("Frames do not share rows, columns, pixel format or photometric interpretation.")]
+    MismatchedFrames,
+    #[error("Element has no string value (sequence or binary VR).")]
+    NotAString,
+    #[error("Element can not be overridden (sequence, binary value, pixel data or file meta information).")]
+    InvalidOverride,
+    #[error("Transfer syntax can only be decoded, GDCM has no encoder for it.")]
+    UnsupportedTarget,
+    #[error("Could not apply the ICC profile.")]
+    IccProfile,
+}
+
+impl Error {
+    fn transfer_code(&self) -> Option<u32> {
+        match self {
+            Error::ReadStream => Some(0x01),
+            Error::FileExplicitFilter => Some(0x02),
+            Error::InvalidTransferSyntax => Some(0x03),
+            Error::ExecuteChange => Some(0x04),
+            Error::DeriveFile => Some(0x05),
+            Error::WriteStream => Some(0x06),
+            Error::UnsupportedBitDepth => Some(0x07),
+            Error::IncompatibleEncoding => Some(0x08),
+            Error::InconsistentOutput => Some(0x09),
+            Error::UnsupportedTarget => Some(0x0A),
+            _ => None,
+        }
+    }
+
+    fn photometric_code(&self) -> Option<u32> {
+        match self {
+            Error::ReadStream => Some(0x01),
+            Error::InvalidPhotometricInterpretation => Some(0x02),
+            Error::ExecuteChange => Some(0x03),
+            Error::ExecuteLUTChange => Some(0x04),
+            Error::WriteStream => Some(0x05),
+            Error::Rescale => Some(0x06),
+            Error::VOILUT => Some(0x07),
+            Error::BurnIn => Some(0x08),
+            Error::PlanarConfiguration => Some(0x09),
+            Error::UnsupportedBitDepth => Some(0x0A),
+            Error::IncompatibleOptions => Some(0x0B),
+            Error::InvalidCombination => Some(0x0C),
+            Error::IccProfile => Some(0x0D),
+            _ => None,
+        }
+    }
+
+    fn charset_code(&self) -> Option<u32> {
+        match self {
+            Error::ReadStream => Some(0x01),
+            Error::CharacterSet => Some(0x02),
+            Error::WriteStream => Some(0x03),
+            _ => None,
+        }
+    }
+
+    fn meta_code(&self) -> Option<u32> {
+        match self {
+            Error::ReadStream => Some(0x01),
+            Error::WriteStream => Some(0x03),
+            Error::InvalidOverride => Some(0x04),
+            _ => None,
+        }
+    }
+
+    fn anon_code(&self) -> Option<u32> {
+        match self {
+            Error::ReadStream => Some(0x01),
+            Error::Anonymize => Some(0x02),
+            Error::WriteStream => Some(0x03),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TransferSyntax {
     None,
     /// [1.2.840.10008.1.2] Implicit VR Endian: Default Transfer Syntax for DICOM.
     ImplicitVRLittleEndian,
     /// [1.2.840.10008.1.2.1] Explicit VR Little Endian.
     ExplicitVRLittleEndian,
-    /// [1.2.840.10008.1.2.2] Explicit VR Big Endian.
+    /// [1.2.840.10008.1.2.1.99] Deflated Explicit VR Little Endian: the whole dataset is zlib-deflated.
+    DeflatedExplicitVRLittleEndian,
+    /// [1.2.840.10008.1.2.1.98] Encapsulated Uncompressed Explicit VR Little Endian: raw pixel data
+    /// encapsulated one fragment per frame with an empty Basic Offset Table. Output only, as the last
+    /// stage of the pipeline, and not for 1 bit pixel data.
+    EncapsulatedUncompressedExplicitVRLittleEndian,
+    /// [1.2.840.10008.1.2.2] Explicit VR Big Endian (retired).
+    /// Always decoded, producing it requires the `deprecated_syntaxes` feature.
     ExplicitVRBigEndian,
-    /// [1.2.840.10008.1.2.5] RLE Lossless.
+    /// [1.2.840.10008.1.2.5] RLE Lossless: one segment per byte plane of each sample, up to 32 bits allocated.
+    /// Color images are interleaved before encoding, multi-frame images are encoded one fragment per frame.
     RLELossless,
     /// [1.2.840.10008.1.2.4.50] JPEG Baseline (Process 1): Default Transfer Syntax for Lossy JPEG 8-bit Image Compression
-    /// (Process 4 only). Input parameter: (quality).
+    /// (Process 4 only). Input parameter: (quality), 1 to 100, 0 for the codec default.
     JPEGBaselineProcess1(u32),
     /// [1.2.840.10008.1.2.4.51] JPEG Baseline (Processes 2 & 4): Default Transfer Syntax for Lossy JPEG 12-bit Image Compression.
-    /// Input parameter: (quality).
+    /// Input parameter: (quality), 1 to 100, 0 for the codec default.
     JPEGExtendedProcess2_4(u32),
+    /// [1.2.840.10008.1.2.4.52] JPEG Extended (Processes 3 & 5) (retired). Decoded only.
+    JPEGExtendedProcess3_5,
+    /// [1.2.840.10008.1.2.4.53] JPEG Spectral Selection, Non-Hierarchical (Processes 6 & 8) (retired).
+    /// Decoded only.
+    JPEGSpectralSelectionProcess6_8,
+    /// [1.2.840.10008.1.2.4.55] JPEG Full Progression, Non-Hierarchical (Processes 10 & 12) (retired).
+    /// Decoded only.
+    JPEGFullProgressionProcess10_12,
     /// [1.2.840.10008.1.2.4.57] JPEG Lossless, Nonhierarchical (Processes 14).
     JPEGLosslessProcess14,
     /// [1.2.840.10008.1.2.4.70] JPEG Lossless, Nonhierarchical, First- Order Prediction (Processes 14 [Selection Value 1]):
@@ -162,20 +481,42 @@ pub enum TransferSyntax {
     /// [1.2.840.10008.1.2.4.80] JPEG-LS Lossless Image Compression.
     JPEGLSLossless,
     /// [1.2.840.10008.1.2.4.81] JPEG-LS Lossy (Near- Lossless) Image Compression.
-    /// Input parameter: (allow_error).
+    /// Input parameter: (allow_error), 0 to 255.
+    ///
+    /// The interleave mode and the coding parameters (T1, T2, T3, RESET) are set with
+    /// `Pipeline::jpegls`.
     JPEGLSNearLossless(u32),
     /// [1.2.840.10008.1.2.4.90] JPEG 2000 Image Compression (Lossless Only).
     JPEG2000Lossless,
     /// [1.2.840.10008.1.2.4.91] JPEG 2000 Image Compression.
-    /// Input parameters: (quality1, quality2, quality3, irreversible)
+    /// Input parameters: (quality1, quality2, quality3, irreversible), one quality per layer,
+    /// strictly increasing, 0 for unused layers after the last one.
     JPEG2000(u32, u32, u32, bool),
     /// [1.2.840.10008.1.2.4.92] JPEG 2000 Part 2 Multicomponent Image Compression (Lossless Only).
     JPEG2000Part2Lossless,
     /// [1.2.840.10008.1.2.4.93] JPEG 2000 Part 2 Multicomponent Image Compression.
-    /// Input parameters: (quality1, quality2, quality3, irreversible)
+    /// Input parameters: (quality1, quality2, quality3, irreversible), as for `JPEG2000`.
     JPEG2000Part2(u32, u32, u32, bool),
-    /// [1.2.840.10008.1.2.4.94] JPIP Referenced
-    MPEG2MainProfile,
+    /// [1.2.840.10008.1.2.4.94] JPIP Referenced. Read only, the pixel data is a URL.
+    JPIPReferenced,
+    /// [1.2.840.10008.1.2.4.100] MPEG2 Main Profile / Main Level. Decoded only, GDCM has no video
+    /// encoder.
+    MPEG2MainProfileMainLevel,
+    /// [1.2.840.10008.1.2.4.201] High-Throughput JPEG 2000 Image Compression (Lossless Only).
+    /// Decoded only, OpenJPEG has no HT block encoder.
+    HTJ2KLossless,
+    /// [1.2.840.10008.1.2.4.202] High-Throughput JPEG 2000 with RPCL Options Image Compression (Lossless Only).
+    /// Decoded only.
+    HTJ2KLosslessRPCL,
+    /// [1.2.840.10008.1.2.4.203] High-Throughput JPEG 2000 Image Compression. Decoded only.
+    /// Input parameter: (quality), 0 to 100, only to name the transfer syntax.
+    HTJ2K(u32),
+    /// [1.2.840.10008.1.2.4.102] MPEG-4 AVC/H.264 High Profile / Level 4.1.
+    /// Decoded only, GDCM has no video encoder.
+    MPEG4AVCH264HP41,
+    /// [1.2.840.10008.1.2.4.103] MPEG-4 AVC/H.264 BD-compatible High Profile / Level 4.1.
+    /// Decoded only, GDCM has no video encoder.
+    MPEG4AVCH264BDCompat,
 }
 
 impl TransferSyntax {
@@ -196,22 +537,406 @@ impl TransferSyntax {
             TransferSyntax::JPEG2000Part2Lossless => 12,
             TransferSyntax::JPEG2000Part2(_, _, _, _) => 13,
             TransferSyntax::RLELossless => 14,
-            TransferSyntax::MPEG2MainProfile => 15,
+            TransferSyntax::MPEG2MainProfileMainLevel => 15,
+            TransferSyntax::HTJ2KLossless => 16,
+            TransferSyntax::HTJ2KLosslessRPCL => 17,
+            TransferSyntax::HTJ2K(_) => 18,
+            TransferSyntax::MPEG4AVCH264HP41 => 19,
+            TransferSyntax::MPEG4AVCH264BDCompat => 20,
+            TransferSyntax::DeflatedExplicitVRLittleEndian => 21,
+            TransferSyntax::JPIPReferenced => 22,
+            TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian => 23,
+            TransferSyntax::JPEGExtendedProcess3_5 => 24,
+            TransferSyntax::JPEGSpectralSelectionProcess6_8 => 25,
+            TransferSyntax::JPEGFullProgressionProcess10_12 => 26,
+        }
+    }
+
+    /// Whether converting to the transfer syntax discards data: JPEG Baseline and Extended, MPEG-2
+    /// and MPEG-4 AVC/H.264 always, JPEG-LS Near-Lossless with an allowed error, JPEG 2000 with a
+    /// quality or irreversible, and HTJ2K with a quality.
+    pub fn is_lossy(self) -> bool {
+        match self {
+            TransferSyntax::JPEGBaselineProcess1(_)
+            | TransferSyntax::JPEGExtendedProcess2_4(_)
+            | TransferSyntax::JPEGExtendedProcess3_5
+            | TransferSyntax::JPEGSpectralSelectionProcess6_8
+            | TransferSyntax::JPEGFullProgressionProcess10_12
+            | TransferSyntax::MPEG2MainProfileMainLevel
+            | TransferSyntax::MPEG4AVCH264HP41
+            | TransferSyntax::MPEG4AVCH264BDCompat => true,
+            TransferSyntax::JPEGLSNearLossless(t) | TransferSyntax::HTJ2K(t) => t > 0,
+            TransferSyntax::JPEG2000(t1, t2, t3, t4)
+            | TransferSyntax::JPEG2000Part2(t1, t2, t3, t4) => t1 != 0 || t2 != 0 || t3 != 0 || t4,
+            _ => false,
+        }
+    }
+
+    /// Whether the pixel data is encapsulated in fragments. All encapsulated transfer syntaxes are
+    /// compressed, except Encapsulated Uncompressed Explicit VR Little Endian.
+    pub fn is_encapsulated(self) -> bool {
+        !matches!(
+            self,
+            TransferSyntax::None
+                | TransferSyntax::ImplicitVRLittleEndian
+                | TransferSyntax::ExplicitVRLittleEndian
+                | TransferSyntax::DeflatedExplicitVRLittleEndian
+                | TransferSyntax::ExplicitVRBigEndian
+        )
+    }
+
+    /// Parameters carried by the variant, in order, e.g. `[Quality]` for `JPEGBaselineProcess1` and
+    /// `[Quality, Quality, Quality, Irreversible]` for `JPEG2000`.
+    pub fn param_spec(self) -> &'static [ParamKind] {
+        match self {
+            TransferSyntax::JPEGBaselineProcess1(_)
+            | TransferSyntax::JPEGExtendedProcess2_4(_)
+            | TransferSyntax::HTJ2K(_) => &[ParamKind::Quality],
+            TransferSyntax::JPEGLSNearLossless(_) => &[ParamKind::AllowedError],
+            TransferSyntax::JPEG2000(_, _, _, _) | TransferSyntax::JPEG2000Part2(_, _, _, _) => &[
+                ParamKind::Quality,
+                ParamKind::Quality,
+                ParamKind::Quality,
+                ParamKind::Irreversible,
+            ],
+            _ => &[],
+        }
+    }
+
+    /// Check the codec parameters, 0 always keeps the codec default:
+    ///
+    /// - `JPEGBaselineProcess1` and `JPEGExtendedProcess2_4`: quality 1 to 100.
+    /// - `JPEGLSNearLossless`: allowed error (NEAR) 0 to 255.
+    /// - `JPEG2000` and `JPEG2000Part2`: one quality per layer, the set layers come first and
+    ///   increase strictly.
+    /// - `HTJ2K`: quality 0 to 100.
+    ///
+    /// Returns `Error::InvalidQuality` otherwise.
+    pub fn validate_params(self) -> Result<(), Error> {
+        let valid = match self {
+            TransferSyntax::JPEGBaselineProcess1(t) | TransferSyntax::JPEGExtendedProcess2_4(t) => {
+                t <= 100
+            }
+            TransferSyntax::JPEGLSNearLossless(t) => t <= 255,
+            TransferSyntax::HTJ2K(t) => t <= 100,
+            TransferSyntax::JPEG2000(t1, t2, t3, _)
+            | TransferSyntax::JPEG2000Part2(t1, t2, t3, _) => {
+                let layers = [t1, t2, t3];
+                let set = layers.iter().take_while(|t| **t != 0).count();
+                layers[set..].iter().all(|t| *t == 0)
+                    && layers[..set].windows(2).all(|t| t[0] < t[1])
+                    && layers.iter().all(|t| *t <= i32::MAX as u32)
+            }
+            _ => true,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidQuality)
+        }
+    }
+
+    /// Whether the transfer syntax is retired from the DICOM standard.
+    pub fn is_retired(self) -> bool {
+        matches!(
+            self,
+            TransferSyntax::ExplicitVRBigEndian
+                | TransferSyntax::JPEGExtendedProcess3_5
+                | TransferSyntax::JPEGSpectralSelectionProcess6_8
+                | TransferSyntax::JPEGFullProgressionProcess10_12
+        )
+    }
+
+    /// Whether the transfer syntax can only be read, GDCM has no encoder for it: the retired JPEG
+    /// processes, HTJ2K, MPEG-2 and MPEG-4 AVC/H.264 video, and JPIP Referenced. Targeting it fails
+    /// with `Error::UnsupportedTarget`.
+    pub fn is_decode_only(self) -> bool {
+        matches!(
+            self,
+            TransferSyntax::JPEGExtendedProcess3_5
+                | TransferSyntax::JPEGSpectralSelectionProcess6_8
+                | TransferSyntax::JPEGFullProgressionProcess10_12
+                | TransferSyntax::HTJ2KLossless
+                | TransferSyntax::HTJ2KLosslessRPCL
+                | TransferSyntax::HTJ2K(_)
+                | TransferSyntax::MPEG2MainProfileMainLevel
+                | TransferSyntax::MPEG4AVCH264HP41
+                | TransferSyntax::MPEG4AVCH264BDCompat
+                | TransferSyntax::JPIPReferenced
+        )
+    }
+
+    /// Transfer syntax UID, empty for `None`.
+    pub fn to_uid(self) -> &'static str {
+        match self {
+            TransferSyntax::None => "",
+            TransferSyntax::ImplicitVRLittleEndian => "1.2.840.10008.1.2",
+            TransferSyntax::ExplicitVRLittleEndian => "1.2.840.10008.1.2.1",
+            TransferSyntax::DeflatedExplicitVRLittleEndian => "1.2.840.10008.1.2.1.99",
+            TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian => {
+                "1.2.840.10008.1.2.1.98"
+            }
+            TransferSyntax::ExplicitVRBigEndian => "1.2.840.10008.1.2.2",
+            TransferSyntax::RLELossless => "1.2.840.10008.1.2.5",
+            TransferSyntax::JPEGBaselineProcess1(_) => "1.2.840.10008.1.2.4.50",
+            TransferSyntax::JPEGExtendedProcess2_4(_) => "1.2.840.10008.1.2.4.51",
+            TransferSyntax::JPEGExtendedProcess3_5 => "1.2.840.10008.1.2.4.52",
+            TransferSyntax::JPEGSpectralSelectionProcess6_8 => "1.2.840.10008.1.2.4.53",
+            TransferSyntax::JPEGFullProgressionProcess10_12 => "1.2.840.10008.1.2.4.55",
+            TransferSyntax::JPEGLosslessProcess14 => "1.2.840.10008.1.2.4.57",
+            TransferSyntax::JPEGLosslessProcess14_1 => "1.2.840.10008.1.2.4.70",
+            TransferSyntax::JPEGLSLossless => "1.2.840.10008.1.2.4.80",
+            TransferSyntax::JPEGLSNearLossless(_) => "1.2.840.10008.1.2.4.81",
+            TransferSyntax::JPEG2000Lossless => "1.2.840.10008.1.2.4.90",
+            TransferSyntax::JPEG2000(_, _, _, _) => "1.2.840.10008.1.2.4.91",
+            TransferSyntax::JPEG2000Part2Lossless => "1.2.840.10008.1.2.4.92",
+            TransferSyntax::JPEG2000Part2(_, _, _, _) => "1.2.840.10008.1.2.4.93",
+            TransferSyntax::JPIPReferenced => "1.2.840.10008.1.2.4.94",
+            TransferSyntax::MPEG2MainProfileMainLevel => "1.2.840.10008.1.2.4.100",
+            TransferSyntax::MPEG4AVCH264HP41 => "1.2.840.10008.1.2.4.102",
+            TransferSyntax::MPEG4AVCH264BDCompat => "1.2.840.10008.1.2.4.103",
+            TransferSyntax::HTJ2KLossless => "1.2.840.10008.1.2.4.201",
+            TransferSyntax::HTJ2KLosslessRPCL => "1.2.840.10008.1.2.4.202",
+            TransferSyntax::HTJ2K(_) => "1.2.840.10008.1.2.4.203",
+        }
+    }
+
+    /// Transfer syntax from its UID.
+    /// Parameterized transfer syntaxes are returned with zeroed quality parameters.
+    pub fn from_uid(uid: &str) -> Option<TransferSyntax> {
+        match uid {
+            "1.2.840.10008.1.2" => Some(TransferSyntax::ImplicitVRLittleEndian),
+            "1.2.840.10008.1.2.1" => Some(TransferSyntax::ExplicitVRLittleEndian),
+            "1.2.840.10008.1.2.1.99" => Some(TransferSyntax::DeflatedExplicitVRLittleEndian),
+            "1.2.840.10008.1.2.1.98" => {
+                Some(TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian)
+            }
+            "1.2.840.10008.1.2.2" => Some(TransferSyntax::ExplicitVRBigEndian),
+            "1.2.840.10008.1.2.5" => Some(TransferSyntax::RLELossless),
+            "1.2.840.10008.1.2.4.50" => Some(TransferSyntax::JPEGBaselineProcess1(0)),
+            "1.2.840.10008.1.2.4.51" => Some(TransferSyntax::JPEGExtendedProcess2_4(0)),
+            "1.2.840.10008.1.2.4.52" => Some(TransferSyntax::JPEGExtendedProcess3_5),
+            "1.2.840.10008.1.2.4.53" => Some(TransferSyntax::JPEGSpectralSelectionProcess6_8),
+            "1.2.840.10008.1.2.4.55" => Some(TransferSyntax::JPEGFullProgressionProcess10_12),
+            "1.2.840.10008.1.2.4.57" => Some(TransferSyntax::JPEGLosslessProcess14),
+            "1.2.840.10008.1.2.4.70" => Some(TransferSyntax::JPEGLosslessProcess14_1),
+            "1.2.840.10008.1.2.4.80" => Some(TransferSyntax::JPEGLSLossless),
+            "1.2.840.10008.1.2.4.81" => Some(TransferSyntax::JPEGLSNearLossless(0)),
+            "1.2.840.10008.1.2.4.90" => Some(TransferSyntax::JPEG2000Lossless),
+            "1.2.840.10008.1.2.4.91" => Some(TransferSyntax::JPEG2000(0, 0, 0, false)),
+            "1.2.840.10008.1.2.4.92" => Some(TransferSyntax::JPEG2000Part2Lossless),
+            "1.2.840.10008.1.2.4.93" => Some(TransferSyntax::JPEG2000Part2(0, 0, 0, false)),
+            "1.2.840.10008.1.2.4.94" => Some(TransferSyntax::JPIPReferenced),
+            "1.2.840.10008.1.2.4.100" => Some(TransferSyntax::MPEG2MainProfileMainLevel),
+            "1.2.840.10008.1.2.4.102" => Some(TransferSyntax::MPEG4AVCH264HP41),
+            "1.2.840.10008.1.2.4.103" => Some(TransferSyntax::MPEG4AVCH264BDCompat),
+            "1.2.840.10008.1.2.4.201" => Some(TransferSyntax::HTJ2KLossless),
+            "1.2.840.10008.1.2.4.202" => Some(TransferSyntax::HTJ2KLosslessRPCL),
+            "1.2.840.10008.1.2.4.203" => Some(TransferSyntax::HTJ2K(0)),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of a `TransferSyntax` parameter, see `TransferSyntax::param_spec`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+    /// Compression quality (`u32`), 0 for the codec default.
+    Quality,
+    /// Allowed error of JPEG-LS Near-Lossless (`u32`).
+    AllowedError,
+    /// Irreversible wavelet transform (`bool`).
+    Irreversible,
+}
+
+impl fmt::Display for TransferSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TransferSyntax::None => write!(f, "none"),
+            TransferSyntax::ImplicitVRLittleEndian => write!(f, "implicit-le"),
+            TransferSyntax::ExplicitVRLittleEndian => write!(f, "explicit-le"),
+            TransferSyntax::DeflatedExplicitVRLittleEndian => write!(f, "deflated-explicit-le"),
+            TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian => {
+                write!(f, "encapsulated-explicit-le")
+            }
+            TransferSyntax::ExplicitVRBigEndian => write!(f, "explicit-be"),
+            TransferSyntax::RLELossless => write!(f, "rle"),
+            TransferSyntax::JPEGBaselineProcess1(t) => write!(f, "jpeg-baseline:{}", t),
+            TransferSyntax::JPEGExtendedProcess2_4(t) => write!(f, "jpeg-extended:{}", t),
+            TransferSyntax::JPEGExtendedProcess3_5 => write!(f, "jpeg-extended-35"),
+            TransferSyntax::JPEGSpectralSelectionProcess6_8 => write!(f, "jpeg-spectral-selection"),
+            TransferSyntax::JPEGFullProgressionProcess10_12 => write!(f, "jpeg-full-progression"),
+            TransferSyntax::JPEGLosslessProcess14 => write!(f, "jpeg-lossless"),
+            TransferSyntax::JPEGLosslessProcess14_1 => write!(f, "jpeg-lossless-sv1"),
+            TransferSyntax::JPEGLSLossless => write!(f, "jpegls-lossless"),
+            TransferSyntax::JPEGLSNearLossless(t) => write!(f, "jpegls-near-lossless:{}", t),
+            TransferSyntax::JPEG2000Lossless => write!(f, "jpeg2000-lossless"),
+            TransferSyntax::JPEG2000(t1, t2, t3, t4) => {
+                write!(f, "jpeg2000:{},{},{},{}", t1, t2, t3, reversibility(t4))
+            }
+            TransferSyntax::JPEG2000Part2Lossless => write!(f, "jpeg2000-part2-lossless"),
+            TransferSyntax::JPEG2000Part2(t1, t2, t3, t4) => {
+                write!(
+                    f,
+                    "jpeg2000-part2:{},{},{},{}",
+                    t1,
+                    t2,
+                    t3,
+                    reversibility(t4)
+                )
+            }
+            TransferSyntax::JPIPReferenced => write!(f, "jpip-referenced"),
+            TransferSyntax::MPEG2MainProfileMainLevel => write!(f, "mpeg2-mpml"),
+            TransferSyntax::MPEG4AVCH264HP41 => write!(f, "mpeg4-hp41"),
+            TransferSyntax::MPEG4AVCH264BDCompat => write!(f, "mpeg4-bd-hp41"),
+            TransferSyntax::HTJ2KLossless => write!(f, "htj2k-lossless"),
+            TransferSyntax::HTJ2KLosslessRPCL => write!(f, "htj2k-lossless-rpcl"),
+            TransferSyntax::HTJ2K(t) => write!(f, "htj2k:{}", t),
+        }
+    }
+}
+
+fn reversibility(irreversible: bool) -> &'static str {
+    if irreversible {
+        "irreversible"
+    } else {
+        "reversible"
+    }
+}
+
+/// Parse a transfer syntax from its short name (as printed by `Display`) or its UID.
+///
+/// Short names are case insensitive and ignore `-` and `_`, so `jpeg2000-lossless` and
+/// `JPEG2000Lossless` are the same. Parameters follow a `:` separated by `,`, missing ones are zeroed:
+/// `jpeg-baseline:90`, `jpegls-near-lossless:2`, `jpeg2000:80,0,0,irreversible`.
+impl FromStr for TransferSyntax {
+    type Err = GDCMError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || GDCMError::UnknownTransferSyntax(s.to_string());
+
+        if let Some(t) = TransferSyntax::from_uid(s.trim()) {
+            return Ok(t);
+        }
+
+        let (name, params) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+
+        let name: String = name
+            .trim()
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+
+        let params: Vec<&str> = match params {
+            Some(p) => p.split(',').map(|t| t.trim()).collect(),
+            None => Vec::new(),
+        };
+
+        // Numeric parameter at position, zero when missing
+        let number = |i: usize| -> Result<u32, GDCMError> {
+            match params.get(i) {
+                Some(t) => t.parse().map_err(|_| unknown()),
+                None => Ok(0),
+            }
+        };
+
+        // Reversibility parameter at position, reversible when missing
+        let irreversible = |i: usize| -> Result<bool, GDCMError> {
+            match params.get(i) {
+                Some(&"irreversible") | Some(&"true") => Ok(true),
+                Some(&"reversible") | Some(&"false") | None => Ok(false),
+                Some(_) => Err(unknown()),
+            }
+        };
+
+        let (t, arity) = match name.as_str() {
+            "none" => (TransferSyntax::None, 0),
+            "implicitle" | "implicitvrlittleendian" => (TransferSyntax::ImplicitVRLittleEndian, 0),
+            "explicitle" | "explicitvrlittleendian" => (TransferSyntax::ExplicitVRLittleEndian, 0),
+            "deflatedexplicitle" | "deflatedexplicitvrlittleendian" => {
+                (TransferSyntax::DeflatedExplicitVRLittleEndian, 0)
+            }
+            "encapsulatedexplicitle" | "encapsulateduncompressedexplicitvrlittleendian" => (
+                TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian,
+                0,
+            ),
+            "explicitbe" | "explicitvrbigendian" => (TransferSyntax::ExplicitVRBigEndian, 0),
+            "rle" | "rlelossless" => (TransferSyntax::RLELossless, 0),
+            "jpegbaseline" | "jpegbaselineprocess1" => {
+                (TransferSyntax::JPEGBaselineProcess1(number(0)?), 1)
+            }
+            "jpegextended" | "jpegextendedprocess24" => {
+                (TransferSyntax::JPEGExtendedProcess2_4(number(0)?), 1)
+            }
+            "jpegextended35" | "jpegextendedprocess35" => {
+                (TransferSyntax::JPEGExtendedProcess3_5, 0)
+            }
+            "jpegspectralselection" | "jpegspectralselectionprocess68" => {
+                (TransferSyntax::JPEGSpectralSelectionProcess6_8, 0)
+            }
+            "jpegfullprogression" | "jpegfullprogressionprocess1012" => {
+                (TransferSyntax::JPEGFullProgressionProcess10_12, 0)
+            }
+            "jpeglossless" | "jpeglosslessprocess14" => (TransferSyntax::JPEGLosslessProcess14, 0),
+            "jpeglosslesssv1" | "jpeglosslessprocess141" => {
+                (TransferSyntax::JPEGLosslessProcess14_1, 0)
+            }
+            "jpeglslossless" => (TransferSyntax::JPEGLSLossless, 0),
+            "jpeglsnearlossless" => (TransferSyntax::JPEGLSNearLossless(number(0)?), 1),
+            "jpeg2000lossless" => (TransferSyntax::JPEG2000Lossless, 0),
+            "jpeg2000" => (
+                TransferSyntax::JPEG2000(number(0)?, number(1)?, number(2)?, irreversible(3)?),
+                4,
+            ),
+            "jpeg2000part2lossless" => (TransferSyntax::JPEG2000Part2Lossless, 0),
+            "jpeg2000part2" => (
+                TransferSyntax::JPEG2000Part2(number(0)?, number(1)?, number(2)?, irreversible(3)?),
+                4,
+            ),
+            "jpipreferenced" => (TransferSyntax::JPIPReferenced, 0),
+            "mpeg2mpml" | "mpeg2mainprofilemainlevel" => {
+                (TransferSyntax::MPEG2MainProfileMainLevel, 0)
+            }
+            "mpeg4hp41" | "mpeg4avch264hp41" => (TransferSyntax::MPEG4AVCH264HP41, 0),
+            "mpeg4bdhp41" | "mpeg4avch264bdcompat" => (TransferSyntax::MPEG4AVCH264BDCompat, 0),
+            "htj2klossless" => (TransferSyntax::HTJ2KLossless, 0),
+            "htj2klosslessrpcl" => (TransferSyntax::HTJ2KLosslessRPCL, 0),
+            "htj2k" => (TransferSyntax::HTJ2K(number(0)?), 1),
+            _ => return Err(unknown()),
+        };
+
+        if params.len() > arity {
+            return Err(unknown());
         }
+
+        Ok(t)
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PhotometricInterpretation {
     None,
+    /// Converting between `Monochrome1` and `Monochrome2` inverts the stored values and the window
+    /// centers, so the image displays the same. A VOI LUT Sequence is removed.
     Monochrome1,
     Monochrome2,
     PaletteColor,
+    /// From `PaletteColor`, the palette lookup tables are applied and the palette elements
+    /// (0028,1101-1103), (0028,1199), (0028,1201-1203) and (0028,1221-1223) removed. Other targets
+    /// from `PaletteColor` go through RGB.
     RGB,
     HSV,
     ARGB,
     CMYK,
     YbrFull,
+    /// From `RGB` or `YbrFull`, the chroma of each horizontal pair of pixels is averaged and stored
+    /// once (Y1 Y2 Cb Cr). Back to `RGB` or `YbrFull`, the chroma of a pair is used for both
+    /// pixels. Both require 8 bits native pixel data and an even number of columns.
     YbrFull422,
     YbrPartial422,
     YbrPartial420,
@@ -238,14 +963,285 @@ impl PhotometricInterpretation {
             PhotometricInterpretation::YbrRct => 13,
         }
     }
+
+    /// Inverse of `to_id`, `None` for unknown ids.
+    fn from_id(id: i32) -> Self {
+        match id {
+            1 => PhotometricInterpretation::Monochrome1,
+            2 => PhotometricInterpretation::Monochrome2,
+            3 => PhotometricInterpretation::PaletteColor,
+            4 => PhotometricInterpretation::RGB,
+            5 => PhotometricInterpretation::HSV,
+            6 => PhotometricInterpretation::ARGB,
+            7 => PhotometricInterpretation::CMYK,
+            8 => PhotometricInterpretation::YbrFull,
+            9 => PhotometricInterpretation::YbrFull422,
+            10 => PhotometricInterpretation::YbrPartial422,
+            11 => PhotometricInterpretation::YbrPartial420,
+            12 => PhotometricInterpretation::YbrIct,
+            13 => PhotometricInterpretation::YbrRct,
+            _ => PhotometricInterpretation::None,
+        }
+    }
+
+    /// Samples per pixel of the interpretation, 0 for `None`.
+    fn samples_per_pixel(self) -> usize {
+        match self {
+            PhotometricInterpretation::None => 0,
+            PhotometricInterpretation::Monochrome1
+            | PhotometricInterpretation::Monochrome2
+            | PhotometricInterpretation::PaletteColor => 1,
+            PhotometricInterpretation::ARGB | PhotometricInterpretation::CMYK => 4,
+            _ => 3,
+        }
+    }
+}
+
+/// Handling of (60xx,eeee) overlay planes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverlayPolicy {
+    /// Keep the overlays. Overlays stored in the unused bits of the pixel data are moved
+    /// to (60xx,3000) Overlay Data before compression, so lossy codecs do not destroy them.
+    Preserve,
+    /// Remove the overlay groups.
+    Strip,
+    /// Rasterize the overlays into the pixel data during the photometric stage, then remove them.
+    BurnIn,
+}
+
+impl OverlayPolicy {
+    pub fn to_id(self) -> i32 {
+        match self {
+            OverlayPolicy::Preserve => 0,
+            OverlayPolicy::Strip => 1,
+            OverlayPolicy::BurnIn => 2,
+        }
+    }
+}
+
+/// Handling of the (0028,2000) ICC Profile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IccPolicy {
+    /// Keep (0028,2000) ICC Profile and (0028,2002) Color Space through the photometric conversion.
+    Preserve,
+    /// Remove the ICC Profile and Color Space, e.g. when converting to monochrome.
+    Strip,
+    /// Convert the pixel data to sRGB with the profile, then replace it with (0028,2002) Color Space
+    /// SRGB. Sources without a profile are left untouched.
+    ///
+    /// GDCM does not bundle a color management engine, only matrix/TRC RGB profiles are applied,
+    /// to unsigned 8 or 16 bits RGB output. Other profiles fail with `Error::IccProfile`.
+    Apply,
+}
+
+impl IccPolicy {
+    pub fn to_id(self) -> i32 {
+        match self {
+            IccPolicy::Preserve => 0,
+            IccPolicy::Strip => 1,
+            IccPolicy::Apply => 2,
+        }
+    }
+}
+
+/// Conversion presets for common targets, see `convert_preset`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// JPEG 2000 Lossless, one fragment per frame with a Basic Offset Table for frame retrieval.
+    /// JPEG Baseline YBR sources go through Explicit VR Little Endian and RGB.
+    WebViewerJ2k,
+    /// JPEG-LS Lossless, the best lossless ratio of the supported codecs.
+    ArchiveLosslessJpegLs,
+    /// Explicit VR Little Endian, uncompressed, for archives without codecs.
+    LegacyPacsExplicitLE,
+    /// MPEG-4 AVC/H.264 High Profile / Level 4.1 sources with the data set rewritten and the video
+    /// stream kept as is, GDCM can not encode video. Other sources fail with
+    /// `Error::IncompatibleEncoding`.
+    VideoMpeg4,
+}
+
+impl Preset {
+    /// Pipeline of the preset, to be extended with further options.
+    pub fn pipeline(self) -> Pipeline {
+        match self {
+            Preset::WebViewerJ2k => Pipeline::new()
+                .post(TransferSyntax::JPEG2000Lossless)
+                .single_fragment_per_frame(true),
+            Preset::ArchiveLosslessJpegLs => Pipeline::new().post(TransferSyntax::JPEGLSLossless),
+            Preset::LegacyPacsExplicitLE => {
+                Pipeline::new().post(TransferSyntax::ExplicitVRLittleEndian)
+            }
+            Preset::VideoMpeg4 => Pipeline::new()
+                .post(TransferSyntax::MPEG4AVCH264HP41)
+                .header_only(true),
+        }
+    }
 }
 
+/// Maximum DICOM header size used to estimate the output length.
+pub const MAX_HEADER_SIZE: usize = 5000;
+
 #[repr(C)]
 struct output_t {
     status: c_uint,
     size: size_t,
 }
 
+#[repr(C)]
+struct options_t {
+    anonymize: c_char,
+    remove_private_tags: c_char,
+    patient_name: *const c_char,
+    regenerate_uids: c_char,
+    regenerate_sop_instance_uid: c_char,
+    dry_run: c_char,
+    progress: Option<extern "C" fn(*mut c_void, c_uint, c_uint)>,
+    progress_data: *mut c_void,
+    cancelled: Option<extern "C" fn(*mut c_void) -> c_char>,
+    cancel_data: *mut c_void,
+    apply_rescale: c_char,
+    bake_voi_lut: c_char,
+    voi_lut_index: c_uint,
+    overlays: c_int,
+    icc_profile: c_int,
+    planar_configuration: c_uint,
+    target_bits: c_uint,
+    to_utf8: c_char,
+    derivation_note: *const c_char,
+    j2k_resolutions: c_uint,
+    j2k_tile_width: c_uint,
+    j2k_tile_height: c_uint,
+    j2k_codeblock_width: c_uint,
+    j2k_codeblock_height: c_uint,
+    j2k_progression_order: c_int,
+    jpegls_interleave_mode: c_int,
+    jpegls_t1: c_uint,
+    jpegls_t2: c_uint,
+    jpegls_t3: c_uint,
+    jpegls_reset: c_uint,
+    header_only: c_char,
+    strip_private: c_char,
+    keep_private_creators: *const *const c_char,
+    keep_private_creators_len: size_t,
+    implementation_class_uid: *const c_char,
+    implementation_version_name: *const c_char,
+    preserve_implementation: c_char,
+    preserve_structure: c_char,
+    force_extended_offset_table: c_char,
+    preserve_spatial: c_char,
+    pixel_hash: *const c_char,
+    strict: c_char,
+    assume_raw_dataset: c_char,
+    single_fragment_per_frame: c_char,
+    warning: Option<extern "C" fn(*mut c_void, *const c_char)>,
+    warning_data: *mut c_void,
+    rebuild_offset_table: c_char,
+    max_frames: c_uint,
+    tag_overrides: *const c_uint,
+    tag_override_values: *const *const c_char,
+    tag_overrides_len: size_t,
+    trust_detected_photometric: c_char,
+}
+
+/// Read the cancellation flag from the C side.
+extern "C" fn cancel_trampoline(data: *mut c_void) -> c_char {
+    let cancel = unsafe { &*(data as *const AtomicBool) };
+
+    cancel.load(Ordering::Relaxed) as c_char
+}
+
+/// Collect a GDCM warning from the C side.
+extern "C" fn warning_trampoline(data: *mut c_void, message: *const c_char) {
+    let warnings = unsafe { &mut *(data as *mut Vec<String>) };
+    let message = unsafe { CStr::from_ptr(message) };
+
+    warnings.push(message.to_string_lossy().into_owned());
+}
+
+/// Forward progress from the C side to a Rust closure.
+extern "C" fn progress_trampoline<F: FnMut(u32, u32)>(
+    data: *mut c_void,
+    current: c_uint,
+    total: c_uint,
+) {
+    let on_progress = unsafe { &mut *(data as *mut F) };
+
+    // Never unwind across the FFI boundary
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| on_progress(current, total)));
+}
+
+#[repr(C)]
+struct frame_t {
+    status: c_uint,
+    size: size_t,
+    rows: c_uint,
+    columns: c_uint,
+    number_of_frames: c_uint,
+    samples_per_pixel: c_uint,
+    bits_allocated: c_uint,
+    bits_stored: c_uint,
+}
+
+/// Decoded pixels of a single frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameData {
+    pub rows: u32,
+    pub columns: u32,
+    pub samples_per_pixel: u32,
+    pub bits_allocated: u32,
+    pub bits_stored: u32,
+    /// Raw pixel bytes, little endian, interleaved samples.
+    pub pixels: Vec<u8>,
+}
+
+/// Frame rendered by `render_rgb`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: u32,
+    pub height: u32,
+    /// Interleaved RGB bytes, row by row.
+    pub data: Vec<u8>,
+}
+
+#[cfg(feature = "sha2")]
+#[repr(C)]
+struct pixel_data_t {
+    status: c_uint,
+    size: size_t,
+    pixel_hash: [c_char; 65],
+}
+
+#[repr(C)]
+struct info_t {
+    status: c_uint,
+    rows: c_uint,
+    columns: c_uint,
+    number_of_frames: c_uint,
+    bits_allocated: c_uint,
+    bits_stored: c_uint,
+    samples_per_pixel: c_uint,
+    photometric_interpretation: [c_char; 17],
+}
+
+/// Core image attributes read from the DICOM header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// (0028,0010) Rows.
+    pub rows: u32,
+    /// (0028,0011) Columns.
+    pub columns: u32,
+    /// (0028,0008) Number of Frames, 1 when missing.
+    pub number_of_frames: u32,
+    /// (0028,0100) Bits Allocated.
+    pub bits_allocated: u32,
+    /// (0028,0101) Bits Stored, Bits Allocated when missing.
+    pub bits_stored: u32,
+    /// (0028,0002) Samples per Pixel, 1 when missing.
+    pub samples_per_pixel: u32,
+    /// (0028,0004) Photometric Interpretation, e.g. "MONOCHROME2".
+    pub photometric_interpretation: String,
+}
+
 extern "C" {
     fn c_convert(
         source_ptr: *const c_uchar,
@@ -260,152 +1256,2830 @@ extern "C" {
         quality3: c_int,      // j2k
         irreversible: c_char, // j2k
         allow_error: c_int,   // jpegls
+        options: *const options_t,
     ) -> output_t;
-}
 
-pub fn pipeline(
-    mut source: Vec<u8>,
-    estimated_length: Option<usize>,
-    transfer_syntax_pre: TransferSyntax,
-    photometric_interpretation: PhotometricInterpretation,
-    transfer_syntax_post: TransferSyntax,
-) -> Result<Vec<u8>, GDCMError> {
-    let mut ret;
-    let max_size;
-
-    // Set lossy compression parameters
-    let (is_lossy, quality1, quality2, quality3, irreversible, allow_error) =
-        match transfer_syntax_post {
-            TransferSyntax::JPEGBaselineProcess1(t) => {
-                // Lossy & Quality1
-                if t > 0 {
-                    (true, t, 0, 0, false, 0)
-                } else {
-                    (false, 0, 0, 0, false, 0)
-                }
-            }
-            TransferSyntax::JPEGExtendedProcess2_4(t) => {
-                // Lossy & Quality1
-                if t > 0 {
-                    (true, t, 0, 0, false, 0)
-                } else {
-                    (false, 0, 0, 0, false, 0)
-                }
-            }
-            TransferSyntax::JPEGLSNearLossless(t) => {
-                // Lossy & Allow_error
-                if t > 0 {
-                    (true, 0, 0, 0, false, t)
-                } else {
-                    (false, 0, 0, 0, false, t)
-                }
-            }
-            TransferSyntax::JPEG2000(t1, t2, t3, t4) => {
-                // Lossy, Quality1, Quality2, Quality3 & Irreversible
-                if t1 != 0 || t2 != 0 || t3 != 0 || t4 {
-                    (true, t1, t2, t3, t4, 0)
-                } else {
-                    (false, 0, 0, 0, false, 0)
-                }
-            }
-            TransferSyntax::JPEG2000Part2(t1, t2, t3, t4) => {
-                // Lossy, Quality1, Quality2, Quality3 & Irreversible
-                if t1 != 0 || t2 != 0 || t3 != 0 || t4 {
-                    (true, t1, t2, t3, t4, 0)
-                } else {
-                    (false, 0, 0, 0, false, 0)
-                }
-            }
-            _ => (false, 0, 0, 0, false, 0),
-        };
+    fn c_init();
 
-    // Add more capacity
-    if let Some(t) = estimated_length {
-        source.reserve(t);
-    } else {
-        source.reserve(source.len() * 3);
-    }
+    fn c_image_info(source_ptr: *const c_uchar, source_len: size_t) -> info_t;
 
-    max_size = source.capacity();
+    fn c_read_tag(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        group: u16,
+        element: u16,
+        value_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> output_t;
 
-    // Call C function
-    ret = unsafe {
-        c_convert(
-            source.as_ptr(),
-            source.len() as size_t,
-            max_size as size_t,
-            transfer_syntax_pre.to_id(),
-            transfer_syntax_post.to_id(),
-            photometric_interpretation.to_id(),
-            is_lossy as c_char,
-            quality1 as i32,
-            quality2 as i32,
-            quality3 as i32,
-            irreversible as c_char,
-            allow_error as i32,
-        )
-    };
+    fn c_extract_frame(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        frame_index: c_uint,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> frame_t;
 
-    // If need more size, reserve more and re-process
-    if ret.status == 0xFF {
-        println!(
-            "OVERSIZED [{:?}] input: {} estimated: {:?} needed: {}",
-            transfer_syntax_pre,
-            source.len(),
-            estimated_length,
-            ret.size,
-        );
-        source.reserve(ret.size);
-        ret = unsafe {
-            c_convert(
-                source.as_ptr(),
-                source.len() as size_t,
-                ret.size as size_t,
-                transfer_syntax_pre.to_id(),
-                transfer_syntax_post.to_id(),
-                photometric_interpretation.to_id(),
-                is_lossy as c_char,
-                quality1 as i32,
-                quality2 as i32,
-                quality3 as i32,
-                irreversible as c_char,
-                allow_error as i32,
-            )
-        };
-    }
+    fn c_transcode_frame(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        frame_index: c_uint,
+        transfer_syntax: c_int,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> frame_t;
 
-    // Translate errors
-    match ret.status {
-        // Success
-        0x00 => {
-            unsafe {
-                source.set_len(ret.size);
+    fn c_split_frames(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+        frame_sizes: *mut size_t,
+        frame_sizes_len: size_t,
+    ) -> frame_t;
+
+    fn c_combine_frames(
+        frame_ptrs: *const *const c_uchar,
+        frame_lens: *const size_t,
+        frame_count: size_t,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> output_t;
+
+    #[cfg(feature = "sha2")]
+    fn c_pixel_data(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> pixel_data_t;
+
+    fn c_thumbnail(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        max_dim: c_uint,
+        quality: c_int,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> output_t;
+
+    fn c_render_rgb(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        frame_index: c_uint,
+        has_window: c_char,
+        window_center: c_double,
+        window_width: c_double,
+        output_ptr: *mut c_uchar,
+        max_size: size_t,
+    ) -> frame_t;
+
+    fn c_supported_transfer_syntax(transfer_syntax: c_int) -> c_char;
+
+    fn c_gdcm_version() -> *const c_char;
+
+    fn c_transfer_syntax(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        uid_ptr: *mut c_char,
+        uid_len: size_t,
+    ) -> c_uint;
+
+    fn c_sop_class_uid(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        uid_ptr: *mut c_char,
+        uid_len: size_t,
+    ) -> c_uint;
+
+    fn c_pixel_data_length(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        pixel_data_len: *mut size_t,
+    ) -> c_uint;
+
+    fn c_detect_photometric(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        photometric_interpretation: *mut c_int,
+    ) -> c_uint;
+
+    fn c_frame_count(
+        source_ptr: *const c_uchar,
+        source_len: size_t,
+        number_of_frames: *mut c_uint,
+    ) -> c_uint;
+}
+
+static INIT: Once = Once::new();
+
+/// Initialize GDCM global state (dictionaries, file meta information defaults) once,
+/// before any concurrent FFI call.
+fn init() {
+    INIT.call_once(|| unsafe { c_init() });
+}
+
+/// Version of the linked GDCM library, e.g. "3.1.0".
+pub fn gdcm_version() -> &'static str {
+    // Safety: GDCM returns a pointer to a static NUL terminated string.
+    unsafe { CStr::from_ptr(c_gdcm_version()) }
+        .to_str()
+        .unwrap_or("unknown")
+}
+
+/// Transfer syntaxes, parameterized ones with zeroed quality parameters.
+const TRANSFER_SYNTAXES: [TransferSyntax; 23] = [
+    TransferSyntax::ImplicitVRLittleEndian,
+    TransferSyntax::ExplicitVRLittleEndian,
+    TransferSyntax::ExplicitVRBigEndian,
+    TransferSyntax::JPEGBaselineProcess1(0),
+    TransferSyntax::JPEGExtendedProcess2_4(0),
+    TransferSyntax::JPEGLosslessProcess14,
+    TransferSyntax::JPEGLosslessProcess14_1,
+    TransferSyntax::JPEGLSLossless,
+    TransferSyntax::JPEGLSNearLossless(0),
+    TransferSyntax::JPEG2000Lossless,
+    TransferSyntax::JPEG2000(0, 0, 0, false),
+    TransferSyntax::JPEG2000Part2Lossless,
+    TransferSyntax::JPEG2000Part2(0, 0, 0, false),
+    TransferSyntax::RLELossless,
+    TransferSyntax::MPEG2MainProfileMainLevel,
+    TransferSyntax::HTJ2KLossless,
+    TransferSyntax::HTJ2KLosslessRPCL,
+    TransferSyntax::HTJ2K(0),
+    TransferSyntax::MPEG4AVCH264HP41,
+    TransferSyntax::MPEG4AVCH264BDCompat,
+    TransferSyntax::DeflatedExplicitVRLittleEndian,
+    TransferSyntax::JPIPReferenced,
+    TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian,
+];
+
+/// Transfer syntaxes the linked GDCM build can produce, asking its codecs at runtime.
+///
+/// Parameterized transfer syntaxes are returned with zeroed quality parameters.
+pub fn supported_transfer_syntaxes() -> Vec<TransferSyntax> {
+    init();
+
+    TRANSFER_SYNTAXES
+        .iter()
+        .copied()
+        .filter(|t| !t.is_retired() || cfg!(feature = "deprecated_syntaxes"))
+        .filter(|t| !t.is_decode_only())
+        // Safety: GDCM global state is initialized by `init`, the call only creates codecs.
+        .filter(|t| unsafe { c_supported_transfer_syntax(t.to_id()) } != 0)
+        .collect()
+}
+
+/// Read the transfer syntax (0002,0010) of a DICOM file.
+///
+/// Parameterized transfer syntaxes are returned with zeroed quality parameters.
+pub fn read_transfer_syntax(source: &[u8]) -> Result<TransferSyntax, GDCMError> {
+    // UIDs are 64 characters long at most
+    let mut uid: [c_char; 65] = [0; 65];
+
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let status = unsafe {
+        c_transfer_syntax(
+            source.as_ptr(),
+            source.len() as size_t,
+            uid.as_mut_ptr(),
+            uid.len() as size_t,
+        )
+    };
+
+    match status {
+        0x00 => {}
+        0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+        0x02 => return Err(GDCMError::Read(Error::InvalidTransferSyntax)),
+        0x0E => return Err(GDCMError::Exception),
+        0x0F => return Err(GDCMError::PointerNULL),
+        0x1F => return Err(GDCMError::EmptyBuffer),
+        t => return Err(GDCMError::Unknown(t)),
+    }
+
+    let uid = unsafe { CStr::from_ptr(uid.as_ptr()) }.to_string_lossy();
+
+    TransferSyntax::from_uid(&uid).ok_or_else(|| GDCMError::UnknownTransferSyntax(uid.into_owned()))
+}
+
+/// Whether the pixel data of a DICOM file is encapsulated, from its transfer syntax (0002,0010).
+///
+/// Only the file meta information is read, see `TransferSyntax::is_encapsulated`.
+pub fn is_encapsulated(source: &[u8]) -> Result<bool, GDCMError> {
+    read_transfer_syntax(source).map(TransferSyntax::is_encapsulated)
+}
+
+/// Byte sizes of a DICOM file, split between pixel data and metadata.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sizes {
+    /// Everything but the pixel data value: preamble, file meta information and data set elements.
+    pub header_len: usize,
+    /// Value of (7FE0,0010) Pixel Data, including the Basic Offset Table and the item headers of
+    /// encapsulated fragments.
+    pub pixel_data_len: usize,
+}
+
+/// Measure how many bytes of a DICOM file are pixel data and how many are metadata.
+///
+/// Useful to compare the storage cost of different compressions over the output of a conversion.
+pub fn measure(source: &[u8]) -> Result<Sizes, GDCMError> {
+    let mut pixel_data_len: size_t = 0;
+
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let status = unsafe {
+        c_pixel_data_length(source.as_ptr(), source.len() as size_t, &mut pixel_data_len)
+    };
+
+    match status {
+        0x00 => Ok(Sizes {
+            header_len: source.len().saturating_sub(pixel_data_len),
+            pixel_data_len,
+        }),
+        0x01 => Err(GDCMError::Read(Error::ReadStream)),
+        0x0E => Err(GDCMError::Exception),
+        0x0F => Err(GDCMError::PointerNULL),
+        0x1F => Err(GDCMError::EmptyBuffer),
+        t => Err(GDCMError::Unknown(t)),
+    }
+}
+
+/// Read (0028,0008) Number of Frames, 1 when missing.
+///
+/// The data set is read up to (0028,0010) Rows, cheaper than `read_image_info` which reads the
+/// whole image header.
+pub fn frame_count(source: &[u8]) -> Result<u32, GDCMError> {
+    let mut number_of_frames: c_uint = 0;
+
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let status = unsafe {
+        c_frame_count(
+            source.as_ptr(),
+            source.len() as size_t,
+            &mut number_of_frames,
+        )
+    };
+
+    match status {
+        0x00 => Ok(number_of_frames),
+        0x01 => Err(GDCMError::Read(Error::ReadStream)),
+        0x0E => Err(GDCMError::Exception),
+        0x0F => Err(GDCMError::PointerNULL),
+        0x1F => Err(GDCMError::EmptyBuffer),
+        t => Err(GDCMError::Unknown(t)),
+    }
+}
+
+/// Tell the photometric interpretation of 8 bits RGB or YBR_FULL pixel data from its samples,
+/// for modalities labelling RGB pixel data as YBR_FULL.
+///
+/// Up to 4096 decoded pixels are sampled: gray RGB pixels decoded as YBR have chroma far from 128
+/// and often fall out of gamut, gray YBR pixels have chroma close to 128. Returns `RGB` or
+/// `YbrFull`, or the declared (0028,0004) for other images and undecided samples, `None` when
+/// unknown. A heuristic, mostly colored images may not be told apart.
+///
+/// ```no_run
+/// use gdcm_conv::PhotometricInterpretation;
+///
+/// let ibuffer = std::fs::read("test.dcm").unwrap();
+/// if gdcm_conv::detect_true_photometric(&ibuffer).unwrap() == PhotometricInterpretation::RGB {
+///     // (0028,0004) may still say YBR_FULL
+/// }
+/// ```
+pub fn detect_true_photometric(source: &[u8]) -> Result<PhotometricInterpretation, GDCMError> {
+    let mut photometric_interpretation: c_int = 0;
+
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let status = unsafe {
+        c_detect_photometric(
+            source.as_ptr(),
+            source.len() as size_t,
+            &mut photometric_interpretation,
+        )
+    };
+
+    match status {
+        0x00 => Ok(PhotometricInterpretation::from_id(
+            photometric_interpretation,
+        )),
+        0x01 => Err(GDCMError::Read(Error::ReadStream)),
+        0x0E => Err(GDCMError::Exception),
+        0x0F => Err(GDCMError::PointerNULL),
+        0x1F => Err(GDCMError::EmptyBuffer),
+        t => Err(GDCMError::Unknown(t)),
+    }
+}
+
+/// Read the core image attributes without running a conversion.
+pub fn read_image_info(source: &[u8]) -> Result<ImageInfo, GDCMError> {
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let info = unsafe { c_image_info(source.as_ptr(), source.len() as size_t) };
+
+    match info.status {
+        0x00 => {}
+        0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+        0x02 => return Err(GDCMError::Read(Error::MissingAttributes)),
+        0x0E => return Err(GDCMError::Exception),
+        0x0F => return Err(GDCMError::PointerNULL),
+        0x1F => return Err(GDCMError::EmptyBuffer),
+        t => return Err(GDCMError::Unknown(t)),
+    }
+
+    let photometric_interpretation =
+        unsafe { CStr::from_ptr(info.photometric_interpretation.as_ptr()) };
+
+    Ok(ImageInfo {
+        rows: info.rows,
+        columns: info.columns,
+        number_of_frames: info.number_of_frames,
+        bits_allocated: info.bits_allocated,
+        bits_stored: info.bits_stored,
+        samples_per_pixel: info.samples_per_pixel,
+        photometric_interpretation: photometric_interpretation.to_string_lossy().into_owned(),
+    })
+}
+
+/// Read an element as a string, `None` when it is absent.
+///
+/// The data set is read up to the element. String values are returned without their padding,
+/// numbers of binary VRs (US, UL, FL, FD, AT...) are printed, and multiple values are joined with
+/// backslashes as DICOM does. Sequences and OB, OW, OD, OF, OL or UN values fail with
+/// `Error::NotAString`. Elements of group 0x0002 are read from the file meta information.
+///
+/// ```no_run
+/// let ibuffer = std::fs::read("test.dcm").unwrap();
+/// let modality = gdcm_conv::read_tag(&ibuffer, 0x0008, 0x0060).unwrap();
+/// ```
+pub fn read_tag(source: &[u8], group: u16, element: u16) -> Result<Option<String>, GDCMError> {
+    // Most values are short, the C side reports the needed size otherwise
+    let mut capacity = 256;
+
+    init();
+
+    loop {
+        let mut value: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the output pointer is valid for `capacity` bytes.
+        let ret = unsafe {
+            c_read_tag(
+                source.as_ptr(),
+                source.len() as size_t,
+                group,
+                element,
+                value.as_mut_ptr(),
+                value.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > value.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    value.set_len(ret.size);
+                }
+                return Ok(Some(String::from_utf8_lossy(&value).into_owned()));
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+            0x02 => return Ok(None),
+            0x03 => return Err(GDCMError::Read(Error::NotAString)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// Decode a single frame of a DICOM file, whatever its transfer syntax.
+pub fn extract_frame(source: &[u8], frame_index: u32) -> Result<FrameData, GDCMError> {
+    // Frame size from the header, the C side reports the needed size otherwise
+    let mut capacity = match read_image_info(source) {
+        Ok(t) => {
+            let bytes = (t.bits_allocated as usize).div_ceil(8);
+            t.rows as usize * t.columns as usize * t.samples_per_pixel as usize * bytes
+        }
+        Err(_) => 0,
+    };
+
+    init();
+
+    loop {
+        let mut pixels: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the output pointer is valid for `capacity` bytes.
+        let ret = unsafe {
+            c_extract_frame(
+                source.as_ptr(),
+                source.len() as size_t,
+                frame_index,
+                pixels.as_mut_ptr(),
+                pixels.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > pixels.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    pixels.set_len(ret.size);
+                }
+                return Ok(FrameData {
+                    rows: ret.rows,
+                    columns: ret.columns,
+                    samples_per_pixel: ret.samples_per_pixel,
+                    bits_allocated: ret.bits_allocated,
+                    bits_stored: ret.bits_stored,
+                    pixels,
+                });
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Read(Error::Decode)),
+            0x03 => {
+                return Err(GDCMError::FrameOutOfRange(
+                    frame_index,
+                    ret.number_of_frames,
+                ))
+            }
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// Split a multi-frame DICOM file into single-frame files, one per frame.
+///
+/// Frames are decoded and written as Explicit VR Little Endian, run a `Pipeline` on each file for
+/// another transfer syntax. The attributes of the shared and per-frame functional groups are copied
+/// to the top level data set of each file, enhanced CT, MR and PET objects become their classic
+/// single-frame SOP Class. Every file gets a new SOP Instance UID and (0020,0013) Instance Number
+/// from 1. A single frame source fails with `Error::NotMultiFrame`.
+///
+/// ```no_run
+/// let ibuffer = std::fs::read("enhanced_ct.dcm").unwrap();
+/// for (i, obuffer) in gdcm_conv::split_frames(&ibuffer).unwrap().iter().enumerate() {
+///     std::fs::write(format!("slice_{}.dcm", i + 1), obuffer).unwrap();
+/// }
+/// ```
+pub fn split_frames(source: &[u8]) -> Result<Vec<Vec<u8>>, GDCMError> {
+    // Raw frames with a header each, the C side reports the needed sizes otherwise
+    let mut frames = frame_count(source)? as usize;
+    let mut capacity = estimate_output_length_per_frame(source, |t| {
+        let bytes = (t.bits_allocated as usize).div_ceil(8);
+        t.rows as usize * t.columns as usize * t.samples_per_pixel as usize * bytes
+            + MAX_HEADER_SIZE
+    })
+    .unwrap_or(0);
+
+    init();
+
+    loop {
+        let mut output: Vec<u8> = Vec::with_capacity(capacity);
+        let mut sizes: Vec<size_t> = vec![0; frames];
+
+        // Safety: the output pointer is valid for `capacity` bytes and `sizes` for `frames` items.
+        let ret = unsafe {
+            c_split_frames(
+                source.as_ptr(),
+                source.len() as size_t,
+                output.as_mut_ptr(),
+                output.capacity() as size_t,
+                sizes.as_mut_ptr(),
+                sizes.len() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > output.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    output.set_len(ret.size);
+                }
+                if ret.number_of_frames as usize > sizes.len() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                sizes.truncate(ret.number_of_frames as usize);
+
+                // The reported sizes must cover the written output exactly
+                let mut files = Vec::with_capacity(sizes.len());
+                let mut rest = output.as_slice();
+                for size in sizes {
+                    let file = rest.get(..size).ok_or(GDCMError::OversizedResult)?;
+                    files.push(file.to_vec());
+                    rest = &rest[size..];
+                }
+                if !rest.is_empty() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                return Ok(files);
+            }
+            0xFF if ret.size > capacity || ret.number_of_frames as usize > frames => {
+                capacity = capacity.max(ret.size);
+                frames = frames.max(ret.number_of_frames as usize);
+            }
+            0x01 => return Err(GDCMError::Frames(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Frames(Error::Decode)),
+            0x03 => return Err(GDCMError::Frames(Error::NotMultiFrame)),
+            0x04 => return Err(GDCMError::Frames(Error::WriteStream)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// Combine single-frame DICOM files into one multi-frame file encoded with `target`.
+///
+/// The frames are stacked in the order given, the first file provides the data set. Classic CT, MR
+/// and PET slices become a legacy converted enhanced object, with (0020,0032) Image Position
+/// (Patient), window and rescale moved to the per-frame functional groups and the orientation and
+/// pixel measures to the shared ones. Files with different rows, columns, pixel format, photometric
+/// interpretation or planar configuration fail with `Error::MismatchedFrames`.
+///
+/// ```no_run
+/// use gdcm_conv::TransferSyntax;
+///
+/// let slices = (1..=3)
+///     .map(|i| std::fs::read(format!("slice_{}.dcm", i)).unwrap())
+///     .collect();
+/// let obuffer = gdcm_conv::combine_frames(slices, TransferSyntax::JPEG2000Lossless).unwrap();
+/// ```
+pub fn combine_frames(frames: Vec<Vec<u8>>, target: TransferSyntax) -> Result<Vec<u8>, GDCMError> {
+    if frames.is_empty() {
+        return Err(GDCMError::EmptyBuffer);
+    }
+
+    // Sum of the raw inputs, the C side reports the needed size otherwise
+    let mut capacity = frames
+        .iter()
+        .map(|t| estimate_output_length(t).unwrap_or(t.len()))
+        .sum::<usize>();
+    let frame_ptrs: Vec<*const c_uchar> = frames.iter().map(|t| t.as_ptr()).collect();
+    let frame_lens: Vec<size_t> = frames.iter().map(|t| t.len() as size_t).collect();
+
+    init();
+
+    let combined = loop {
+        let mut output: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the frame pointers are valid for their lengths while `frames` lives, the output
+        // pointer for `capacity` bytes.
+        let ret = unsafe {
+            c_combine_frames(
+                frame_ptrs.as_ptr(),
+                frame_lens.as_ptr(),
+                frames.len() as size_t,
+                output.as_mut_ptr(),
+                output.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > output.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    output.set_len(ret.size);
+                }
+                break output;
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Frames(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Frames(Error::Decode)),
+            0x04 => return Err(GDCMError::Frames(Error::WriteStream)),
+            0x05 => return Err(GDCMError::Frames(Error::MismatchedFrames)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    };
+    drop(frames);
+
+    Pipeline::new().post(target).run(combined)
+}
+
+/// Transcode the frames of a DICOM file one at a time, keeping the peak memory to a single frame.
+///
+/// Only the requested frame is decoded from the borrowed source, then encoded to the target transfer
+/// syntax. Each item is the encoded frame: the codestream of an encapsulated transfer syntax, or the
+/// raw little endian pixel bytes for Implicit and Explicit VR Little Endian. Encoding uses the codec
+/// defaults, quality parameters of the transfer syntax are ignored. Deflated and Big Endian targets
+/// fail with `Error::InvalidTransferSyntax`, decode only ones with `Error::UnsupportedTarget`.
+///
+/// ```no_run
+/// use gdcm_conv::{FrameTranscoder, TransferSyntax};
+///
+/// let ibuffer = std::fs::read("test.dcm").unwrap();
+/// for frame in FrameTranscoder::new(&ibuffer, TransferSyntax::JPEG2000Lossless).unwrap() {
+///     let codestream = frame.unwrap();
+///     // ...
+/// }
+/// ```
+pub struct FrameTranscoder<'a> {
+    source: &'a [u8],
+    transfer_syntax: TransferSyntax,
+    frame_length: usize,
+    next: u32,
+    number_of_frames: u32,
+}
+
+impl<'a> FrameTranscoder<'a> {
+    pub fn new(
+        source: &'a [u8],
+        transfer_syntax: TransferSyntax,
+    ) -> Result<FrameTranscoder<'a>, GDCMError> {
+        if transfer_syntax.is_decode_only() {
+            return Err(GDCMError::Post(Error::UnsupportedTarget));
+        }
+
+        let info = read_image_info(source)?;
+        let bytes = (info.bits_allocated as usize).div_ceil(8);
+
+        Ok(FrameTranscoder {
+            source,
+            transfer_syntax,
+            frame_length: info.rows as usize
+                * info.columns as usize
+                * info.samples_per_pixel as usize
+                * bytes,
+            next: 0,
+            number_of_frames: info.number_of_frames.max(1),
+        })
+    }
+
+    /// Number of frames of the source.
+    pub fn number_of_frames(&self) -> u32 {
+        self.number_of_frames
+    }
+
+    fn transcode(&self, frame_index: u32) -> Result<Vec<u8>, GDCMError> {
+        // Raw frame size, the C side reports the needed size otherwise
+        let mut capacity = self.frame_length;
+
+        init();
+
+        loop {
+            let mut frame: Vec<u8> = Vec::with_capacity(capacity);
+
+            // Safety: the output pointer is valid for `capacity` bytes.
+            let ret = unsafe {
+                c_transcode_frame(
+                    self.source.as_ptr(),
+                    self.source.len() as size_t,
+                    frame_index,
+                    self.transfer_syntax.to_id(),
+                    frame.as_mut_ptr(),
+                    frame.capacity() as size_t,
+                )
+            };
+
+            match ret.status {
+                0x00 => {
+                    if ret.size > frame.capacity() {
+                        return Err(GDCMError::OversizedResult);
+                    }
+                    unsafe {
+                        frame.set_len(ret.size);
+                    }
+                    return Ok(frame);
+                }
+                0xFF if ret.size > capacity => capacity = ret.size,
+                0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+                0x02 => return Err(GDCMError::Read(Error::Decode)),
+                0x03 => {
+                    return Err(GDCMError::FrameOutOfRange(
+                        frame_index,
+                        ret.number_of_frames,
+                    ))
+                }
+                0x04 => return Err(GDCMError::Post(Error::ExecuteChange)),
+                0x05 => return Err(GDCMError::Post(Error::InvalidTransferSyntax)),
+                0x0E => return Err(GDCMError::Exception),
+                0x0F => return Err(GDCMError::PointerNULL),
+                0x1F => return Err(GDCMError::EmptyBuffer),
+                t => return Err(GDCMError::Unknown(t)),
+            }
+        }
+    }
+}
+
+impl Iterator for FrameTranscoder<'_> {
+    type Item = Result<Vec<u8>, GDCMError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.number_of_frames {
+            return None;
+        }
+
+        let frame_index = self.next;
+        self.next += 1;
+
+        Some(self.transcode(frame_index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let t = (self.number_of_frames - self.next) as usize;
+        (t, Some(t))
+    }
+}
+
+/// Decode the pixel data of all frames, with the hash stored by `Pipeline::embed_pixel_hash`.
+#[cfg(feature = "sha2")]
+fn decode_pixel_data(source: &[u8]) -> Result<(Vec<u8>, Option<String>), GDCMError> {
+    let mut capacity = source.len();
+
+    init();
+
+    loop {
+        let mut pixels: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the output pointer is valid for `capacity` bytes.
+        let ret = unsafe {
+            c_pixel_data(
+                source.as_ptr(),
+                source.len() as size_t,
+                pixels.as_mut_ptr(),
+                pixels.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > pixels.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    pixels.set_len(ret.size);
+                }
+                // Safety: the C side NUL terminates the hash.
+                let hash = unsafe { CStr::from_ptr(ret.pixel_hash.as_ptr()) };
+                let hash = hash.to_string_lossy().into_owned();
+                return Ok((pixels, Some(hash).filter(|t| !t.is_empty())));
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Read(Error::Decode)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// SHA-256 as lowercase hex.
+#[cfg(feature = "sha2")]
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(data)
+        .iter()
+        .map(|t| format!("{:02x}", t))
+        .collect()
+}
+
+/// Store the hash of the decoded pixel data in a converted file.
+#[cfg(feature = "sha2")]
+fn embed_pixel_hash(source: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+    let (pixels, _) = decode_pixel_data(&source)?;
+
+    let pipeline = Pipeline {
+        // Room for the private block
+        estimated_length: Some(source.len() + 256),
+        pixel_hash: Some(sha256_hex(&pixels)),
+        ..Pipeline::default()
+    };
+    let params = Params::new(&pipeline)?;
+
+    ffi_guard(|| convert(source, &pipeline, &params)).map(|t| t.0)
+}
+
+/// Check the pixel data against the hash stored by `Pipeline::embed_pixel_hash`.
+///
+/// Returns `false` if the hash does not match or the file has none. Requires the `sha2` feature.
+#[cfg(feature = "sha2")]
+pub fn verify_pixel_hash(source: &[u8]) -> Result<bool, GDCMError> {
+    let (pixels, hash) = decode_pixel_data(source)?;
+
+    Ok(hash.is_some_and(|t| t.eq_ignore_ascii_case(&sha256_hex(&pixels))))
+}
+
+/// Make a baseline JPEG thumbnail (not DICOM) of the first frame.
+///
+/// Applies the rescale and the first window (0028,1050)/(0028,1051) to monochrome images, or the full
+/// pixel range when missing. The image is downsampled to fit `max_dim` (0 keeps the original size),
+/// `quality` 0 uses the codec default.
+pub fn make_thumbnail(source: &[u8], max_dim: u32, quality: u32) -> Result<Vec<u8>, GDCMError> {
+    // Raw RGB size as a worst case, the C side reports the needed size otherwise
+    let mut capacity = max_dim as usize * max_dim as usize * 3;
+
+    init();
+
+    loop {
+        let mut output: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the output pointer is valid for `capacity` bytes.
+        let ret = unsafe {
+            c_thumbnail(
+                source.as_ptr(),
+                source.len() as size_t,
+                max_dim,
+                quality as c_int,
+                output.as_mut_ptr(),
+                output.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > output.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    output.set_len(ret.size);
+                }
+                return Ok(output);
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Thumbnail(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Thumbnail(Error::Decode)),
+            0x03 => return Err(GDCMError::Thumbnail(Error::Encode)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// Render a frame as interleaved 8-bit RGB (not DICOM), for canvases and image libraries.
+///
+/// Monochrome images are rescaled then windowed with `window` as (center, width), else the first
+/// window (0028,1050)/(0028,1051), else the full pixel range, and replicated to the three channels.
+/// Palette color is expanded with its lookup tables and YBR converted to RGB.
+pub fn render_rgb(
+    source: &[u8],
+    frame: u32,
+    window: Option<(f64, f64)>,
+) -> Result<RgbImage, GDCMError> {
+    // RGB size from the header, the C side reports the needed size otherwise
+    let mut capacity = match read_image_info(source) {
+        Ok(t) => t.rows as usize * t.columns as usize * 3,
+        Err(_) => 0,
+    };
+    let (center, width) = window.unwrap_or((0.0, 0.0));
+
+    init();
+
+    loop {
+        let mut data: Vec<u8> = Vec::with_capacity(capacity);
+
+        // Safety: the output pointer is valid for `capacity` bytes.
+        let ret = unsafe {
+            c_render_rgb(
+                source.as_ptr(),
+                source.len() as size_t,
+                frame,
+                window.is_some() as c_char,
+                center as c_double,
+                width as c_double,
+                data.as_mut_ptr(),
+                data.capacity() as size_t,
+            )
+        };
+
+        match ret.status {
+            0x00 => {
+                if ret.size > data.capacity() {
+                    return Err(GDCMError::OversizedResult);
+                }
+                unsafe {
+                    data.set_len(ret.size);
+                }
+                return Ok(RgbImage {
+                    width: ret.columns,
+                    height: ret.rows,
+                    data,
+                });
+            }
+            0xFF if ret.size > capacity => capacity = ret.size,
+            0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+            0x02 => return Err(GDCMError::Read(Error::Decode)),
+            0x03 => return Err(GDCMError::FrameOutOfRange(frame, ret.number_of_frames)),
+            0x0E => return Err(GDCMError::Exception),
+            0x0F => return Err(GDCMError::PointerNULL),
+            0x1F => return Err(GDCMError::EmptyBuffer),
+            t => return Err(GDCMError::Unknown(t)),
+        }
+    }
+}
+
+/// Estimate the output length of a conversion, using the worst case of a raw image.
+///
+/// Reads bits allocated, photometric interpretation, number of frames, rows and columns from the header.
+/// Returns `None` if the header could not be parsed or the bits allocated are not 1, 8, 16, 32 or 64,
+/// so the caller can fall back to the default estimation.
+pub fn estimate_output_length(source: &[u8]) -> Option<usize> {
+    let info = read_image_info(source).ok()?;
+
+    let a = match info.bits_allocated {
+        t @ (1 | 8 | 16 | 32 | 64) => t as usize,
+        _ => return None,
+    };
+
+    let b = match info.photometric_interpretation.as_str() {
+        "MONOCHROME1" => 1,
+        "MONOCHROME2" => 1,
+        _ => 3,
+    };
+    let pixels = info.rows as usize * info.columns as usize * info.number_of_frames as usize;
+
+    Some((a * b * pixels).div_ceil(8) + MAX_HEADER_SIZE)
+}
+
+/// Estimate the output length of a conversion from a caller supplied size of one output frame.
+///
+/// The closure receives the header attributes and returns the bytes of a single frame, the total is
+/// that size times the number of frames plus `MAX_HEADER_SIZE`. Returns `None` if the header could
+/// not be parsed.
+///
+/// ```no_run
+/// use gdcm_conv::{Pipeline, TransferSyntax};
+///
+/// let ibuffer = std::fs::read("test.dcm").unwrap();
+/// // JPEG 2000 lossless, about half of the raw frame
+/// let estimated_length = gdcm_conv::estimate_output_length_per_frame(&ibuffer, |t| {
+///     (t.rows * t.columns * t.samples_per_pixel * t.bits_allocated / 8) as usize / 2
+/// })
+/// .unwrap();
+///
+/// let obuffer = Pipeline::new()
+///     .post(TransferSyntax::JPEG2000Lossless)
+///     .estimated_length(estimated_length)
+///     .run(ibuffer)
+///     .unwrap();
+/// ```
+pub fn estimate_output_length_per_frame<F: Fn(&ImageInfo) -> usize>(
+    source: &[u8],
+    frame_length: F,
+) -> Option<usize> {
+    let info = read_image_info(source).ok()?;
+
+    Some(frame_length(&info) * info.number_of_frames as usize + MAX_HEADER_SIZE)
+}
+
+/// Maximum number of re-allocations when the output does not fit the estimated length.
+const MAX_RETRIES: u32 = 3;
+
+/// Checksum algorithms of `Pipeline::output_integrity`. Requires the `integrity` feature.
+#[cfg(feature = "integrity")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IntegrityAlgorithm {
+    /// CRC-32 (IEEE 802.3, as zlib and PNG), in the low 32 bits of the value.
+    Crc32,
+    /// XXH64 with seed 0.
+    Xxh64,
+}
+
+#[cfg(feature = "integrity")]
+impl IntegrityAlgorithm {
+    fn digest(self, data: &[u8]) -> u64 {
+        match self {
+            IntegrityAlgorithm::Crc32 => u64::from(crc32fast::hash(data)),
+            IntegrityAlgorithm::Xxh64 => xxhash_rust::xxh64::xxh64(data, 0),
+        }
+    }
+}
+
+/// Checksum of the whole output buffer, for transport verification.
+#[cfg(feature = "integrity")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutputIntegrity {
+    /// Algorithm of the checksum.
+    pub algorithm: IntegrityAlgorithm,
+    /// Checksum value.
+    pub value: u64,
+}
+
+#[cfg(feature = "integrity")]
+impl OutputIntegrity {
+    /// Compute the checksum of a buffer.
+    pub fn compute(algorithm: IntegrityAlgorithm, data: &[u8]) -> Self {
+        OutputIntegrity {
+            algorithm,
+            value: algorithm.digest(data),
+        }
+    }
+
+    /// Whether the buffer matches the checksum, e.g. on the receiver side.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        self.algorithm.digest(data) == self.value
+    }
+}
+
+/// Conversion metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// Input file size in bytes.
+    pub input_size: usize,
+    /// Output file size in bytes.
+    pub output_size: usize,
+    /// Number of re-allocations because the estimated length was not enough.
+    pub retries: u32,
+    /// Transfer syntax of the input, `None` when it could not be detected.
+    pub source_transfer_syntax: Option<TransferSyntax>,
+    /// Warnings and errors logged by GDCM during the conversion, e.g. fixed VRs of the source.
+    ///
+    /// The bundled GDCM always logs them. A system GDCM built without `GDCM_ALWAYS_TRACE_MACRO`
+    /// only does in debug builds, so the list stays empty otherwise.
+    pub warnings: Vec<String>,
+    /// Checksum of the output, when requested with `Pipeline::output_integrity`.
+    #[cfg(feature = "integrity")]
+    pub integrity: Option<OutputIntegrity>,
+}
+
+impl ConversionReport {
+    /// Whether the output buffer was re-allocated.
+    pub fn reallocated(&self) -> bool {
+        self.retries > 0
+    }
+
+    /// Input size over output size, greater than 1 when the output is smaller.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.output_size == 0 {
+            return 0.0;
+        }
+
+        self.input_size as f64 / self.output_size as f64
+    }
+}
+
+/// Anonymization options.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnonOptions {
+    /// Remove all private tags.
+    pub remove_private_tags: bool,
+    /// Replace (0010,0010) Patient's Name.
+    pub patient_name: Option<String>,
+    /// Regenerate Study, Series, SOP Instance and Frame of Reference UIDs.
+    pub regenerate_uids: bool,
+}
+
+/// JPEG 2000 progression order, the order of the packets in the codestream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum J2kProgressionOrder {
+    /// Layer, resolution, component, position, the GDCM default.
+    LRCP,
+    /// Resolution, layer, component, position.
+    RLCP,
+    /// Resolution, position, component, layer, e.g. for tiled viewers streaming resolutions.
+    RPCL,
+    /// Position, component, resolution, layer.
+    PCRL,
+    /// Component, position, resolution, layer.
+    CPRL,
+}
+
+impl J2kProgressionOrder {
+    pub fn to_id(self) -> i32 {
+        match self {
+            J2kProgressionOrder::LRCP => 0,
+            J2kProgressionOrder::RLCP => 1,
+            J2kProgressionOrder::RPCL => 2,
+            J2kProgressionOrder::PCRL => 3,
+            J2kProgressionOrder::CPRL => 4,
+        }
+    }
+}
+
+/// JPEG 2000 encoder options, unset fields keep the GDCM defaults.
+///
+/// GDCM does not expose the codeblock size nor the progression order of its encoder, once one is
+/// set the frames are encoded with OpenJPEG directly, one fragment per frame. 8 and 16 bits
+/// allocated only.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct J2kOptions {
+    /// Number of resolutions, the decomposition levels plus one.
+    pub resolutions: Option<u32>,
+    /// Tile width and height.
+    pub tile_size: Option<(u32, u32)>,
+    /// Codeblock width and height, powers of 2 from 4 to 1024 with at most 4096 samples, 64x64 by
+    /// default. Invalid sizes fail with `Error::ExecuteChange`.
+    pub codeblock_size: Option<(u32, u32)>,
+    /// Progression order, LRCP by default.
+    pub progression_order: Option<J2kProgressionOrder>,
+}
+
+/// JPEG-LS interleave mode of color images.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JpegLsInterleaveMode {
+    /// One scan per component.
+    None,
+    /// Components interleaved line by line.
+    Line,
+    /// Components interleaved pixel by pixel, the GDCM default.
+    Sample,
+}
+
+impl JpegLsInterleaveMode {
+    pub fn to_id(self) -> i32 {
+        match self {
+            JpegLsInterleaveMode::None => 0,
+            JpegLsInterleaveMode::Line => 1,
+            JpegLsInterleaveMode::Sample => 2,
+        }
+    }
+}
+
+/// JPEG-LS encoder options, unset fields keep the GDCM defaults.
+///
+/// GDCM does not expose these parameters of its codec, once one is set the frames are encoded
+/// with CharLS directly, one fragment per frame. 8 and 16 bits allocated only.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct JpegLsOptions {
+    /// Interleave mode, ignored for monochrome images.
+    pub interleave_mode: Option<JpegLsInterleaveMode>,
+    /// Gradient threshold T1 of the preset coding parameters.
+    pub t1: Option<u16>,
+    /// Gradient threshold T2 of the preset coding parameters.
+    pub t2: Option<u16>,
+    /// Gradient threshold T3 of the preset coding parameters.
+    pub t3: Option<u16>,
+    /// Context counters RESET value of the preset coding parameters.
+    pub reset: Option<u16>,
+}
+
+/// Conversion pipeline builder.
+///
+/// Every stage defaults to `None`, unset stages are skipped.
+///
+/// ```no_run
+/// use gdcm_conv::{Pipeline, TransferSyntax};
+///
+/// let ibuffer = std::fs::read("test.dcm").unwrap();
+/// let obuffer = Pipeline::new()
+///     .post(TransferSyntax::JPEG2000Lossless)
+///     .run(ibuffer)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    skip_if_same: bool,
+    anon: Option<AnonOptions>,
+    regenerate_uids: Option<bool>,
+    apply_rescale: bool,
+    bake_voi_lut: bool,
+    voi_lut_index: u32,
+    overlays: OverlayPolicy,
+    icc_profile: IccPolicy,
+    planar_configuration: u32,
+    target_bits: Option<u8>,
+    to_utf8: bool,
+    derivation_note: Option<String>,
+    j2k: J2kOptions,
+    jpegls: JpegLsOptions,
+    header_only: bool,
+    strip_private: bool,
+    keep_private_creators: Vec<String>,
+    implementation_class_uid: Option<String>,
+    implementation_version_name: Option<String>,
+    preserve_implementation: bool,
+    preserve_structure: bool,
+    force_extended_offset_table: bool,
+    preserve_spatial: bool,
+    embed_pixel_hash: bool,
+    pixel_hash: Option<String>,
+    strict: bool,
+    assume_raw_dataset: bool,
+    single_fragment_per_frame: bool,
+    rebuild_offset_table: bool,
+    max_frames: Option<u32>,
+    tag_overrides: Vec<(u16, u16, String)>,
+    trust_detected_photometric: bool,
+    #[cfg(feature = "integrity")]
+    output_integrity: Option<IntegrityAlgorithm>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline {
+            estimated_length: None,
+            transfer_syntax_pre: TransferSyntax::None,
+            photometric_interpretation: PhotometricInterpretation::None,
+            transfer_syntax_post: TransferSyntax::None,
+            skip_if_same: false,
+            anon: None,
+            regenerate_uids: None,
+            apply_rescale: false,
+            bake_voi_lut: false,
+            voi_lut_index: 0,
+            overlays: OverlayPolicy::Preserve,
+            icc_profile: IccPolicy::Preserve,
+            planar_configuration: 0,
+            target_bits: None,
+            to_utf8: false,
+            derivation_note: None,
+            j2k: J2kOptions::default(),
+            jpegls: JpegLsOptions::default(),
+            header_only: false,
+            strip_private: false,
+            keep_private_creators: Vec::new(),
+            implementation_class_uid: None,
+            implementation_version_name: None,
+            preserve_implementation: false,
+            preserve_structure: false,
+            force_extended_offset_table: false,
+            preserve_spatial: false,
+            embed_pixel_hash: false,
+            pixel_hash: None,
+            strict: false,
+            assume_raw_dataset: false,
+            single_fragment_per_frame: true,
+            rebuild_offset_table: false,
+            max_frames: None,
+            tag_overrides: Vec::new(),
+            trust_detected_photometric: false,
+            #[cfg(feature = "integrity")]
+            output_integrity: None,
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// First transfer syntax conversion (PRE-TRANSFER).
+    pub fn pre(mut self, transfer_syntax: TransferSyntax) -> Self {
+        self.transfer_syntax_pre = transfer_syntax;
+        self
+    }
+
+    /// Photometric conversion.
+    ///
+    /// A photometric interpretation the output transfer syntax can not encode, e.g. palette color
+    /// with JPEG 2000, fails with `Error::InvalidCombination` before GDCM is called. Without a post
+    /// transfer syntax the output keeps the pre transfer syntax, the one of the source otherwise.
+    pub fn photometric(mut self, photometric_interpretation: PhotometricInterpretation) -> Self {
+        self.photometric_interpretation = photometric_interpretation;
+        self
+    }
+
+    /// Second transfer syntax conversion (POST-TRANSFER).
+    pub fn post(mut self, transfer_syntax: TransferSyntax) -> Self {
+        self.transfer_syntax_post = transfer_syntax;
+        self
+    }
+
+    /// Estimated total output length, see `estimate_output_length`.
+    pub fn estimated_length(mut self, estimated_length: usize) -> Self {
+        self.estimated_length = Some(estimated_length);
+        self
+    }
+
+    /// Return the source untouched when it is already in the POST-TRANSFER syntax
+    /// and there is no photometric conversion, rescale, VOI LUT, overlay, ICC profile, bit depth
+    /// or character set change, derivation note, JPEG 2000 or JPEG-LS options, fragmentation or
+    /// offset table change, anonymization, UID regeneration, private tag removal or implementation
+    /// change.
+    pub fn skip_if_same(mut self, skip_if_same: bool) -> Self {
+        self.skip_if_same = skip_if_same;
+        self
+    }
+
+    /// Anonymization, executed between the photometric conversion and the POST-TRANSFER.
+    pub fn anon(mut self, anon: AnonOptions) -> Self {
+        self.anon = Some(anon);
+        self
+    }
+
+    /// Regenerate (0008,0018) SOP Instance UID and (0002,0003) Media Storage SOP Instance UID on output.
+    ///
+    /// By default the UIDs are preserved for lossless conversions and regenerated for lossy ones,
+    /// as the pixel data changes.
+    pub fn regenerate_uids(mut self, regenerate_uids: bool) -> Self {
+        self.regenerate_uids = Some(regenerate_uids);
+        self
+    }
+
+    /// Bake (0028,1053) Rescale Slope and (0028,1052) Rescale Intercept into the pixel values
+    /// during the photometric stage, removing the rescale attributes from the output.
+    ///
+    /// Only applies to single sample images, images without the attributes are left untouched.
+    pub fn apply_rescale(mut self, apply_rescale: bool) -> Self {
+        self.apply_rescale = apply_rescale;
+        self
+    }
+
+    /// Apply the window (0028,1050) Window Center and (0028,1051) Window Width during the photometric
+    /// stage, producing 8-bit MONOCHROME2 pixel data for presentation (e.g. before lossy JPEG).
+    ///
+    /// The VOI and rescale attributes are removed from the output. Images without a window use
+    /// the full pixel range.
+    pub fn bake_voi_lut(mut self, bake_voi_lut: bool) -> Self {
+        self.bake_voi_lut = bake_voi_lut;
+        self
+    }
+
+    /// Window used by `bake_voi_lut` when the image has several, the first one by default.
+    pub fn voi_lut_index(mut self, voi_lut_index: u32) -> Self {
+        self.voi_lut_index = voi_lut_index;
+        self
+    }
+
+    /// How (60xx,eeee) overlay planes are handled, `OverlayPolicy::Preserve` by default.
+    pub fn overlays(mut self, overlays: OverlayPolicy) -> Self {
+        self.overlays = overlays;
+        self
+    }
+
+    /// How the (0028,2000) ICC Profile is handled by the photometric stage, `IccPolicy::Preserve` by default.
+    pub fn icc_profile(mut self, icc_profile: IccPolicy) -> Self {
+        self.icc_profile = icc_profile;
+        self
+    }
+
+    /// (0028,0006) Planar Configuration of the output when converting to `PhotometricInterpretation::RGB`:
+    /// 0 interleaved (default, most broadly compatible) or 1 separated planes.
+    pub fn planar_configuration(mut self, planar_configuration: u32) -> Self {
+        self.planar_configuration = planar_configuration;
+        self
+    }
+
+    /// Bits allocated of the output, 8 or 16.
+    ///
+    /// With 8, single sample images of more than 8 bits are windowed as in `bake_voi_lut` and
+    /// rescaled to 8-bit MONOCHROME2 during the photometric stage. Images already within the
+    /// target are left untouched.
+    pub fn target_bits(mut self, target_bits: u8) -> Self {
+        self.target_bits = Some(target_bits);
+        self
+    }
+
+    /// Re-encode string values to UTF-8 and set (0008,0005) Specific Character Set to `ISO_IR 192`,
+    /// executed between the anonymization and the POST-TRANSFER.
+    ///
+    /// Multi-valued character sets with ISO 2022 code extensions (e.g. `\ISO 2022 IR 87` Japanese)
+    /// are decoded following the escape sequences. Uses `iconv`, not available on Windows.
+    pub fn to_utf8(mut self, to_utf8: bool) -> Self {
+        self.to_utf8 = to_utf8;
+        self
+    }
+
+    /// (0008,2111) Derivation Description written on output.
+    ///
+    /// Lossy conversions always write a Derivation Description and a Derivation Code Sequence
+    /// (113040 Lossy Compression), by default with a text naming the source transfer syntax UID and
+    /// the applied quality. Lossless conversions only write the description when it is given.
+    pub fn derivation_note(mut self, derivation_note: String) -> Self {
+        self.derivation_note = Some(derivation_note);
+        self
+    }
+
+    /// JPEG 2000 encoder options, used by the JPEG 2000 transfer syntaxes.
+    pub fn j2k(mut self, j2k: J2kOptions) -> Self {
+        self.j2k = j2k;
+        self
+    }
+
+    /// JPEG-LS encoder options, used by the JPEG-LS transfer syntaxes.
+    ///
+    /// Invalid coding parameters, e.g. T1 greater than T2, fail with `Error::ExecuteChange`.
+    pub fn jpegls(mut self, jpegls: JpegLsOptions) -> Self {
+        self.jpegls = jpegls;
+        self
+    }
+
+    /// Transcode only the data set in the transfer syntax stages, the pixel data is never decoded
+    /// or recompressed.
+    ///
+    /// Only works when the source and target pixel encodings are compatible: raw pixel data moves
+    /// between Implicit, Explicit and Deflated Explicit VR Little Endian, and encapsulated pixel data
+    /// keeps its transfer syntax (its data set is always Explicit VR Little Endian), decode only ones
+    /// included. Any other target fails with `Error::IncompatibleEncoding`.
+    pub fn header_only(mut self, header_only: bool) -> Self {
+        self.header_only = header_only;
+        self
+    }
+
+    /// Do not reformat a data set that is already Explicit VR beyond the transfer syntax change:
+    /// element VRs, including the private ones, are written as read and group length elements are
+    /// kept. An Implicit VR data set still gets its VRs from the dictionary. Elements are always
+    /// written in ascending tag order, as required by the standard.
+    ///
+    /// Can not be combined with a photometric conversion, which fails with
+    /// `Error::IncompatibleOptions`.
+    pub fn preserve_structure(mut self, preserve_structure: bool) -> Self {
+        self.preserve_structure = preserve_structure;
+        self
+    }
+
+    /// Write the frame offsets of encapsulated pixel data in an Extended Offset Table (7FE0,0001)
+    /// and (7FE0,0002), leaving the Basic Offset Table empty.
+    ///
+    /// Outputs larger than 4 GiB always get it, as their offsets do not fit the 32 bits of the Basic
+    /// Offset Table. The pixel data must have one fragment per frame, otherwise a forced table fails
+    /// with `Error::WriteStream`.
+    pub fn force_extended_offset_table(mut self, force_extended_offset_table: bool) -> Self {
+        self.force_extended_offset_table = force_extended_offset_table;
+        self
+    }
+
+    /// Restore (0028,0030) Pixel Spacing and (0018,0050) Slice Thickness of the source after the
+    /// last stage. If the rows or columns of the output differ from the source, the pixel spacing
+    /// is scaled so the physical size of the image is kept.
+    pub fn preserve_spatial(mut self, preserve_spatial: bool) -> Self {
+        self.preserve_spatial = preserve_spatial;
+        self
+    }
+
+    /// Store the SHA-256 of the decoded pixel data of the output in the "GDCM_CONV" private block
+    /// of group 0x0099, see `verify_pixel_hash`.
+    ///
+    /// The hash is computed over the decoded pixels, so it does not change with a lossless
+    /// re-encoding. Not available for Encapsulated Uncompressed Explicit VR Little Endian outputs.
+    /// Requires the `sha2` feature.
+    #[cfg(feature = "sha2")]
+    pub fn embed_pixel_hash(mut self, embed_pixel_hash: bool) -> Self {
+        self.embed_pixel_hash = embed_pixel_hash;
+        self
+    }
+
+    /// Reject sources GDCM would otherwise tolerate with `GDCMError::NonConformant`: a missing
+    /// preamble or file meta information, a data set not encoded with the declared transfer syntax,
+    /// odd value lengths in the top level data set, or Bits Stored and High Bit inconsistent with
+    /// Bits Allocated.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Read the source as a data set without preamble and file meta information (NEMA / raw
+    /// streams), even if it starts with bytes looking like a preamble. Sources without the "DICM"
+    /// prefix are detected automatically. The output is always a Part 10 file.
+    pub fn assume_raw_dataset(mut self, assume_raw_dataset: bool) -> Self {
+        self.assume_raw_dataset = assume_raw_dataset;
+        self
+    }
+
+    /// Hold each frame of a JPEG 2000 output in a single fragment and fill the Basic Offset Table
+    /// with one entry per frame, as some viewers require. Enabled by default, when disabled the
+    /// fragmentation of the encoder is kept.
+    pub fn single_fragment_per_frame(mut self, single_fragment_per_frame: bool) -> Self {
+        self.single_fragment_per_frame = single_fragment_per_frame;
+        self
+    }
+
+    /// Write the Basic Offset Table of encapsulated outputs from the fragments, also when the
+    /// transfer syntax is kept, so an empty or corrupt table of the source is repaired without
+    /// recompressing the pixel data.
+    ///
+    /// Frames are found by their JPEG or JPEG 2000 start marker when there is not one fragment per
+    /// frame, otherwise the conversion fails with `Error::WriteStream`.
+    pub fn rebuild_offset_table(mut self, rebuild_offset_table: bool) -> Self {
+        self.rebuild_offset_table = rebuild_offset_table;
+        self
+    }
+
+    /// Keep only the first frames of a multi-frame source, e.g. for a preview of a long cine loop.
+    ///
+    /// The source is cut before any decoding, so only the kept frames are transcoded. (0028,0008)
+    /// Number of Frames, the (5200,9230) Per-frame Functional Groups Sequence and (0018,1065) Frame
+    /// Time Vector are truncated accordingly. Encapsulated frames are told apart as in
+    /// `rebuild_offset_table`, otherwise the conversion fails with `Error::ExecuteChange`. Sources
+    /// with fewer frames are left untouched, 0 keeps all frames.
+    pub fn max_frames(mut self, max_frames: u32) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    /// Set or replace an element of the data set before the POST-TRANSFER stage, e.g. to correct
+    /// (0008,0060) Modality. Setting the same tag again replaces the value.
+    ///
+    /// The VR comes from the dictionary, private and unknown tags are written as UN. Numbers of
+    /// US, SS, UL, SL, FL, FD and AT elements are given as strings, multiple values separated by
+    /// backslashes. Values are written as given, non-ASCII text needs a matching (0008,0005)
+    /// Specific Character Set. Sequences, other binary VRs, the pixel data and the file meta
+    /// information fail with `Error::InvalidOverride`.
+    pub fn set_tag(mut self, group: u16, element: u16, value: &str) -> Self {
+        self.tag_overrides
+            .retain(|t| (t.0, t.1) != (group, element));
+        self.tag_overrides.push((group, element, value.to_string()));
+        self
+    }
+
+    /// Relabel (0028,0004) with the interpretation told by `detect_true_photometric` before any
+    /// stage, e.g. for RGB pixel data declared as YBR_FULL that would otherwise come out with
+    /// inverted colors. Only the tag is changed, the pixel data is kept as is.
+    pub fn trust_detected_photometric(mut self, trust_detected_photometric: bool) -> Self {
+        self.trust_detected_photometric = trust_detected_photometric;
+        self
+    }
+
+    /// Compute a checksum of the whole output buffer into `ConversionReport::integrity`, so the
+    /// transfer layer can verify it without reading the output again. Requires the `integrity`
+    /// feature.
+    #[cfg(feature = "integrity")]
+    pub fn output_integrity(mut self, algorithm: IntegrityAlgorithm) -> Self {
+        self.output_integrity = Some(algorithm);
+        self
+    }
+
+    /// Remove all private (odd group) elements, executed with the anonymization.
+    pub fn strip_private(mut self, strip_private: bool) -> Self {
+        self.strip_private = strip_private;
+        self
+    }
+
+    /// Private creators, e.g. "SIEMENS CSA HEADER", whose blocks survive `strip_private`.
+    pub fn keep_private_creators(mut self, keep_private_creators: Vec<String>) -> Self {
+        self.keep_private_creators = keep_private_creators;
+        self
+    }
+
+    /// (0002,0012) Implementation Class UID of the output, instead of the GDCM default.
+    pub fn implementation_class_uid(mut self, implementation_class_uid: String) -> Self {
+        self.implementation_class_uid = Some(implementation_class_uid);
+        self
+    }
+
+    /// (0002,0013) Implementation Version Name of the output, instead of the GDCM default.
+    pub fn implementation_version_name(mut self, implementation_version_name: String) -> Self {
+        self.implementation_version_name = Some(implementation_version_name);
+        self
+    }
+
+    /// Keep the source Implementation Class UID and Version Name on output.
+    /// Values given with `implementation_class_uid` and `implementation_version_name` take precedence.
+    pub fn preserve_implementation(mut self, preserve_implementation: bool) -> Self {
+        self.preserve_implementation = preserve_implementation;
+        self
+    }
+
+    /// Execute the pipeline over the DICOM file buffer.
+    pub fn run(&self, source: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+        self.run_with_report(source).map(|(t, _)| t)
+    }
+
+    /// Execute the pipeline over the DICOM file buffer, returning conversion metadata.
+    pub fn run_with_report(
+        &self,
+        source: Vec<u8>,
+    ) -> Result<(Vec<u8>, ConversionReport), GDCMError> {
+        self.execute(source, Params::new(self)?)
+    }
+
+    /// Execute the pipeline over the DICOM file buffer, reporting progress as `(current, total)` frames.
+    ///
+    /// The callback is invoked from the C side, a panic inside it is caught and never unwinds
+    /// across the FFI boundary.
+    pub fn run_with_progress<F: FnMut(u32, u32)>(
+        &self,
+        source: Vec<u8>,
+        mut on_progress: F,
+    ) -> Result<Vec<u8>, GDCMError> {
+        let mut params = Params::new(self)?;
+        params.options.progress = Some(progress_trampoline::<F>);
+        params.options.progress_data = &mut on_progress as *mut F as *mut c_void;
+
+        self.execute(source, params).map(|(t, _)| t)
+    }
+
+    /// Execute the pipeline over the DICOM file buffer, stopping with `GDCMError::Cancelled`
+    /// as soon as the flag is set. The flag is checked between stages and frames.
+    pub fn run_with_cancel(
+        &self,
+        source: Vec<u8>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result<Vec<u8>, GDCMError> {
+        let mut params = Params::new(self)?;
+        params.options.cancelled = Some(cancel_trampoline);
+        params.options.cancel_data = &*cancel as *const AtomicBool as *mut c_void;
+
+        self.execute(source, params).map(|(t, _)| t)
+    }
+
+    /// Execute the pipeline on a worker thread, returning `GDCMError::Timeout` if it does not
+    /// finish within the limit.
+    ///
+    /// The C call can not be interrupted: on timeout the conversion is cancelled at the next
+    /// stage or frame check, but the worker thread keeps running until GDCM returns.
+    pub fn run_with_timeout(
+        &self,
+        source: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, GDCMError> {
+        let pipeline = self.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let _ = sender.send(pipeline.run_with_cancel(source, worker_cancel));
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(t) => t,
+            Err(_) => {
+                cancel.store(true, Ordering::Relaxed);
+                Err(GDCMError::Timeout)
+            }
+        }
+    }
+
+    fn execute(
+        &self,
+        source: Vec<u8>,
+        mut params: Params,
+    ) -> Result<(Vec<u8>, ConversionReport), GDCMError> {
+        validate_source(&source)?;
+        validate_output_combination(&source, self)?;
+
+        let mut report = ConversionReport {
+            input_size: source.len(),
+            source_transfer_syntax: read_transfer_syntax(&source).ok(),
+            ..ConversionReport::default()
+        };
+
+        if self.skip_if_same
+            && !self.apply_rescale
+            && !self.bake_voi_lut
+            && self.overlays == OverlayPolicy::Preserve
+            && self.icc_profile == IccPolicy::Preserve
+            && self.target_bits.is_none()
+            && !self.to_utf8
+            && self.derivation_note.is_none()
+            && self.j2k == J2kOptions::default()
+            && self.jpegls == JpegLsOptions::default()
+            && self.anon.is_none()
+            && self.regenerate_uids != Some(true)
+            && !self.strip_private
+            && self.implementation_class_uid.is_none()
+            && self.implementation_version_name.is_none()
+            && !self.embed_pixel_hash
+            && !self.strict
+            && !self.assume_raw_dataset
+            && !self.rebuild_offset_table
+            && !self.force_extended_offset_table
+            && self.single_fragment_per_frame
+            && self.max_frames.is_none()
+            && self.tag_overrides.is_empty()
+            && !self.trust_detected_photometric
+            && has_preamble(&source)
+        {
+            if let PhotometricInterpretation::None = self.photometric_interpretation {
+                if let Some(t) = report.source_transfer_syntax {
+                    if t.to_id() == self.transfer_syntax_post.to_id() {
+                        report.output_size = source.len();
+                        #[cfg(feature = "integrity")]
+                        {
+                            report.integrity = self
+                                .output_integrity
+                                .map(|t| OutputIntegrity::compute(t, &source));
+                        }
+                        return Ok((source, report));
+                    }
+                }
+            }
+        }
+
+        // JPEG Baseline YBR to JPEG 2000 goes through Explicit VR Little Endian and RGB,
+        // GDCM misinterprets the colors otherwise
+        if let Some(TransferSyntax::JPEGBaselineProcess1(_)) = report.source_transfer_syntax {
+            if let Some(target) = self.ybr_jpeg_to_j2k_target(&source) {
+                params.transfer_syntax_pre = TransferSyntax::ExplicitVRLittleEndian.to_id();
+                params.photometric_interpretation = PhotometricInterpretation::RGB.to_id();
+                params.transfer_syntax_post = target.to_id();
+            }
+        }
+
+        let mut warnings: Vec<String> = Vec::new();
+        params.options.warning = Some(warning_trampoline);
+        params.options.warning_data = &mut warnings as *mut Vec<String> as *mut c_void;
+
+        let (output, retries) = ffi_guard(|| convert(source, self, &params))?;
+
+        // Retried calls log the same warnings again
+        let mut seen = HashSet::new();
+        warnings.retain(|t| seen.insert(t.clone()));
+
+        #[cfg(feature = "sha2")]
+        let output = if self.embed_pixel_hash {
+            embed_pixel_hash(output)?
+        } else {
+            output
+        };
+
+        report.output_size = output.len();
+        report.retries = retries;
+        report.warnings = warnings;
+        #[cfg(feature = "integrity")]
+        {
+            report.integrity = self
+                .output_integrity
+                .map(|t| OutputIntegrity::compute(t, &output));
+        }
+
+        Ok((output, report))
+    }
+
+    /// JPEG 2000 target of a pipeline needing the YBR workaround, the source is JPEG Baseline.
+    fn ybr_jpeg_to_j2k_target(&self, source: &[u8]) -> Option<TransferSyntax> {
+        if self.photometric_interpretation != PhotometricInterpretation::None {
+            return None;
+        }
+
+        let target = match (self.transfer_syntax_pre, self.transfer_syntax_post) {
+            (t, TransferSyntax::None) | (TransferSyntax::None, t) => t,
+            _ => return None,
+        };
+        let is_j2k = matches!(
+            target,
+            TransferSyntax::JPEG2000Lossless
+                | TransferSyntax::JPEG2000(_, _, _, _)
+                | TransferSyntax::JPEG2000Part2Lossless
+                | TransferSyntax::JPEG2000Part2(_, _, _, _)
+        );
+        if !is_j2k {
+            return None;
+        }
+
+        let info = read_image_info(source).ok()?;
+        match info.photometric_interpretation.as_str() {
+            "YBR_FULL" | "YBR_FULL_422" => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Check that the pipeline can be executed over the DICOM file buffer, without producing output.
+    ///
+    /// Returns the same errors as `run`, so the failing stage is known.
+    pub fn check(&self, source: &[u8]) -> Result<(), GDCMError> {
+        ffi_guard(|| check(source, self))
+    }
+
+    /// Execute the pipeline over a borrowed DICOM file buffer.
+    ///
+    /// The output buffer is allocated once with the estimated length and the source copied into it.
+    /// Without an estimated length, the size is taken from `estimate_output_length`.
+    pub fn run_slice(&self, source: &[u8]) -> Result<Vec<u8>, GDCMError> {
+        let capacity = match self
+            .estimated_length
+            .or_else(|| estimate_output_length(source))
+        {
+            Some(t) => t.max(source.len()),
+            None => source.len() * 4,
+        };
+
+        let mut buffer = Vec::with_capacity(capacity);
+        buffer.extend_from_slice(source);
+
+        self.run(buffer)
+    }
+
+    /// Execute the pipeline over a borrowed DICOM file buffer, writing the output into a buffer owned
+    /// by the caller, which is reused across calls.
+    ///
+    /// `out` is overwritten on each call, growing it if needed, and the written length is returned.
+    /// On error `out` is left empty.
+    pub fn run_into(&self, source: &[u8], out: &mut Vec<u8>) -> Result<usize, GDCMError> {
+        let mut buffer = std::mem::take(out);
+        buffer.clear();
+        buffer.extend_from_slice(source);
+
+        *out = self.run(buffer)?;
+
+        Ok(out.len())
+    }
+
+    /// Execute the pipeline reading the DICOM file from a reader.
+    pub fn run_reader<R: Read>(&self, reader: &mut R) -> Result<Vec<u8>, GDCMError> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+
+        self.run(buffer)
+    }
+
+    /// Execute the pipeline writing the converted DICOM file to a writer.
+    pub fn run_to_writer<W: Write>(
+        &self,
+        source: Vec<u8>,
+        writer: &mut W,
+    ) -> Result<(), GDCMError> {
+        let buffer = self.run(source)?;
+        writer.write_all(&buffer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+pub fn pipeline(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<Vec<u8>, GDCMError> {
+    pipeline_with_report(
+        source,
+        estimated_length,
+        transfer_syntax_pre,
+        photometric_interpretation,
+        transfer_syntax_post,
+    )
+    .map(|(t, _)| t)
+}
+
+/// Same as `pipeline`, returning conversion metadata alongside the output.
+pub fn pipeline_with_report(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<(Vec<u8>, ConversionReport), GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_with_report(source)
+}
+
+/// Check that a conversion can be executed, without producing output.
+pub fn can_convert(
+    source: &[u8],
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<(), GDCMError> {
+    Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post)
+        .check(source)
+}
+
+/// Same as `pipeline`, but borrows the source instead of consuming it.
+///
+/// The input is left intact, which avoids cloning it when both the original and the converted
+/// file are needed. Shares the re-allocation logic of `pipeline`.
+pub fn convert_slice(
+    source: &[u8],
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_slice(source)
+}
+
+/// Same as `convert_slice`, leaving the source intact when both the original and the converted
+/// file are kept, e.g. for dual-format archiving.
+///
+/// The output is a fresh buffer sized with `estimated_length`, `estimate_output_length` otherwise,
+/// and re-allocated like `pipeline` when it does not fit.
+pub fn convert_keep_source(
+    source: &[u8],
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<Vec<u8>, GDCMError> {
+    convert_slice(
+        source,
+        estimated_length,
+        transfer_syntax_pre,
+        photometric_interpretation,
+        transfer_syntax_post,
+    )
+}
+
+/// Same as `pipeline`, but borrows the source and writes into a caller owned buffer, see
+/// `Pipeline::run_into`.
+pub fn convert_into(
+    source: &[u8],
+    out: &mut Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<usize, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_into(source, out)
+}
+
+/// Same as `pipeline`, but reads the source from a reader.
+pub fn convert_reader<R: Read>(
+    reader: &mut R,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_reader(reader)
+}
+
+/// Same as `pipeline`, but writes the output to a writer.
+pub fn convert_to_writer<W: Write>(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    writer: &mut W,
+) -> Result<(), GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_to_writer(source, writer)
+}
+
+/// Same as `pipeline`, reporting progress as `(current, total)` frames.
+pub fn pipeline_with_progress<F: FnMut(u32, u32)>(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    on_progress: F,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_with_progress(source, on_progress)
+}
+
+/// Same as `pipeline`, stopping with `GDCMError::Cancelled` as soon as the flag is set.
+pub fn pipeline_with_cancel(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    cancel: Arc<AtomicBool>,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_with_cancel(source, cancel)
+}
+
+/// Same as `pipeline`, returning `GDCMError::Timeout` if the conversion does not finish within the limit.
+///
+/// The worker thread may outlive the call until GDCM returns, see `Pipeline::run_with_timeout`.
+pub fn pipeline_with_timeout(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    timeout: Duration,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run_with_timeout(source, timeout)
+}
+
+/// Same as `pipeline`, anonymizing the data set before the POST-TRANSFER, see `Pipeline::anon`.
+pub fn pipeline_anon(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+    anon: AnonOptions,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new()
+        .pre(transfer_syntax_pre)
+        .photometric(photometric_interpretation)
+        .post(transfer_syntax_post)
+        .anon(anon);
+
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run(source)
+}
+
+/// Convert a JPEG Baseline YBR_FULL or YBR_FULL_422 file to JPEG 2000 Lossless.
+///
+/// The pipeline goes through Explicit VR Little Endian and RGB on its own, see "How it works".
+pub fn convert_ybr_jpeg_to_j2k(source: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+    Pipeline::new()
+        .pre(TransferSyntax::JPEG2000Lossless)
+        .run(source)
+}
+
+/// Decompress a DICOM file to Explicit VR Little Endian, whatever its transfer syntax.
+///
+/// Same as `pipeline(source, None, TransferSyntax::ExplicitVRLittleEndian, PhotometricInterpretation::None,
+/// TransferSyntax::None)`, with the output length taken from `estimate_output_length`.
+pub fn decompress(source: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new().pre(TransferSyntax::ExplicitVRLittleEndian);
+
+    if let Some(t) = estimate_output_length(&source) {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run(source)
+}
+
+/// Change only the photometric interpretation of a DICOM file, the transfer syntax is kept.
+///
+/// Same as `pipeline(source, None, TransferSyntax::None, photometric_interpretation,
+/// TransferSyntax::None)`, only the PHOTO stage runs. For native pixel data the output length is
+/// the source with the pixel data scaled by the samples per pixel of the target, e.g. the same
+/// length from YBR_FULL to RGB instead of three times the source. Encapsulated sources use
+/// `estimate_output_length`.
+///
+/// ```no_run
+/// use gdcm_conv::PhotometricInterpretation;
+///
+/// let ibuffer = std::fs::read("ybr_full.dcm").unwrap();
+/// let obuffer = gdcm_conv::convert_photometric(ibuffer, PhotometricInterpretation::RGB).unwrap();
+/// ```
+pub fn convert_photometric(
+    source: Vec<u8>,
+    photometric_interpretation: PhotometricInterpretation,
+) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new().photometric(photometric_interpretation);
+
+    let estimated_length = match is_encapsulated(&source) {
+        Ok(false) => read_image_info(&source).ok().map(|t| {
+            let bytes = (t.bits_allocated as usize).div_ceil(8);
+            let pixels = t.rows as usize * t.columns as usize * t.number_of_frames as usize * bytes;
+            let samples = photometric_interpretation.samples_per_pixel();
+            source
+                .len()
+                .saturating_sub(pixels * t.samples_per_pixel as usize)
+                + pixels * samples
+                + MAX_HEADER_SIZE
+        }),
+        _ => estimate_output_length(&source),
+    };
+    if let Some(t) = estimated_length {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run(source)
+}
+
+/// Convert a DICOM file with a preset, the output length is taken from `estimate_output_length`.
+pub fn convert_preset(source: Vec<u8>, preset: Preset) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = preset.pipeline();
+
+    if let Some(t) = estimate_output_length(&source) {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run(source)
+}
+
+/// Recommended transfer syntax by SOP Class UID, see `recommended_syntax_for`.
+#[rustfmt::skip]
+const SOP_CLASS_SYNTAXES: [(&str, TransferSyntax); 27] = [
+    // Mammography and breast tomosynthesis, lossless only
+    ("1.2.840.10008.5.1.4.1.1.1.2", TransferSyntax::JPEG2000Lossless),
+    ("1.2.840.10008.5.1.4.1.1.1.2.1", TransferSyntax::JPEG2000Lossless),
+    ("1.2.840.10008.5.1.4.1.1.13.1.3", TransferSyntax::JPEG2000Lossless),
+    // Projection radiography
+    ("1.2.840.10008.5.1.4.1.1.1", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.1.1", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.1.1.1", TransferSyntax::JPEGLSLossless),
+    // Cross-sectional imaging
+    ("1.2.840.10008.5.1.4.1.1.2", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.2.1", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.4", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.4.1", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.128", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.130", TransferSyntax::JPEGLSLossless),
+    ("1.2.840.10008.5.1.4.1.1.20", TransferSyntax::JPEGLSLossless),
+    // Angiography and fluoroscopy, usually stored as JPEG Lossless
+    ("1.2.840.10008.5.1.4.1.1.12.1", TransferSyntax::JPEGLosslessProcess14_1),
+    ("1.2.840.10008.5.1.4.1.1.12.1.1", TransferSyntax::JPEGLosslessProcess14_1),
+    ("1.2.840.10008.5.1.4.1.1.12.2", TransferSyntax::JPEGLosslessProcess14_1),
+    ("1.2.840.10008.5.1.4.1.1.12.2.1", TransferSyntax::JPEGLosslessProcess14_1),
+    // Ultrasound and visible light, 8 bits images tolerating lossy compression
+    ("1.2.840.10008.5.1.4.1.1.6.1", TransferSyntax::JPEGBaselineProcess1(90)),
+    ("1.2.840.10008.5.1.4.1.1.3.1", TransferSyntax::JPEGBaselineProcess1(90)),
+    ("1.2.840.10008.5.1.4.1.1.77.1.1", TransferSyntax::JPEGBaselineProcess1(90)),
+    ("1.2.840.10008.5.1.4.1.1.77.1.2", TransferSyntax::JPEGBaselineProcess1(90)),
+    ("1.2.840.10008.5.1.4.1.1.77.1.4", TransferSyntax::JPEGBaselineProcess1(90)),
+    ("1.2.840.10008.5.1.4.1.1.77.1.6", TransferSyntax::JPEGBaselineProcess1(90)),
+    // Secondary capture, may hold diagnostic content
+    ("1.2.840.10008.5.1.4.1.1.7", TransferSyntax::JPEGLSLossless),
+    // Segmentation and RT Dose, 1 bit or 32 bits pixel data the codecs do not take
+    ("1.2.840.10008.5.1.4.1.1.66.4", TransferSyntax::ExplicitVRLittleEndian),
+    ("1.2.840.10008.5.1.4.1.1.481.2", TransferSyntax::ExplicitVRLittleEndian),
+    // Parametric maps, float pixel data
+    ("1.2.840.10008.5.1.4.1.1.30", TransferSyntax::ExplicitVRLittleEndian),
+];
+
+/// Recommend a transfer syntax from the (0008,0016) SOP Class UID of a DICOM file.
+///
+/// Mammography and breast tomosynthesis get JPEG 2000 Lossless, projection radiography,
+/// cross-sectional and secondary capture images JPEG-LS Lossless, angiography and fluoroscopy JPEG
+/// Lossless, ultrasound and visible light JPEG Baseline at quality 90. Segmentation, RT Dose and
+/// parametric maps stay Explicit VR Little Endian. Other or missing SOP Classes get JPEG 2000 Lossless.
+pub fn recommended_syntax_for(source: &[u8]) -> Result<TransferSyntax, GDCMError> {
+    // UIDs are 64 characters long at most
+    let mut uid: [c_char; 65] = [0; 65];
+
+    init();
+
+    // Safety: GDCM global state is initialized by `init`, the call only reads it.
+    let status = unsafe {
+        c_sop_class_uid(
+            source.as_ptr(),
+            source.len() as size_t,
+            uid.as_mut_ptr(),
+            uid.len() as size_t,
+        )
+    };
+
+    match status {
+        0x00 => {}
+        0x01 => return Err(GDCMError::Read(Error::ReadStream)),
+        0x02 => return Ok(TransferSyntax::JPEG2000Lossless),
+        0x0E => return Err(GDCMError::Exception),
+        0x0F => return Err(GDCMError::PointerNULL),
+        0x1F => return Err(GDCMError::EmptyBuffer),
+        t => return Err(GDCMError::Unknown(t)),
+    }
+
+    let uid = unsafe { CStr::from_ptr(uid.as_ptr()) }.to_string_lossy();
+
+    Ok(SOP_CLASS_SYNTAXES
+        .iter()
+        .find(|t| t.0 == uid)
+        .map_or(TransferSyntax::JPEG2000Lossless, |t| t.1))
+}
+
+/// Convert a DICOM file to the transfer syntax of `recommended_syntax_for`, the output length is
+/// taken from `estimate_output_length`.
+pub fn convert_recommended(source: Vec<u8>) -> Result<Vec<u8>, GDCMError> {
+    let mut builder = Pipeline::new().post(recommended_syntax_for(&source)?);
+
+    if let Some(t) = estimate_output_length(&source) {
+        builder = builder.estimated_length(t);
+    }
+
+    builder.run(source)
+}
+
+/// Convert a batch of DICOM files in parallel, the output keeps the input order.
+///
+/// Each file runs its own GDCM pipeline, conversions do not share mutable state.
+#[cfg(feature = "rayon")]
+pub fn convert_batch(inputs: Vec<Vec<u8>>, pipeline: &Pipeline) -> Vec<Result<Vec<u8>, GDCMError>> {
+    use rayon::prelude::*;
+
+    inputs.into_par_iter().map(|t| pipeline.run(t)).collect()
+}
+
+/// Same as `pipeline`, run on the blocking thread pool of the Tokio runtime so the conversion does
+/// not stall other tasks. Must be awaited within a Tokio runtime.
+///
+/// A panic of the conversion is resumed in the caller, a task dropped by a runtime shutting down
+/// returns `GDCMError::Cancelled`.
+#[cfg(feature = "async")]
+pub async fn pipeline_async(
+    source: Vec<u8>,
+    estimated_length: Option<usize>,
+    transfer_syntax_pre: TransferSyntax,
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax_post: TransferSyntax,
+) -> Result<Vec<u8>, GDCMError> {
+    let task = tokio::task::spawn_blocking(move || {
+        pipeline(
+            source,
+            estimated_length,
+            transfer_syntax_pre,
+            photometric_interpretation,
+            transfer_syntax_post,
+        )
+    });
+
+    match task.await {
+        Ok(t) => t,
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(GDCMError::Cancelled),
+    }
+}
+
+/// Parameters of `c_convert`, derived from a pipeline.
+struct Params {
+    transfer_syntax_pre: c_int,
+    transfer_syntax_post: c_int,
+    photometric_interpretation: c_int,
+    is_lossy: c_char,
+    quality1: c_int,
+    quality2: c_int,
+    quality3: c_int,
+    irreversible: c_char,
+    allow_error: c_int,
+    options: options_t,
+    // Strings pointed by the options, must live as long as the parameters
+    _patient_name: Option<CString>,
+    _derivation_note: Option<CString>,
+    _keep_private_creators: Vec<CString>,
+    _keep_private_creator_ptrs: Vec<*const c_char>,
+    _implementation_class_uid: Option<CString>,
+    _implementation_version_name: Option<CString>,
+    _pixel_hash: Option<CString>,
+    _tag_overrides: Vec<c_uint>,
+    _tag_override_values: Vec<CString>,
+    _tag_override_value_ptrs: Vec<*const c_char>,
+}
+
+impl Params {
+    fn new(pipeline: &Pipeline) -> Result<Params, GDCMError> {
+        // Set anonymization parameters
+        let anon = pipeline.anon.as_ref();
+        let patient_name = match anon.and_then(|t| t.patient_name.as_ref()) {
+            Some(t) => {
+                Some(CString::new(t.as_str()).map_err(|_| GDCMError::Anon(Error::Anonymize))?)
+            }
+            None => None,
+        };
+
+        // Set private creators to keep
+        let keep_private_creators = pipeline
+            .keep_private_creators
+            .iter()
+            .map(|t| CString::new(t.as_str()).map_err(|_| GDCMError::Anon(Error::Anonymize)))
+            .collect::<Result<Vec<CString>, GDCMError>>()?;
+        let keep_private_creator_ptrs: Vec<*const c_char> =
+            keep_private_creators.iter().map(|t| t.as_ptr()).collect();
+
+        // Set implementation
+        let meta = |t: &Option<String>| match t {
+            Some(t) => CString::new(t.as_str())
+                .map(Some)
+                .map_err(|_| GDCMError::Meta(Error::WriteStream)),
+            None => Ok(None),
+        };
+        let implementation_class_uid = meta(&pipeline.implementation_class_uid)?;
+        let implementation_version_name = meta(&pipeline.implementation_version_name)?;
+        let pixel_hash = meta(&pipeline.pixel_hash)?;
+
+        // Set element overrides
+        let tag_overrides: Vec<c_uint> = pipeline
+            .tag_overrides
+            .iter()
+            .map(|t| ((t.0 as c_uint) << 16) | t.1 as c_uint)
+            .collect();
+        let tag_override_values = pipeline
+            .tag_overrides
+            .iter()
+            .map(|t| {
+                CString::new(t.2.as_str()).map_err(|_| GDCMError::Meta(Error::InvalidOverride))
+            })
+            .collect::<Result<Vec<CString>, GDCMError>>()?;
+        let tag_override_value_ptrs: Vec<*const c_char> =
+            tag_override_values.iter().map(|t| t.as_ptr()).collect();
+
+        // Set derivation description
+        let derivation_note = match pipeline.derivation_note.as_ref() {
+            Some(t) => {
+                Some(CString::new(t.as_str()).map_err(|_| GDCMError::Post(Error::DeriveFile))?)
+            }
+            None => None,
+        };
+
+        // Retired JPEG processes, HTJ2K, video and JPIP are read only, header only conversions
+        // relabel sources already in them
+        if pipeline.transfer_syntax_pre.is_decode_only() && !pipeline.header_only {
+            return Err(GDCMError::Pre(Error::UnsupportedTarget));
+        }
+        if pipeline.transfer_syntax_post.is_decode_only() && !pipeline.header_only {
+            return Err(GDCMError::Post(Error::UnsupportedTarget));
+        }
+
+        // Retired transfer syntaxes are opt-in
+        for t in [pipeline.transfer_syntax_pre, pipeline.transfer_syntax_post] {
+            if t.is_retired() && !cfg!(feature = "deprecated_syntaxes") {
+                return Err(GDCMError::RetiredTransferSyntax(t));
+            }
+        }
+
+        // The photometric conversion rewrites the data set
+        if pipeline.preserve_structure
+            && pipeline.photometric_interpretation != PhotometricInterpretation::None
+        {
+            return Err(GDCMError::Photo(Error::IncompatibleOptions));
+        }
+
+        pipeline
+            .transfer_syntax_pre
+            .validate_params()
+            .map_err(GDCMError::Pre)?;
+        pipeline
+            .transfer_syntax_post
+            .validate_params()
+            .map_err(GDCMError::Post)?;
+
+        // Without a post transfer syntax the output keeps the pre one, the source syntax is checked
+        // by `validate_output_combination`
+        let output_transfer_syntax = match pipeline.transfer_syntax_post {
+            TransferSyntax::None => pipeline.transfer_syntax_pre,
+            t => t,
+        };
+        validate_combination(pipeline.photometric_interpretation, output_transfer_syntax)?;
+
+        // Only 8 and 16 bits outputs are supported
+        if let Some(t) = pipeline.target_bits {
+            if t != 8 && t != 16 {
+                return Err(GDCMError::Photo(Error::UnsupportedBitDepth));
             }
-            Ok(source)
         }
+
+        // Set lossy compression parameters
+        let (quality1, quality2, quality3, irreversible, allow_error) =
+            match pipeline.transfer_syntax_post {
+                // Quality1
+                TransferSyntax::JPEGBaselineProcess1(t)
+                | TransferSyntax::JPEGExtendedProcess2_4(t)
+                | TransferSyntax::HTJ2K(t) => (t, 0, 0, false, 0),
+                // Allow_error
+                TransferSyntax::JPEGLSNearLossless(t) => (0, 0, 0, false, t),
+                // Quality1, Quality2, Quality3 & Irreversible
+                TransferSyntax::JPEG2000(t1, t2, t3, t4)
+                | TransferSyntax::JPEG2000Part2(t1, t2, t3, t4) => (t1, t2, t3, t4, 0),
+                _ => (0, 0, 0, false, 0),
+            };
+        let lossy = pipeline.transfer_syntax_post.is_lossy();
+
+        let options = options_t {
+            anonymize: anon.is_some() as c_char,
+            remove_private_tags: anon.is_some_and(|t| t.remove_private_tags) as c_char,
+            patient_name: patient_name.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+            regenerate_uids: anon.is_some_and(|t| t.regenerate_uids) as c_char,
+            regenerate_sop_instance_uid: pipeline.regenerate_uids.unwrap_or(lossy) as c_char,
+            dry_run: 0,
+            progress: None,
+            progress_data: ptr::null_mut(),
+            cancelled: None,
+            cancel_data: ptr::null_mut(),
+            apply_rescale: pipeline.apply_rescale as c_char,
+            bake_voi_lut: pipeline.bake_voi_lut as c_char,
+            voi_lut_index: pipeline.voi_lut_index as c_uint,
+            overlays: pipeline.overlays.to_id(),
+            icc_profile: pipeline.icc_profile.to_id(),
+            planar_configuration: pipeline.planar_configuration as c_uint,
+            target_bits: pipeline.target_bits.unwrap_or(0) as c_uint,
+            to_utf8: pipeline.to_utf8 as c_char,
+            derivation_note: derivation_note.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+            j2k_resolutions: pipeline.j2k.resolutions.unwrap_or(0) as c_uint,
+            j2k_tile_width: pipeline.j2k.tile_size.map_or(0, |t| t.0) as c_uint,
+            j2k_tile_height: pipeline.j2k.tile_size.map_or(0, |t| t.1) as c_uint,
+            j2k_codeblock_width: pipeline.j2k.codeblock_size.map_or(0, |t| t.0) as c_uint,
+            j2k_codeblock_height: pipeline.j2k.codeblock_size.map_or(0, |t| t.1) as c_uint,
+            j2k_progression_order: pipeline.j2k.progression_order.map_or(-1, |t| t.to_id()),
+            jpegls_interleave_mode: pipeline.jpegls.interleave_mode.map_or(-1, |t| t.to_id()),
+            jpegls_t1: pipeline.jpegls.t1.unwrap_or(0) as c_uint,
+            jpegls_t2: pipeline.jpegls.t2.unwrap_or(0) as c_uint,
+            jpegls_t3: pipeline.jpegls.t3.unwrap_or(0) as c_uint,
+            jpegls_reset: pipeline.jpegls.reset.unwrap_or(0) as c_uint,
+            header_only: pipeline.header_only as c_char,
+            strip_private: pipeline.strip_private as c_char,
+            keep_private_creators: keep_private_creator_ptrs.as_ptr(),
+            keep_private_creators_len: keep_private_creator_ptrs.len() as size_t,
+            implementation_class_uid: implementation_class_uid
+                .as_ref()
+                .map_or(ptr::null(), |t| t.as_ptr()),
+            implementation_version_name: implementation_version_name
+                .as_ref()
+                .map_or(ptr::null(), |t| t.as_ptr()),
+            preserve_implementation: pipeline.preserve_implementation as c_char,
+            preserve_structure: pipeline.preserve_structure as c_char,
+            force_extended_offset_table: pipeline.force_extended_offset_table as c_char,
+            preserve_spatial: pipeline.preserve_spatial as c_char,
+            pixel_hash: pixel_hash.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+            strict: pipeline.strict as c_char,
+            assume_raw_dataset: pipeline.assume_raw_dataset as c_char,
+            single_fragment_per_frame: pipeline.single_fragment_per_frame as c_char,
+            warning: None,
+            warning_data: ptr::null_mut(),
+            rebuild_offset_table: pipeline.rebuild_offset_table as c_char,
+            max_frames: pipeline.max_frames.unwrap_or(0) as c_uint,
+            tag_overrides: tag_overrides.as_ptr(),
+            tag_override_values: tag_override_value_ptrs.as_ptr(),
+            tag_overrides_len: tag_overrides.len() as size_t,
+            trust_detected_photometric: pipeline.trust_detected_photometric as c_char,
+        };
+
+        Ok(Params {
+            transfer_syntax_pre: pipeline.transfer_syntax_pre.to_id(),
+            transfer_syntax_post: pipeline.transfer_syntax_post.to_id(),
+            photometric_interpretation: pipeline.photometric_interpretation.to_id(),
+            is_lossy: lossy as c_char,
+            quality1: quality1 as c_int,
+            quality2: quality2 as c_int,
+            quality3: quality3 as c_int,
+            irreversible: irreversible as c_char,
+            allow_error: allow_error as c_int,
+            options,
+            _patient_name: patient_name,
+            _derivation_note: derivation_note,
+            _keep_private_creators: keep_private_creators,
+            _keep_private_creator_ptrs: keep_private_creator_ptrs,
+            _implementation_class_uid: implementation_class_uid,
+            _implementation_version_name: implementation_version_name,
+            _pixel_hash: pixel_hash,
+            _tag_overrides: tag_overrides,
+            _tag_override_values: tag_override_values,
+            _tag_override_value_ptrs: tag_override_value_ptrs,
+        })
+    }
+
+    /// Call C function.
+    ///
+    /// Safety: the source pointer must be valid for `max_size` bytes. GDCM global state is initialized
+    /// by `init`, each call uses its own readers, writers and codecs, so concurrent calls over different
+    /// buffers do not share mutable state.
+    unsafe fn call(
+        &self,
+        source_ptr: *const c_uchar,
+        source_len: usize,
+        max_size: usize,
+    ) -> output_t {
+        init();
+
+        c_convert(
+            source_ptr,
+            source_len as size_t,
+            max_size as size_t,
+            self.transfer_syntax_pre,
+            self.transfer_syntax_post,
+            self.photometric_interpretation,
+            self.is_lossy,
+            self.quality1,
+            self.quality2,
+            self.quality3,
+            self.irreversible,
+            self.allow_error,
+            &self.options,
+        )
+    }
+}
+
+/// Translate `c_convert` error status.
+fn convert_error(status: c_uint) -> GDCMError {
+    match status {
         // PRE Transfer Syntax conversion error
-        0x11 => Err(GDCMError::Pre(Error::ReadStream)),
-        0x12 => Err(GDCMError::Pre(Error::FileExplicitFilter)),
-        0x13 => Err(GDCMError::Pre(Error::InvalidTransferSyntax)),
-        0x14 => Err(GDCMError::Pre(Error::ExecuteChange)),
-        0x15 => Err(GDCMError::Pre(Error::DeriveFile)),
-        0x16 => Err(GDCMError::Pre(Error::WriteStream)),
+        0x11 => GDCMError::Pre(Error::ReadStream),
+        0x12 => GDCMError::Pre(Error::FileExplicitFilter),
+        0x13 => GDCMError::Pre(Error::InvalidTransferSyntax),
+        0x14 => GDCMError::Pre(Error::ExecuteChange),
+        0x15 => GDCMError::Pre(Error::DeriveFile),
+        0x16 => GDCMError::Pre(Error::WriteStream),
+        0x17 => GDCMError::Pre(Error::UnsupportedBitDepth),
+        0x18 => GDCMError::Pre(Error::IncompatibleEncoding),
+        0x19 => GDCMError::Pre(Error::InconsistentOutput),
+        0x1A => GDCMError::Pre(Error::UnsupportedTarget),
         // Photometric conversion error
-        0x21 => Err(GDCMError::Photo(Error::ReadStream)),
-        0x22 => Err(GDCMError::Photo(Error::InvalidPhotometricInterpretation)),
-        0x23 => Err(GDCMError::Photo(Error::ExecuteChange)),
-        0x24 => Err(GDCMError::Photo(Error::ExecuteLUTChange)),
-        0x25 => Err(GDCMError::Photo(Error::WriteStream)),
+        0x21 => GDCMError::Photo(Error::ReadStream),
+        0x22 => GDCMError::Photo(Error::InvalidPhotometricInterpretation),
+        0x23 => GDCMError::Photo(Error::ExecuteChange),
+        0x24 => GDCMError::Photo(Error::ExecuteLUTChange),
+        0x25 => GDCMError::Photo(Error::WriteStream),
+        0x26 => GDCMError::Photo(Error::Rescale),
+        0x27 => GDCMError::Photo(Error::VOILUT),
+        0x28 => GDCMError::Photo(Error::BurnIn),
+        0x29 => GDCMError::Photo(Error::PlanarConfiguration),
+        0x2A => GDCMError::Photo(Error::UnsupportedBitDepth),
+        0x2B => GDCMError::Photo(Error::IncompatibleOptions),
+        0x2C => GDCMError::Photo(Error::InvalidCombination),
+        0x2D => GDCMError::Photo(Error::IccProfile),
         // POST Transfer Syntax conversion error
-        0x31 => Err(GDCMError::Post(Error::ReadStream)),
-        0x32 => Err(GDCMError::Post(Error::FileExplicitFilter)),
-        0x33 => Err(GDCMError::Post(Error::InvalidTransferSyntax)),
-        0x34 => Err(GDCMError::Post(Error::ExecuteChange)),
-        0x35 => Err(GDCMError::Post(Error::DeriveFile)),
-        0x36 => Err(GDCMError::Post(Error::WriteStream)),
+        0x31 => GDCMError::Post(Error::ReadStream),
+        0x32 => GDCMError::Post(Error::FileExplicitFilter),
+        0x33 => GDCMError::Post(Error::InvalidTransferSyntax),
+        0x34 => GDCMError::Post(Error::ExecuteChange),
+        0x35 => GDCMError::Post(Error::DeriveFile),
+        0x36 => GDCMError::Post(Error::WriteStream),
+        0x37 => GDCMError::Post(Error::UnsupportedBitDepth),
+        0x38 => GDCMError::Post(Error::IncompatibleEncoding),
+        0x39 => GDCMError::Post(Error::InconsistentOutput),
+        0x3A => GDCMError::Post(Error::UnsupportedTarget),
+        // Anonymization error
+        0x41 => GDCMError::Anon(Error::ReadStream),
+        0x42 => GDCMError::Anon(Error::Anonymize),
+        0x43 => GDCMError::Anon(Error::WriteStream),
+        // Character set conversion error
+        0x51 => GDCMError::Charset(Error::ReadStream),
+        0x52 => GDCMError::Charset(Error::CharacterSet),
+        0x53 => GDCMError::Charset(Error::WriteStream),
+        // File meta information error
+        0x61 => GDCMError::Meta(Error::ReadStream),
+        0x63 => GDCMError::Meta(Error::WriteStream),
+        0x64 => GDCMError::Meta(Error::InvalidOverride),
         // Other errors
-        0x0F => Err(GDCMError::PointerNULL),
-        0x1F => Err(GDCMError::EmptyBuffer),
-        _ => Err(GDCMError::Unknown),
+        0x0C => GDCMError::Cancelled,
+        0x0D => GDCMError::NonConformant,
+        0x0E => GDCMError::Exception,
+        0x0F => GDCMError::PointerNULL,
+        0x1F => GDCMError::EmptyBuffer,
+        t => GDCMError::Unknown(t),
+    }
+}
+
+/// Report a panic around an FFI call, e.g. over sizes returned for a malformed source, as
+/// `GDCMError::Exception` like the C++ exceptions caught by convert.cc, never unwinding into the
+/// caller.
+fn ffi_guard<T>(call: impl FnOnce() -> Result<T, GDCMError>) -> Result<T, GDCMError> {
+    panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or(Err(GDCMError::Exception))
+}
+
+/// Call the conversion with the capacity of the source as the maximum output size, returning its
+/// status and the number of re-allocations.
+///
+/// If need more size, reserve more and re-process. The output size may change between calls (e.g.
+/// regenerated UIDs), so retry until it fits.
+#[cfg_attr(not(feature = "log"), allow(unused_variables))]
+fn fit_output(
+    source: &mut Vec<u8>,
+    pipeline: &Pipeline,
+    mut call: impl FnMut(&Vec<u8>) -> output_t,
+) -> Result<(output_t, u32), GDCMError> {
+    let mut ret = call(source);
+
+    let mut retries = 0;
+    while ret.status == 0xFF {
+        if retries == MAX_RETRIES {
+            return Err(GDCMError::BufferTooSmall(ret.size));
+        }
+        retries += 1;
+
+        #[cfg(feature = "log")]
+        log::warn!(
+            "OVERSIZED [{:?}] input: {} estimated: {:?} needed: {}",
+            pipeline.transfer_syntax_pre,
+            source.len(),
+            pipeline.estimated_length,
+            ret.size,
+        );
+        source.reserve(ret.size.saturating_sub(source.len()));
+        ret = call(source);
+    }
+
+    Ok((ret, retries))
+}
+
+/// Run the conversion, returning the output and the number of re-allocations.
+fn convert(
+    mut source: Vec<u8>,
+    pipeline: &Pipeline,
+    params: &Params,
+) -> Result<(Vec<u8>, u32), GDCMError> {
+    // Add more capacity
+    if let Some(t) = pipeline.estimated_length {
+        source.reserve(t.saturating_sub(source.len()));
+    } else {
+        source.reserve(source.len() * 3);
+    }
+
+    let (ret, retries) = fit_output(&mut source, pipeline, |t| unsafe {
+        params.call(t.as_ptr(), t.len(), t.capacity())
+    })?;
+
+    match ret.status {
+        // Success
+        0x00 => {
+            // Never expose memory beyond the allocated buffer
+            if ret.size > source.capacity() {
+                return Err(GDCMError::OversizedResult);
+            }
+            unsafe {
+                source.set_len(ret.size);
+            }
+            Ok((source, retries))
+        }
+        t => Err(convert_error(t)),
+    }
+}
+
+/// 128 bytes preamble and the "DICM" prefix.
+const DICOM_PREAMBLE_SIZE: usize = 132;
+
+/// Whether the source starts with the preamble and the "DICM" prefix of a Part 10 file.
+fn has_preamble(source: &[u8]) -> bool {
+    source.len() >= DICOM_PREAMBLE_SIZE && &source[128..DICOM_PREAMBLE_SIZE] == b"DICM"
+}
+
+/// Reject obviously invalid input before the FFI call.
+fn validate_source(source: &[u8]) -> Result<(), GDCMError> {
+    if source.is_empty() {
+        return Err(GDCMError::EmptyBuffer);
+    }
+    if source.len() < DICOM_PREAMBLE_SIZE {
+        return Err(GDCMError::Read(Error::ReadStream));
+    }
+
+    Ok(())
+}
+
+/// Photometric interpretations allowed by each transfer syntax (PS3.5 8.2), keyed by `to_id()`.
+/// Transfer syntaxes missing from the table take any photometric interpretation.
+const PHOTOMETRIC_COMBINATIONS: [(i32, &[i32]); 22] = [
+    // Native: monochrome, palette, RGB, retired HSV, ARGB, CMYK, YBR_FULL and YBR_FULL_422, which
+    // PS3.3 C.7.6.3.1.2 allows uncompressed with the chroma of each pair of pixels stored once
+    (1, &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+    (2, &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+    (3, &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+    (21, &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+    (23, &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+    // JPEG lossy
+    (4, &[1, 2, 4, 8, 9]),
+    (5, &[1, 2, 4, 8, 9]),
+    // JPEG lossless
+    (6, &[1, 2, 3, 4, 8]),
+    (7, &[1, 2, 3, 4, 8]),
+    // JPEG-LS, palette only when lossless
+    (8, &[1, 2, 3, 4, 8]),
+    (9, &[1, 2, 4, 8]),
+    // JPEG 2000 and HTJ2K lossless, reversible color transform
+    (10, &[1, 2, 4, 8, 13]),
+    (12, &[1, 2, 4, 8, 13]),
+    (16, &[1, 2, 4, 8, 13]),
+    (17, &[1, 2, 4, 8, 13]),
+    // JPEG 2000 and HTJ2K lossy, irreversible or reversible color transform
+    (11, &[1, 2, 4, 8, 12, 13]),
+    (13, &[1, 2, 4, 8, 12, 13]),
+    (18, &[1, 2, 4, 8, 12, 13]),
+    // RLE
+    (14, &[1, 2, 3, 4, 8]),
+    // MPEG-2 and MPEG-4 AVC/H.264
+    (15, &[11]),
+    (19, &[11]),
+    (20, &[11]),
+];
+
+/// Reject a photometric interpretation the target transfer syntax can not encode.
+fn validate_combination(
+    photometric_interpretation: PhotometricInterpretation,
+    transfer_syntax: TransferSyntax,
+) -> Result<(), GDCMError> {
+    if photometric_interpretation == PhotometricInterpretation::None {
+        return Ok(());
+    }
+
+    let allowed = match PHOTOMETRIC_COMBINATIONS
+        .iter()
+        .find(|t| t.0 == transfer_syntax.to_id())
+    {
+        Some(t) => t.1.contains(&photometric_interpretation.to_id()),
+        None => true,
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(GDCMError::Photo(Error::InvalidCombination))
+    }
+}
+
+/// Check the photometric interpretation against the transfer syntax of the source when neither
+/// transfer syntax stage changes it.
+fn validate_output_combination(source: &[u8], pipeline: &Pipeline) -> Result<(), GDCMError> {
+    if pipeline.transfer_syntax_pre != TransferSyntax::None
+        || pipeline.transfer_syntax_post != TransferSyntax::None
+    {
+        return Ok(());
+    }
+
+    match read_transfer_syntax(source) {
+        Ok(t) => validate_combination(pipeline.photometric_interpretation, t),
+        // Raw data sets and unreadable sources are reported by GDCM
+        Err(_) => Ok(()),
+    }
+}
+
+fn check(source: &[u8], pipeline: &Pipeline) -> Result<(), GDCMError> {
+    validate_source(source)?;
+    validate_output_combination(source, pipeline)?;
+
+    let mut params = Params::new(pipeline)?;
+    params.options.dry_run = 1;
+
+    let ret = unsafe { params.call(source.as_ptr(), source.len(), 0) };
+
+    match ret.status {
+        0x00 => Ok(()),
+        t => Err(convert_error(t)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_output_is_retried_with_the_reported_size() {
+        let mut source = vec![0; 16];
+        let (ret, retries) = fit_output(&mut source, &Pipeline::new(), |t| output_t {
+            status: if t.capacity() < 4096 { 0xFF } else { 0x00 },
+            size: 4096,
+        })
+        .unwrap();
+
+        assert_eq!((ret.status, retries), (0x00, 1));
+        assert!(source.capacity() >= 4096);
+    }
+
+    #[test]
+    fn growing_output_is_buffer_too_small() {
+        let mut source = vec![0; 16];
+        let result = fit_output(&mut source, &Pipeline::new(), |t| output_t {
+            status: 0xFF,
+            size: t.capacity() + 1,
+        });
+
+        assert!(matches!(result, Err(GDCMError::BufferTooSmall(_))));
     }
 }
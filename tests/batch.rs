@@ -0,0 +1,33 @@
+#![cfg(feature = "rayon")]
+
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{Pipeline, TransferSyntax};
+
+/// 32x32 8 bits image whose pixels start at `seed`, so every input of a batch is unique.
+fn numbered(seed: usize) -> (Vec<u8>, Vec<u8>) {
+    let pixels: Vec<u8> = (0..32 * 32).map(|t| ((seed + t) % 256) as u8).collect();
+    let source = Fixture::image(32, 32, 1, 8, "MONOCHROME2")
+        .pixels(pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+#[test]
+fn batch_runs_100_conversions_in_order() {
+    let (inputs, pixels): (Vec<_>, Vec<_>) = (0..100).map(numbered).unzip();
+
+    let outputs =
+        gdcm_conv::convert_batch(inputs, &Pipeline::new().post(TransferSyntax::RLELossless));
+
+    assert_eq!(outputs.len(), 100);
+    for (output, pixels) in outputs.into_iter().zip(pixels) {
+        let output = output.unwrap();
+        assert_eq!(
+            gdcm_conv::read_transfer_syntax(&output).unwrap(),
+            TransferSyntax::RLELossless
+        );
+        assert_eq!(common::decoded_pixels(output), pixels);
+    }
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{Error, GDCMError, Pipeline, TransferSyntax, MAX_HEADER_SIZE};
+
+const SEGMENTATION: &str = "1.2.840.10008.5.1.4.1.1.66.4";
+const PARAMETRIC_MAP: &str = "1.2.840.10008.5.1.4.1.1.30";
+
+fn convert(source: Vec<u8>, target: TransferSyntax) -> Result<Vec<u8>, GDCMError> {
+    Pipeline::new().post(target).run(source)
+}
+
+/// 16x16 1 bit binary segmentation, a checkerboard of bytes.
+fn segmentation() -> (Vec<u8>, Vec<u8>) {
+    let pixels: Vec<u8> = (0..32)
+        .map(|t| if t % 2 == 0 { 0xF0 } else { 0x0F })
+        .collect();
+    let source = Fixture::image(16, 16, 1, 1, "MONOCHROME2")
+        .string(0x0008, 0x0016, "UI", SEGMENTATION)
+        .string(0x0062, 0x0001, "CS", "BINARY")
+        .pixels(pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+/// 8x8 32 bits integer parametric map.
+fn parametric_map() -> (Vec<u8>, Vec<u8>) {
+    let pixels: Vec<u8> = (0..64u32)
+        .flat_map(|t| (t * 0x0101_0101 + 0x0F00_0000).to_le_bytes().to_vec())
+        .collect();
+    let source = Fixture::image(8, 8, 1, 32, "MONOCHROME2")
+        .string(0x0008, 0x0016, "UI", PARAMETRIC_MAP)
+        .pixels(pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+#[test]
+fn one_bit_segmentation_is_converted() {
+    let (source, pixels) = segmentation();
+    assert_eq!(
+        gdcm_conv::estimate_output_length(&source),
+        Some(16 * 16 / 8 + MAX_HEADER_SIZE)
+    );
+
+    let output = convert(source.clone(), TransferSyntax::ImplicitVRLittleEndian).unwrap();
+    assert_eq!(common::decoded_pixels(output), pixels);
+
+    assert!(matches!(
+        convert(source, TransferSyntax::JPEG2000Lossless),
+        Err(GDCMError::Post(Error::UnsupportedBitDepth))
+    ));
+}
+
+#[test]
+fn thirty_two_bits_parametric_map_is_converted() {
+    let (source, pixels) = parametric_map();
+    assert_eq!(
+        gdcm_conv::estimate_output_length(&source),
+        Some(8 * 8 * 4 + MAX_HEADER_SIZE)
+    );
+
+    let output = convert(source.clone(), TransferSyntax::RLELossless).unwrap();
+    assert_eq!(common::decoded_pixels(output), pixels);
+
+    assert!(matches!(
+        convert(source, TransferSyntax::JPEGLSLossless),
+        Err(GDCMError::Post(Error::UnsupportedBitDepth))
+    ));
+}
+
+/// 8x8 parametric map with (7FE0,0008) Float Pixel Data.
+fn float_parametric_map() -> (Vec<u8>, Vec<u8>) {
+    let pixels: Vec<u8> = (0..64)
+        .flat_map(|t| (t as f32 * -0.37 + 1e-3).to_le_bytes().to_vec())
+        .collect();
+    let source = Fixture::image(8, 8, 1, 32, "MONOCHROME2")
+        .string(0x0008, 0x0016, "UI", PARAMETRIC_MAP)
+        .remove(0x0028, 0x0101)
+        .remove(0x0028, 0x0102)
+        .remove(0x0028, 0x0103)
+        .bytes(0x7FE0, 0x0008, "OF", pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+#[test]
+fn float_pixel_data_survives_raw_syntaxes() {
+    let (source, pixels) = float_parametric_map();
+
+    let implicit = convert(source.clone(), TransferSyntax::ImplicitVRLittleEndian).unwrap();
+    let explicit = convert(implicit, TransferSyntax::ExplicitVRLittleEndian).unwrap();
+    assert_eq!(common::element(&explicit, 0x7FE0, 0x0008), Some(pixels));
+    assert_eq!(common::element(&explicit, 0x7FE0, 0x0010), None);
+
+    assert!(matches!(
+        convert(source, TransferSyntax::JPEG2000Lossless),
+        Err(GDCMError::Post(Error::UnsupportedBitDepth))
+    ));
+}
+
+/// Multi-frame 10x10 binary segmentation with a segment and per-frame references, its frames do
+/// not end on byte boundaries.
+fn multi_frame_segmentation() -> (Vec<u8>, Vec<u8>) {
+    // 3 frames of 100 bits, padded to an even length
+    let mut pixels: Vec<u8> = (0..38u32).map(|t| (t * 37 % 256) as u8).collect();
+    pixels[37] &= 0x0F;
+    let segment = Fixture::default()
+        .us(0x0062, 0x0004, 1)
+        .string(0x0062, 0x0005, "LO", "Liver")
+        .string(0x0062, 0x0008, "CS", "AUTOMATIC");
+    let frame = Fixture::default().sequence(
+        0x0062,
+        0x000A,
+        vec![Fixture::default().us(0x0062, 0x000B, 1)],
+    );
+    let source = Fixture::image(10, 10, 1, 1, "MONOCHROME2")
+        .string(0x0008, 0x0016, "UI", SEGMENTATION)
+        .frames(3)
+        .string(0x0062, 0x0001, "CS", "BINARY")
+        .sequence(0x0062, 0x0002, vec![segment])
+        .sequence(0x5200, 0x9230, vec![frame.clone(), frame.clone(), frame])
+        .pixels(pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+#[test]
+fn one_bit_segmentation_round_trip_is_byte_identical() {
+    let (source, pixels) = multi_frame_segmentation();
+
+    let implicit = convert(source, TransferSyntax::ImplicitVRLittleEndian).unwrap();
+    let explicit = convert(implicit, TransferSyntax::ExplicitVRLittleEndian).unwrap();
+
+    assert_eq!(common::element(&explicit, 0x7FE0, 0x0010).unwrap(), pixels);
+    assert_eq!(
+        common::string(&explicit, 0x0062, 0x0001).as_deref(),
+        Some("BINARY")
+    );
+    assert_eq!(gdcm_conv::frame_count(&explicit).unwrap(), 3);
+}
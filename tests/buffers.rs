@@ -0,0 +1,74 @@
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{PhotometricInterpretation, Pipeline, TransferSyntax};
+
+#[test]
+fn estimated_length_is_the_total_capacity() {
+    let mut source = Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .pixels(common::gradient(64))
+        .build();
+    source.shrink_to_fit();
+    let total = 64 * 1024;
+    assert!(source.len() * 2 < total);
+
+    let (output, report) = Pipeline::new()
+        .post(TransferSyntax::ImplicitVRLittleEndian)
+        .estimated_length(total)
+        .run_with_report(source)
+        .unwrap();
+
+    assert_eq!(report.retries, 0);
+    assert_eq!(output.capacity(), total);
+}
+
+#[test]
+fn undersized_estimate_is_retried() {
+    let mut source = Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .pixels(common::gradient(64))
+        .build();
+    source.shrink_to_fit();
+    // The note makes the output larger than the source
+    let pipeline = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .derivation_note("Checked ".repeat(100));
+    let expected = pipeline.run(source.clone()).unwrap();
+    assert!(expected.len() > source.len());
+
+    let (output, report) = pipeline
+        .estimated_length(1)
+        .run_with_report(source)
+        .unwrap();
+
+    assert!(report.retries >= 1);
+    assert_eq!(output.len(), expected.len());
+    assert_eq!(
+        common::element(&output, 0x7FE0, 0x0010).unwrap(),
+        common::gradient(64)
+    );
+}
+
+#[test]
+fn keep_source_leaves_the_input_intact() {
+    let source = common::sample();
+    let copy = source.clone();
+
+    let output = gdcm_conv::convert_keep_source(
+        &source,
+        None,
+        TransferSyntax::None,
+        PhotometricInterpretation::None,
+        TransferSyntax::JPEGLSLossless,
+    )
+    .unwrap();
+
+    assert_eq!(source, copy);
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::JPEGLSLossless
+    );
+    assert_eq!(
+        common::decoded_pixels(output),
+        common::element(&source, 0x7FE0, 0x0010).unwrap()
+    );
+}
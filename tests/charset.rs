@@ -0,0 +1,29 @@
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{Pipeline, TransferSyntax};
+
+// PS3.5 H.3.1, Japanese person name in ISO 2022 IR 87 with code extensions
+const YAMADA: &str = "Yamada^Tarou=\u{1b}$B;3ED\u{1b}(B^\u{1b}$BB@O:\u{1b}(B=\u{1b}$B$d$^$@\u{1b}(B^\u{1b}$B$?$m$&\u{1b}(B";
+
+#[test]
+fn japanese_names_are_converted_to_utf8() {
+    let source = Fixture::new()
+        .string(0x0008, 0x0005, "CS", "\\ISO 2022 IR 87")
+        .string(0x0010, 0x0010, "PN", YAMADA)
+        .build();
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .to_utf8(true)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0008, 0x0005).as_deref(),
+        Some("ISO_IR 192")
+    );
+    let name = common::element(&output, 0x0010, 0x0010).unwrap();
+    let name = String::from_utf8(name).expect("PN is not valid UTF-8");
+    assert_eq!(name.trim_end(), "Yamada^Tarou=山田^太郎=やまだ^たろう");
+}
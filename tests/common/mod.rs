@@ -0,0 +1,404 @@
+//! DICOM fixtures built in memory and a minimal reader of Explicit VR Little Endian outputs.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+pub const SECONDARY_CAPTURE: &str = "1.2.840.10008.5.1.4.1.1.7";
+pub const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+
+/// The 512x512 16 bits MONOCHROME2 MR of the repository, Explicit VR Little Endian.
+pub fn sample() -> Vec<u8> {
+    std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/test.dcm")).unwrap()
+}
+
+/// Data set written with Explicit VR Little Endian, elements in tag order.
+#[derive(Clone, Debug, Default)]
+pub struct Fixture {
+    elements: BTreeMap<(u16, u16), Value>,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Bytes([u8; 2], Vec<u8>),
+    Sequence(Vec<Fixture>),
+}
+
+impl Fixture {
+    /// Secondary Capture instance without pixel data.
+    pub fn new() -> Self {
+        Fixture::default()
+            .string(0x0008, 0x0016, "UI", SECONDARY_CAPTURE)
+            .string(0x0008, 0x0018, "UI", "1.2.826.0.1.3680043.2.1143.1")
+            .string(0x0008, 0x0060, "CS", "OT")
+            .string(0x0010, 0x0010, "PN", "Fixture^Patient")
+            .string(0x0010, 0x0020, "LO", "FIXTURE")
+            .string(0x0020, 0x000D, "UI", "1.2.826.0.1.3680043.2.1143.2")
+            .string(0x0020, 0x000E, "UI", "1.2.826.0.1.3680043.2.1143.3")
+    }
+
+    /// Unsigned image with the Image Pixel module set, the pixel data is added with `pixels`.
+    pub fn image(rows: u16, columns: u16, samples: u16, bits: u16, photometric: &str) -> Self {
+        let fixture = Fixture::new()
+            .us(0x0028, 0x0002, samples)
+            .string(0x0028, 0x0004, "CS", photometric)
+            .us(0x0028, 0x0010, rows)
+            .us(0x0028, 0x0011, columns)
+            .us(0x0028, 0x0100, bits)
+            .us(0x0028, 0x0101, bits)
+            .us(0x0028, 0x0102, bits.saturating_sub(1))
+            .us(0x0028, 0x0103, 0);
+
+        if samples > 1 {
+            fixture.us(0x0028, 0x0006, 0)
+        } else {
+            fixture
+        }
+    }
+
+    /// (0028,0008) Number of Frames.
+    pub fn frames(self, number_of_frames: u32) -> Self {
+        self.string(0x0028, 0x0008, "IS", &number_of_frames.to_string())
+    }
+
+    /// Native (7FE0,0010) Pixel Data, OW for more than 8 bits allocated.
+    pub fn pixels(self, data: Vec<u8>) -> Self {
+        let vr = match self.element(0x0028, 0x0100) {
+            Some(t) if u16::from_le_bytes([t[0], t[1]]) > 8 => "OW",
+            _ => "OB",
+        };
+        self.bytes(0x7FE0, 0x0010, vr, data)
+    }
+
+    /// String element, padded to an even length (NUL for UI, space otherwise).
+    pub fn string(self, group: u16, element: u16, vr: &str, value: &str) -> Self {
+        let mut bytes = value.as_bytes().to_vec();
+        if bytes.len() % 2 == 1 {
+            bytes.push(if vr == "UI" { 0 } else { b' ' });
+        }
+        self.bytes(group, element, vr, bytes)
+    }
+
+    pub fn us(self, group: u16, element: u16, value: u16) -> Self {
+        self.bytes(group, element, "US", value.to_le_bytes().to_vec())
+    }
+
+    pub fn bytes(mut self, group: u16, element: u16, vr: &str, value: Vec<u8>) -> Self {
+        let vr = [vr.as_bytes()[0], vr.as_bytes()[1]];
+        self.elements
+            .insert((group, element), Value::Bytes(vr, value));
+        self
+    }
+
+    pub fn sequence(mut self, group: u16, element: u16, items: Vec<Fixture>) -> Self {
+        self.elements
+            .insert((group, element), Value::Sequence(items));
+        self
+    }
+
+    pub fn remove(mut self, group: u16, element: u16) -> Self {
+        self.elements.remove(&(group, element));
+        self
+    }
+
+    fn element(&self, group: u16, element: u16) -> Option<&[u8]> {
+        match self.elements.get(&(group, element)) {
+            Some(Value::Bytes(_, t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Preamble, file meta information and data set.
+    pub fn build(&self) -> Vec<u8> {
+        self.build_with_syntax(EXPLICIT_VR_LITTLE_ENDIAN)
+    }
+
+    /// Same as `build`, declaring another transfer syntax with the data set still written Explicit
+    /// VR Little Endian, e.g. for an encapsulated pixel data added with `bytes`.
+    pub fn build_with_syntax(&self, transfer_syntax: &str) -> Vec<u8> {
+        let sop_class = self.element(0x0008, 0x0016).unwrap_or(b"").to_vec();
+        let sop_instance = self.element(0x0008, 0x0018).unwrap_or(b"").to_vec();
+
+        let meta = Fixture::default()
+            .bytes(0x0002, 0x0001, "OB", vec![0, 1])
+            .bytes(0x0002, 0x0002, "UI", sop_class)
+            .bytes(0x0002, 0x0003, "UI", sop_instance)
+            .string(0x0002, 0x0010, "UI", transfer_syntax)
+            .string(
+                0x0002,
+                0x0012,
+                "UI",
+                "1.2.826.0.1.3680043.2.1143.107.104.103.115",
+            )
+            .string(0x0002, 0x0013, "SH", "FIXTURE");
+        let meta = meta.encode();
+
+        let mut buffer = vec![0; 128];
+        buffer.extend_from_slice(b"DICM");
+        write_header(&mut buffer, 0x0002, 0x0000, *b"UL", 4);
+        buffer.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&meta);
+        buffer.extend_from_slice(&self.encode());
+        buffer
+    }
+
+    /// Data set without preamble nor file meta information.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for (tag, value) in &self.elements {
+            match value {
+                Value::Bytes(vr, bytes) => {
+                    write_header(&mut buffer, tag.0, tag.1, *vr, bytes.len() as u32);
+                    buffer.extend_from_slice(bytes);
+                }
+                Value::Sequence(items) => {
+                    let mut value = Vec::new();
+                    for item in items {
+                        let item = item.encode();
+                        value.extend_from_slice(&0xFFFEu16.to_le_bytes());
+                        value.extend_from_slice(&0xE000u16.to_le_bytes());
+                        value.extend_from_slice(&(item.len() as u32).to_le_bytes());
+                        value.extend_from_slice(&item);
+                    }
+                    write_header(&mut buffer, tag.0, tag.1, *b"SQ", value.len() as u32);
+                    buffer.extend_from_slice(&value);
+                }
+            }
+        }
+        buffer
+    }
+}
+
+fn write_header(buffer: &mut Vec<u8>, group: u16, element: u16, vr: [u8; 2], length: u32) {
+    buffer.extend_from_slice(&group.to_le_bytes());
+    buffer.extend_from_slice(&element.to_le_bytes());
+    buffer.extend_from_slice(&vr);
+    if long_length(vr) {
+        buffer.extend_from_slice(&[0, 0]);
+        buffer.extend_from_slice(&length.to_le_bytes());
+    } else {
+        buffer.extend_from_slice(&(length as u16).to_le_bytes());
+    }
+}
+
+fn long_length(vr: [u8; 2]) -> bool {
+    matches!(
+        &vr,
+        b"OB"
+            | b"OD"
+            | b"OF"
+            | b"OL"
+            | b"OV"
+            | b"OW"
+            | b"SQ"
+            | b"SV"
+            | b"UC"
+            | b"UN"
+            | b"UR"
+            | b"UT"
+            | b"UV"
+    )
+}
+
+/// Element of the top level data set read back by `parse`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub tag: (u16, u16),
+    pub vr: [u8; 2],
+    /// Value, the fragments and their item headers for encapsulated pixel data.
+    pub value: Vec<u8>,
+    pub undefined_length: bool,
+}
+
+/// Top level elements of an Explicit VR Little Endian file, file meta information included.
+pub fn parse(buffer: &[u8]) -> Vec<Element> {
+    assert!(
+        buffer.len() >= 132 && &buffer[128..132] == b"DICM",
+        "not a DICOM file"
+    );
+
+    let mut elements = Vec::new();
+    let mut pos = 132;
+    while pos + 8 <= buffer.len() {
+        let (element, next) = read_element(buffer, pos);
+        elements.push(element);
+        pos = next;
+    }
+    elements
+}
+
+fn u16_at(buffer: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buffer[pos], buffer[pos + 1]])
+}
+
+fn u32_at(buffer: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([
+        buffer[pos],
+        buffer[pos + 1],
+        buffer[pos + 2],
+        buffer[pos + 3],
+    ])
+}
+
+fn read_element(buffer: &[u8], pos: usize) -> (Element, usize) {
+    let tag = (u16_at(buffer, pos), u16_at(buffer, pos + 2));
+    let vr = [buffer[pos + 4], buffer[pos + 5]];
+    let (length, start) = if long_length(vr) {
+        (u32_at(buffer, pos + 8), pos + 12)
+    } else {
+        (u16_at(buffer, pos + 6) as u32, pos + 8)
+    };
+
+    if length != 0xFFFF_FFFF {
+        let end = start + length as usize;
+        let element = Element {
+            tag,
+            vr,
+            value: buffer[start..end].to_vec(),
+            undefined_length: false,
+        };
+        return (element, end);
+    }
+
+    // Items up to the Sequence Delimitation Item, nested undefined lengths included
+    let end = skip_items(buffer, start);
+    let element = Element {
+        tag,
+        vr,
+        value: buffer[start..end - 8].to_vec(),
+        undefined_length: true,
+    };
+    (element, end)
+}
+
+/// Position after the Sequence Delimitation Item closing the items starting at `pos`.
+fn skip_items(buffer: &[u8], mut pos: usize) -> usize {
+    loop {
+        let tag = (u16_at(buffer, pos), u16_at(buffer, pos + 2));
+        let length = u32_at(buffer, pos + 4);
+        pos += 8;
+        match tag {
+            (0xFFFE, 0xE0DD) => return pos,
+            (0xFFFE, 0xE000) if length == 0xFFFF_FFFF => {
+                // Elements up to the Item Delimitation Item
+                loop {
+                    if (u16_at(buffer, pos), u16_at(buffer, pos + 2)) == (0xFFFE, 0xE00D) {
+                        pos += 8;
+                        break;
+                    }
+                    pos = read_element(buffer, pos).1;
+                }
+            }
+            _ => pos += length as usize,
+        }
+    }
+}
+
+/// Value of a top level element, `None` when absent.
+pub fn element(buffer: &[u8], group: u16, element: u16) -> Option<Vec<u8>> {
+    parse(buffer)
+        .into_iter()
+        .find(|t| t.tag == (group, element))
+        .map(|t| t.value)
+}
+
+/// String value of a top level element without its padding.
+pub fn string(buffer: &[u8], group: u16, element: u16) -> Option<String> {
+    self::element(buffer, group, element).map(|t| {
+        String::from_utf8_lossy(&t)
+            .trim_end_matches([' ', '\0'])
+            .to_string()
+    })
+}
+
+/// US value of a top level element.
+pub fn us(buffer: &[u8], group: u16, element: u16) -> Option<u16> {
+    self::element(buffer, group, element).map(|t| u16_at(&t, 0))
+}
+
+/// Top level tags in file order, file meta information included.
+pub fn tags(buffer: &[u8]) -> Vec<(u16, u16)> {
+    parse(buffer).into_iter().map(|t| t.tag).collect()
+}
+
+/// Items of encapsulated pixel data: the Basic Offset Table first, then the fragments.
+pub fn fragments(buffer: &[u8]) -> Vec<Vec<u8>> {
+    let value = parse(buffer)
+        .into_iter()
+        .find(|t| t.tag == (0x7FE0, 0x0010))
+        .filter(|t| t.undefined_length)
+        .expect("encapsulated pixel data")
+        .value;
+
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= value.len() {
+        let length = u32_at(&value, pos + 4) as usize;
+        items.push(value[pos + 8..pos + 8 + length].to_vec());
+        pos += 8 + length;
+    }
+    items
+}
+
+/// Offsets of a Basic Offset Table item.
+pub fn offsets(table: &[u8]) -> Vec<u32> {
+    table.chunks(4).map(|t| u32_at(t, 0)).collect()
+}
+
+/// Encapsulated pixel data value: an empty Basic Offset Table and one fragment per frame. Pass the
+/// built fixture to `close_encapsulation`.
+pub fn encapsulate(frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut value = Vec::new();
+    let item = |value: &mut Vec<u8>, data: &[u8]| {
+        value.extend_from_slice(&0xFFFEu16.to_le_bytes());
+        value.extend_from_slice(&0xE000u16.to_le_bytes());
+        value.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        value.extend_from_slice(data);
+    };
+    item(&mut value, &[]);
+    for frame in frames {
+        let mut frame = frame.clone();
+        if frame.len() % 2 == 1 {
+            frame.push(0);
+        }
+        item(&mut value, &frame);
+    }
+    value
+}
+
+/// Turn the trailing pixel data of a built fixture holding `encapsulate` output into an undefined
+/// length sequence, closed by its delimiter.
+pub fn close_encapsulation(buf: &mut Vec<u8>) {
+    let tag = [0xE0, 0x7F, 0x10, 0x00];
+    let pos = buf
+        .windows(4)
+        .rposition(|w| w == tag)
+        .expect("fixture without pixel data");
+    buf[pos + 8..pos + 12].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    buf.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+}
+
+/// Deterministic gradient of `len` bytes, so round trips compare real content.
+pub fn gradient(len: usize) -> Vec<u8> {
+    (0..len).map(|t| ((t * 7 + t / 13) % 251) as u8).collect()
+}
+
+/// 8 bits RGB frames, interleaved, each frame with its own pattern.
+pub fn rgb_frames(rows: u16, columns: u16, frames: u32) -> Vec<u8> {
+    let pixels = rows as usize * columns as usize;
+    let mut data = Vec::with_capacity(pixels * 3 * frames as usize);
+    for f in 0..frames as usize {
+        for p in 0..pixels {
+            let (y, x) = (p / columns as usize, p % columns as usize);
+            data.push((x * 255 / columns as usize) as u8);
+            data.push((y * 255 / rows as usize) as u8);
+            data.push(((f * 60 + x + y) % 256) as u8);
+        }
+    }
+    data
+}
+
+/// Pixel data of a file decoded to Explicit VR Little Endian by the crate.
+pub fn decoded_pixels(buffer: Vec<u8>) -> Vec<u8> {
+    let decoded = gdcm_conv::decompress(buffer).unwrap();
+    element(&decoded, 0x7FE0, 0x0010).expect("pixel data")
+}
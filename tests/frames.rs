@@ -0,0 +1,91 @@
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{Error, GDCMError, TransferSyntax};
+
+/// 8x8 8 bits image of 3 frames, each frame with its own pixels and (0020,0032) Image Position
+/// (Patient) in the per-frame functional groups.
+fn multi_frame() -> (Vec<u8>, Vec<u8>) {
+    let pixels = common::gradient(3 * 64);
+    let groups = (0..3)
+        .map(|i| {
+            let position = Fixture::default().string(0x0020, 0x0032, "DS", &format!("0\\0\\{}", i));
+            Fixture::default().sequence(0x0020, 0x9113, vec![position])
+        })
+        .collect();
+    let source = Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .frames(3)
+        .sequence(0x5200, 0x9230, groups)
+        .pixels(pixels.clone())
+        .build();
+    (source, pixels)
+}
+
+#[test]
+fn split_frames_distributes_the_per_frame_groups() {
+    let (source, pixels) = multi_frame();
+    let files = gdcm_conv::split_frames(&source).unwrap();
+
+    assert_eq!(files.len(), 3);
+    for (i, file) in files.iter().enumerate() {
+        assert_eq!(
+            common::element(file, 0x7FE0, 0x0010).unwrap(),
+            pixels[i * 64..(i + 1) * 64]
+        );
+        assert_eq!(
+            common::string(file, 0x0020, 0x0032),
+            Some(format!("0\\0\\{}", i))
+        );
+        assert_eq!(
+            common::string(file, 0x0020, 0x0013),
+            Some((i + 1).to_string())
+        );
+        assert_eq!(common::element(file, 0x5200, 0x9230), None);
+    }
+}
+
+#[test]
+fn split_frames_rejects_single_frame_sources() {
+    let source = Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .pixels(common::gradient(64))
+        .build();
+
+    assert!(matches!(
+        gdcm_conv::split_frames(&source),
+        Err(GDCMError::Frames(Error::NotMultiFrame))
+    ));
+}
+
+#[test]
+fn combine_frames_stacks_the_slices() {
+    let (source, pixels) = multi_frame();
+    let files = gdcm_conv::split_frames(&source).unwrap();
+
+    let combined =
+        gdcm_conv::combine_frames(files, TransferSyntax::ExplicitVRLittleEndian).unwrap();
+
+    assert_eq!(gdcm_conv::frame_count(&combined).unwrap(), 3);
+    assert_eq!(
+        common::string(&combined, 0x0028, 0x0008).as_deref(),
+        Some("3")
+    );
+    assert_eq!(common::element(&combined, 0x7FE0, 0x0010).unwrap(), pixels);
+}
+
+#[test]
+fn combine_frames_rejects_mismatched_rows() {
+    // Same pixel count, different shape
+    let slices = vec![
+        Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+            .pixels(common::gradient(64))
+            .build(),
+        Fixture::image(4, 16, 1, 8, "MONOCHROME2")
+            .pixels(common::gradient(64))
+            .build(),
+    ];
+
+    assert!(matches!(
+        gdcm_conv::combine_frames(slices, TransferSyntax::ExplicitVRLittleEndian),
+        Err(GDCMError::Frames(Error::MismatchedFrames))
+    ));
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "integrity")]
+
+use gdcm_conv::{IntegrityAlgorithm, OutputIntegrity};
+
+fn digest(algorithm: IntegrityAlgorithm, data: &[u8]) -> u64 {
+    OutputIntegrity::compute(algorithm, data).value
+}
+
+#[test]
+fn crc32_known_answers() {
+    assert_eq!(digest(IntegrityAlgorithm::Crc32, b""), 0);
+    assert_eq!(digest(IntegrityAlgorithm::Crc32, b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn xxh64_known_answers() {
+    assert_eq!(
+        digest(IntegrityAlgorithm::Xxh64, b""),
+        0xEF46_DB37_51D8_E999
+    );
+    assert_eq!(
+        digest(IntegrityAlgorithm::Xxh64, b"abc"),
+        0x44BC_2CF5_AD77_0999
+    );
+    // Longer than a 32 bytes stripe
+    assert_eq!(
+        digest(
+            IntegrityAlgorithm::Xxh64,
+            b"Nobody inspects the spammish repetition"
+        ),
+        0xFBCE_A83C_8A37_8BF1
+    );
+}
+
+#[test]
+fn verify_detects_a_changed_byte() {
+    let mut data = b"123456789".to_vec();
+    let integrity = OutputIntegrity::compute(IntegrityAlgorithm::Xxh64, &data);
+    assert!(integrity.verify(&data));
+
+    data[4] ^= 1;
+    assert!(!integrity.verify(&data));
+}
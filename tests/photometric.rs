@@ -0,0 +1,435 @@
+mod common;
+
+use common::Fixture;
+use gdcm_conv::{
+    Error, GDCMError, IccPolicy, OverlayPolicy, PhotometricInterpretation, Pipeline, TransferSyntax,
+};
+
+/// 8x8 8 bits black image with a graphics overlay covering its first row.
+fn overlaid() -> (Vec<u8>, Vec<u8>) {
+    let overlay = vec![0xFF, 0, 0, 0, 0, 0, 0, 0];
+    let source = Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .pixels(vec![0; 64])
+        .us(0x6000, 0x0010, 8)
+        .us(0x6000, 0x0011, 8)
+        .string(0x6000, 0x0040, "CS", "G")
+        .bytes(0x6000, 0x0050, "SS", vec![1, 0, 1, 0])
+        .us(0x6000, 0x0100, 1)
+        .us(0x6000, 0x0102, 0)
+        .bytes(0x6000, 0x3000, "OW", overlay.clone())
+        .build();
+    (source, overlay)
+}
+
+fn overlay_tags(buffer: &[u8]) -> Vec<(u16, u16)> {
+    common::tags(buffer)
+        .into_iter()
+        .filter(|t| t.0 == 0x6000)
+        .collect()
+}
+
+fn with_overlays(overlays: OverlayPolicy, source: Vec<u8>) -> Vec<u8> {
+    Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .overlays(overlays)
+        .run(source)
+        .unwrap()
+}
+
+#[test]
+fn overlays_are_preserved() {
+    let (source, overlay) = overlaid();
+    let output = with_overlays(OverlayPolicy::Preserve, source.clone());
+
+    assert_eq!(overlay_tags(&output), overlay_tags(&source));
+    assert_eq!(common::element(&output, 0x6000, 0x3000), Some(overlay));
+    assert_eq!(common::decoded_pixels(output), vec![0; 64]);
+}
+
+#[test]
+fn overlays_are_stripped() {
+    let (source, _) = overlaid();
+    let output = with_overlays(OverlayPolicy::Strip, source);
+
+    assert!(overlay_tags(&output).is_empty());
+    assert_eq!(common::decoded_pixels(output), vec![0; 64]);
+}
+
+#[test]
+fn overlays_are_burned_in() {
+    let (source, _) = overlaid();
+    let output = with_overlays(OverlayPolicy::BurnIn, source);
+
+    assert!(overlay_tags(&output).is_empty());
+    let pixels = common::decoded_pixels(output);
+    assert!(pixels[..8].iter().all(|&t| t == 0xFF), "{:?}", &pixels[..8]);
+    assert!(pixels[8..].iter().all(|&t| t == 0));
+}
+
+/// Matrix/TRC ICC profile with the sRGB primaries and linear tone curves.
+fn linear_srgb_profile() -> Vec<u8> {
+    let primaries = [
+        (*b"rXYZ", [0.4361, 0.2225, 0.0139]),
+        (*b"gXYZ", [0.3851, 0.7169, 0.0971]),
+        (*b"bXYZ", [0.1431, 0.0606, 0.7141]),
+    ];
+    let mut tags: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+    for (signature, xyz) in primaries.iter() {
+        let mut data = b"XYZ \0\0\0\0".to_vec();
+        for t in xyz.iter() {
+            data.extend_from_slice(&((t * 65536.0) as i32).to_be_bytes());
+        }
+        tags.push((*signature, data));
+    }
+    for signature in [b"rTRC", b"gTRC", b"bTRC"].iter() {
+        // No entry is the identity
+        tags.push((**signature, b"curv\0\0\0\0\0\0\0\0".to_vec()));
+    }
+
+    let mut header = vec![0; 128];
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let mut offset = 128 + 4 + 12 * tags.len();
+    for (signature, value) in tags {
+        table.extend_from_slice(&signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        offset += value.len();
+        data.extend(value);
+    }
+
+    let mut profile = header;
+    profile.extend(table);
+    profile.extend(data);
+    let size = profile.len() as u32;
+    profile[..4].copy_from_slice(&size.to_be_bytes());
+    profile
+}
+
+/// sRGB encoding of a linear 8 bits value.
+fn srgb(value: u8) -> f64 {
+    let t = value as f64 / 255.0;
+    let t = if t <= 0.0031308 {
+        12.92 * t
+    } else {
+        1.055 * t.powf(1.0 / 2.4) - 0.055
+    };
+    t * 255.0
+}
+
+#[test]
+fn icc_profile_survives_ybr_to_rgb() {
+    let profile = linear_srgb_profile();
+    let source = Fixture::image(8, 8, 3, 8, "YBR_FULL")
+        .pixels(vec![128; 8 * 8 * 3])
+        .bytes(0x0028, 0x2000, "OB", profile.clone())
+        .string(0x0028, 0x2002, "CS", "SRGB")
+        .build();
+
+    let output = Pipeline::new()
+        .photometric(PhotometricInterpretation::RGB)
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .icc_profile(IccPolicy::Preserve)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("RGB")
+    );
+    assert_eq!(common::element(&output, 0x0028, 0x2000), Some(profile));
+    assert_eq!(
+        common::string(&output, 0x0028, 0x2002).as_deref(),
+        Some("SRGB")
+    );
+}
+
+#[test]
+fn icc_profile_is_applied() {
+    // Gray ramp, each pixel is R = G = B
+    let ramp: Vec<u8> = (0..64u32).map(|t| (t * 4 + t / 16) as u8).collect();
+    let pixels: Vec<u8> = ramp.iter().flat_map(|&t| vec![t; 3]).collect();
+    let source = Fixture::image(8, 8, 3, 8, "RGB")
+        .pixels(pixels)
+        .bytes(0x0028, 0x2000, "OB", linear_srgb_profile())
+        .build();
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .icc_profile(IccPolicy::Apply)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(common::element(&output, 0x0028, 0x2000), None);
+    assert_eq!(
+        common::string(&output, 0x0028, 0x2002).as_deref(),
+        Some("SRGB")
+    );
+    let converted = common::decoded_pixels(output);
+    for (i, &t) in ramp.iter().enumerate() {
+        for k in 0..3 {
+            let value = converted[i * 3 + k] as f64;
+            assert!(
+                (value - srgb(t)).abs() <= 2.0,
+                "{}: {} != {}",
+                t,
+                value,
+                srgb(t)
+            );
+        }
+    }
+}
+
+#[test]
+fn convert_photometric_ybr_full_to_rgb() {
+    let ybr: Vec<u8> = (0..64u32)
+        .flat_map(|t| vec![(t * 4) as u8, (64 + t * 2) as u8, (192 - t * 2) as u8])
+        .collect();
+    let source = Fixture::image(8, 8, 3, 8, "YBR_FULL")
+        .pixels(ybr.clone())
+        .build();
+
+    let output = gdcm_conv::convert_photometric(source, PhotometricInterpretation::RGB).unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("RGB")
+    );
+    assert_eq!(common::us(&output, 0x0028, 0x0002), Some(3));
+    let rgb = common::element(&output, 0x7FE0, 0x0010).unwrap();
+    assert_eq!(rgb.len(), ybr.len());
+    for (t, p) in ybr.chunks(3).zip(rgb.chunks(3)) {
+        let (y, cb, cr) = (t[0] as f64, t[1] as f64 - 128.0, t[2] as f64 - 128.0);
+        let expected = [
+            y + 1.402 * cr,
+            y - 0.344_136 * cb - 0.714_136 * cr,
+            y + 1.772 * cb,
+        ];
+        for (&value, expected) in p.iter().zip(expected.iter()) {
+            let expected = expected.round().clamp(0.0, 255.0);
+            assert!((value as f64 - expected).abs() <= 1.0, "{:?}: {:?}", t, p);
+        }
+    }
+}
+
+#[test]
+fn ybr_full_422_round_trip_is_close() {
+    // Smooth colors, the chroma of neighbouring pixels is close
+    let rgb: Vec<u8> = (0..64u32)
+        .flat_map(|t| {
+            let (y, x) = (t / 8, t % 8);
+            vec![
+                (40 + x * 20) as u8,
+                (200 - y * 20) as u8,
+                (60 + x * 8 + y * 8) as u8,
+            ]
+        })
+        .collect();
+    let source = Fixture::image(8, 8, 3, 8, "RGB")
+        .pixels(rgb.clone())
+        .build();
+
+    let ybr = Pipeline::new()
+        .photometric(PhotometricInterpretation::YbrFull422)
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        common::string(&ybr, 0x0028, 0x0004).as_deref(),
+        Some("YBR_FULL_422")
+    );
+    assert_eq!(common::us(&ybr, 0x0028, 0x0002), Some(3));
+    assert_eq!(common::us(&ybr, 0x0028, 0x0006), Some(0));
+    // Y1 Y2 Cb Cr for each pair of pixels
+    assert_eq!(
+        common::element(&ybr, 0x7FE0, 0x0010).unwrap().len(),
+        8 * 8 * 2
+    );
+
+    let output = to_rgb(ybr, 0);
+    assert_eq!(common::us(&output, 0x0028, 0x0002), Some(3));
+    assert_eq!(common::us(&output, 0x0028, 0x0006), Some(0));
+    let converted = common::element(&output, 0x7FE0, 0x0010).unwrap();
+    assert_eq!(converted.len(), rgb.len());
+    for (i, (&a, &b)) in rgb.iter().zip(converted.iter()).enumerate() {
+        assert!((a as i32 - b as i32).abs() <= 16, "{}: {} != {}", i, a, b);
+    }
+}
+
+fn is_invalid_combination<T>(result: Result<T, GDCMError>) -> bool {
+    matches!(result, Err(GDCMError::Photo(Error::InvalidCombination)))
+}
+
+#[test]
+fn palette_color_is_rejected_with_jpeg2000_lossless() {
+    let source = common::sample();
+    let palette = Pipeline::new().photometric(PhotometricInterpretation::PaletteColor);
+
+    let post = palette.clone().post(TransferSyntax::JPEG2000Lossless);
+    assert!(is_invalid_combination(post.check(&source)));
+    assert!(is_invalid_combination(post.run(source.clone())));
+
+    // Without a post transfer syntax the output keeps the pre one
+    let pre = palette.clone().pre(TransferSyntax::JPEG2000Lossless);
+    assert!(is_invalid_combination(pre.run(source.clone())));
+
+    // Without any transfer syntax the output keeps the one of the source
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(source)
+        .unwrap();
+    assert!(is_invalid_combination(palette.check(&encoded)));
+    assert!(is_invalid_combination(palette.run(encoded)));
+}
+
+#[test]
+fn rgb_labelled_ybr_full_is_detected() {
+    // Gray ramp, each pixel is R = G = B
+    let pixels: Vec<u8> = (0..64u32).flat_map(|t| vec![(t * 4) as u8; 3]).collect();
+    let source = Fixture::image(8, 8, 3, 8, "YBR_FULL")
+        .pixels(pixels.clone())
+        .build();
+
+    assert_eq!(
+        gdcm_conv::detect_true_photometric(&source).unwrap(),
+        PhotometricInterpretation::RGB
+    );
+
+    let output = Pipeline::new()
+        .photometric(PhotometricInterpretation::RGB)
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .trust_detected_photometric(true)
+        .run(source.clone())
+        .unwrap();
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("RGB")
+    );
+    assert_eq!(common::element(&output, 0x7FE0, 0x0010).unwrap(), pixels);
+
+    // Trusting the label converts the gray pixels to colors
+    assert_ne!(
+        common::element(&to_rgb(source, 0), 0x7FE0, 0x0010).unwrap(),
+        pixels
+    );
+}
+
+/// 2x2 RGB pixels, interleaved and planar.
+fn rgb_2x2() -> (Vec<u8>, Vec<u8>) {
+    let interleaved = vec![10, 20, 30, 11, 21, 31, 12, 22, 32, 13, 23, 33];
+    let planar = vec![10, 11, 12, 13, 20, 21, 22, 23, 30, 31, 32, 33];
+    (interleaved, planar)
+}
+
+fn to_rgb(source: Vec<u8>, planar_configuration: u32) -> Vec<u8> {
+    Pipeline::new()
+        .photometric(PhotometricInterpretation::RGB)
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .planar_configuration(planar_configuration)
+        .run(source)
+        .unwrap()
+}
+
+#[test]
+fn planar_configuration_is_forced() {
+    let (interleaved, planar) = rgb_2x2();
+    let source = Fixture::image(2, 2, 3, 8, "RGB")
+        .pixels(interleaved.clone())
+        .build();
+
+    let output = to_rgb(source, 1);
+    assert_eq!(common::us(&output, 0x0028, 0x0006), Some(1));
+    assert_eq!(
+        common::element(&output, 0x7FE0, 0x0010),
+        Some(planar.clone())
+    );
+
+    let output = to_rgb(output, 0);
+    assert_eq!(common::us(&output, 0x0028, 0x0006), Some(0));
+    assert_eq!(common::element(&output, 0x7FE0, 0x0010), Some(interleaved));
+}
+
+#[test]
+fn monochrome1_is_inverted_to_monochrome2() {
+    let pixels = common::gradient(64);
+    let source = Fixture::image(8, 8, 1, 8, "MONOCHROME1")
+        .string(0x0028, 0x1050, "DS", "100")
+        .string(0x0028, 0x1051, "DS", "50")
+        .pixels(pixels.clone())
+        .build();
+
+    let output = Pipeline::new()
+        .photometric(PhotometricInterpretation::Monochrome2)
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("MONOCHROME2")
+    );
+    let inverted: Vec<u8> = pixels.iter().map(|t| 255 - t).collect();
+    assert_eq!(common::element(&output, 0x7FE0, 0x0010).unwrap(), inverted);
+
+    // The window moves with the values, the width is kept
+    let center: f64 = common::string(&output, 0x0028, 0x1050)
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(center, 155.0);
+    assert_eq!(
+        common::string(&output, 0x0028, 0x1051).as_deref(),
+        Some("50")
+    );
+}
+
+#[test]
+fn palette_color_is_expanded_to_rgb() {
+    let words = |values: &[u16]| -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|t| t.to_le_bytes().to_vec())
+            .collect()
+    };
+    // 4 entries of 16 bits from index 0
+    let descriptor = words(&[4, 0, 16]);
+    let red = [0x0000, 0xFFFF, 0x0000, 0x8000];
+    let green = [0x0000, 0x0000, 0xFFFF, 0x8000];
+    let blue = [0x0000, 0x0000, 0x0000, 0x8000];
+    let source = Fixture::image(2, 2, 1, 8, "PALETTE COLOR")
+        .bytes(0x0028, 0x1101, "US", descriptor.clone())
+        .bytes(0x0028, 0x1102, "US", descriptor.clone())
+        .bytes(0x0028, 0x1103, "US", descriptor)
+        .bytes(0x0028, 0x1201, "OW", words(&red))
+        .bytes(0x0028, 0x1202, "OW", words(&green))
+        .bytes(0x0028, 0x1203, "OW", words(&blue))
+        .pixels(vec![0, 1, 2, 3])
+        .build();
+
+    let output = to_rgb(source, 0);
+
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("RGB")
+    );
+    assert_eq!(common::us(&output, 0x0028, 0x0002), Some(3));
+    let rgb: Vec<u16> = (0..4)
+        .flat_map(|t| vec![red[t], green[t], blue[t]])
+        .collect();
+    assert_eq!(
+        common::element(&output, 0x7FE0, 0x0010).unwrap(),
+        words(&rgb)
+    );
+
+    let palette = common::tags(&output)
+        .into_iter()
+        .filter(|t| t.0 == 0x0028 && (0x1100..0x1300).contains(&t.1))
+        .collect::<Vec<_>>();
+    assert!(palette.is_empty(), "{:?}", palette);
+}
@@ -0,0 +1,362 @@
+mod common;
+
+use common::{sample, Fixture};
+use gdcm_conv::{Error, GDCMError, Pipeline, TransferSyntax};
+
+fn same_syntax() -> Pipeline {
+    Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .skip_if_same(true)
+}
+
+#[test]
+fn skip_if_same_returns_the_source() {
+    let source = sample();
+    let output = same_syntax().run(source.clone()).unwrap();
+
+    assert_eq!(output, source);
+}
+
+#[test]
+fn skip_if_same_regenerates_uids() {
+    let source = sample();
+    let output = same_syntax()
+        .regenerate_uids(true)
+        .run(source.clone())
+        .unwrap();
+
+    assert_ne!(
+        common::string(&output, 0x0008, 0x0018),
+        common::string(&source, 0x0008, 0x0018)
+    );
+    assert_eq!(
+        common::string(&output, 0x0002, 0x0003),
+        common::string(&output, 0x0008, 0x0018)
+    );
+}
+
+/// 8x8 8 bits gradient.
+fn image() -> Fixture {
+    Fixture::image(8, 8, 1, 8, "MONOCHROME2").pixels(common::gradient(64))
+}
+
+/// SOP Instance UID of the output of an 8 bits image converted to `post` with the defaults.
+fn sop_instance_uid(post: TransferSyntax) -> (Option<String>, Option<String>) {
+    let output = Pipeline::new().post(post).run(image().build()).unwrap();
+
+    (
+        common::string(&output, 0x0008, 0x0018),
+        common::string(&output, 0x0002, 0x0003),
+    )
+}
+
+#[test]
+fn lossless_targets_keep_the_sop_instance_uid() {
+    let (uid, meta) = sop_instance_uid(TransferSyntax::JPEGLSLossless);
+
+    assert_eq!(uid.as_deref(), Some("1.2.826.0.1.3680043.2.1143.1"));
+    assert_eq!(meta, uid);
+}
+
+#[test]
+fn lossy_targets_regenerate_the_sop_instance_uid() {
+    let (uid, meta) = sop_instance_uid(TransferSyntax::JPEGBaselineProcess1(90));
+
+    assert!(uid.is_some());
+    assert_ne!(uid.as_deref(), Some("1.2.826.0.1.3680043.2.1143.1"));
+    assert_eq!(meta, uid);
+}
+
+#[test]
+fn skip_if_same_writes_the_derivation_note() {
+    let output = same_syntax()
+        .derivation_note("Reviewed copy".to_string())
+        .run(sample())
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0008, 0x2111).as_deref(),
+        Some("Reviewed copy")
+    );
+}
+
+/// Two private blocks in group 0009, "KEEP ME" at 0x10 and "DROP ME" at 0x11.
+fn private_blocks() -> Vec<u8> {
+    common::Fixture::new()
+        .string(0x0009, 0x0010, "LO", "KEEP ME")
+        .string(0x0009, 0x0011, "LO", "DROP ME")
+        .string(0x0009, 0x1001, "LO", "kept")
+        .string(0x0009, 0x1101, "LO", "dropped")
+        .string(0x0011, 0x0010, "LO", "OTHER")
+        .string(0x0011, 0x1001, "LO", "dropped")
+        .build()
+}
+
+fn private_tags(buffer: &[u8]) -> Vec<(u16, u16)> {
+    common::tags(buffer)
+        .into_iter()
+        .filter(|t| t.0 % 2 == 1)
+        .collect()
+}
+
+#[test]
+fn private_tags_are_stripped() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .strip_private(true)
+        .run(private_blocks())
+        .unwrap();
+
+    assert!(private_tags(&output).is_empty());
+    assert!(common::string(&output, 0x0010, 0x0010).is_some());
+}
+
+#[test]
+fn whitelisted_private_block_survives() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .strip_private(true)
+        .keep_private_creators(vec!["KEEP ME".to_string()])
+        .run(private_blocks())
+        .unwrap();
+
+    assert_eq!(
+        private_tags(&output),
+        vec![(0x0009, 0x0010), (0x0009, 0x1001)]
+    );
+    assert_eq!(
+        common::string(&output, 0x0009, 0x1001).as_deref(),
+        Some("kept")
+    );
+}
+
+#[test]
+fn implementation_is_set_by_the_caller() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::JPEGLSLossless)
+        .implementation_class_uid("1.2.826.0.1.3680043.2.1143.99".to_string())
+        .implementation_version_name("PROVENANCE_1".to_string())
+        .run(sample())
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0002, 0x0012).as_deref(),
+        Some("1.2.826.0.1.3680043.2.1143.99")
+    );
+    assert_eq!(
+        common::string(&output, 0x0002, 0x0013).as_deref(),
+        Some("PROVENANCE_1")
+    );
+}
+
+#[test]
+fn preserve_structure_keeps_the_element_order() {
+    let source = common::Fixture::new()
+        .bytes(0x0009, 0x0000, "UL", 28u32.to_le_bytes().to_vec())
+        .string(0x0009, 0x0010, "LO", "KEEP ME")
+        .string(0x0009, 0x1001, "LO", "kept")
+        .bytes(0x0011, 0x0000, "UL", 28u32.to_le_bytes().to_vec())
+        .string(0x0011, 0x0010, "LO", "OTHER")
+        .string(0x0011, 0x1001, "LO", "other")
+        .build();
+    let data_set = |buffer: &[u8]| -> Vec<(u16, u16)> {
+        common::tags(buffer)
+            .into_iter()
+            .filter(|t| t.0 != 0x0002)
+            .collect()
+    };
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .preserve_structure(true)
+        .run(source.clone())
+        .unwrap();
+
+    assert_eq!(data_set(&output), data_set(&source));
+}
+
+/// 8x8 image with 0.5 mm square pixels and 2 mm slices.
+fn spatial() -> Vec<u8> {
+    common::Fixture::image(8, 8, 1, 8, "MONOCHROME2")
+        .string(0x0018, 0x0050, "DS", "2")
+        .string(0x0028, 0x0030, "DS", "0.5\\0.5")
+        .pixels(common::gradient(64))
+        .build()
+}
+
+fn pixel_spacing(buffer: &[u8]) -> Vec<f64> {
+    common::string(buffer, 0x0028, 0x0030)
+        .unwrap()
+        .split('\\')
+        .map(|t| t.trim().parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn preserve_spatial_keeps_the_pixel_spacing() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::RLELossless)
+        .preserve_spatial(true)
+        .run(spatial())
+        .unwrap();
+
+    assert_eq!(pixel_spacing(&output), vec![0.5, 0.5]);
+    assert_eq!(
+        common::string(&output, 0x0018, 0x0050).as_deref(),
+        Some("2")
+    );
+}
+
+#[test]
+fn preserve_spatial_scales_the_pixel_spacing() {
+    // Same pixel data read as 4 rows of 16 columns
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .set_tag(0x0028, 0x0010, "4")
+        .set_tag(0x0028, 0x0011, "16")
+        .preserve_spatial(true)
+        .run(spatial())
+        .unwrap();
+
+    assert_eq!(common::us(&output, 0x0028, 0x0010), Some(4));
+    assert_eq!(pixel_spacing(&output), vec![1.0, 0.25]);
+    assert_eq!(
+        common::string(&output, 0x0018, 0x0050).as_deref(),
+        Some("2")
+    );
+}
+
+#[test]
+fn set_tag_overrides_the_modality() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .set_tag(0x0008, 0x0060, "MR")
+        .set_tag(0x0008, 0x0060, "CT")
+        .set_tag(0x0008, 0x0080, "Fixture Hospital")
+        .run(sample())
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0008, 0x0060).as_deref(),
+        Some("CT")
+    );
+    assert_eq!(
+        gdcm_conv::read_tag(&output, 0x0008, 0x0060)
+            .unwrap()
+            .as_deref(),
+        Some("CT")
+    );
+    assert_eq!(
+        common::string(&output, 0x0008, 0x0080).as_deref(),
+        Some("Fixture Hospital")
+    );
+}
+
+#[test]
+fn set_tag_rejects_sequences() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .set_tag(0x5200, 0x9230, "1")
+        .run(sample());
+
+    assert!(matches!(
+        output,
+        Err(GDCMError::Meta(Error::InvalidOverride))
+    ));
+}
+
+#[test]
+fn strict_rejects_nonconformant_sources() {
+    let sources = vec![
+        // Odd value length
+        image().bytes(0x0008, 0x0080, "LO", b"ODD".to_vec()).build(),
+        // High Bit inconsistent with Bits Stored
+        image().us(0x0028, 0x0102, 4).build(),
+    ];
+
+    let pipeline = Pipeline::new().post(TransferSyntax::ExplicitVRLittleEndian);
+    for source in sources {
+        let strict = pipeline.clone().strict(true);
+        assert!(matches!(
+            strict.check(&source),
+            Err(GDCMError::NonConformant)
+        ));
+        assert!(matches!(
+            strict.run(source.clone()),
+            Err(GDCMError::NonConformant)
+        ));
+
+        // Tolerated by default
+        let output = pipeline.run(source).unwrap();
+        assert_eq!(
+            common::element(&output, 0x7FE0, 0x0010).unwrap(),
+            common::gradient(64)
+        );
+    }
+
+    assert!(pipeline.strict(true).run(image().build()).is_ok());
+}
+
+#[test]
+fn raw_datasets_are_written_as_part_10_files() {
+    // No preamble, "DICM" prefix nor file meta information
+    let source = image().encode();
+
+    for assume_raw_dataset in [true, false].iter().copied() {
+        let output = Pipeline::new()
+            .post(TransferSyntax::ExplicitVRLittleEndian)
+            .assume_raw_dataset(assume_raw_dataset)
+            .run(source.clone())
+            .unwrap();
+
+        assert_eq!(&output[128..132], b"DICM");
+        assert_eq!(
+            common::string(&output, 0x0002, 0x0010).as_deref(),
+            Some(common::EXPLICIT_VR_LITTLE_ENDIAN)
+        );
+        assert_eq!(
+            common::element(&output, 0x7FE0, 0x0010).unwrap(),
+            common::gradient(64)
+        );
+    }
+}
+
+/// Pseudo-random bytes, the same on every run.
+fn garbage(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn malformed_sources_fail_with_an_error() {
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(sample())
+        .unwrap();
+
+    let mut sources = Vec::new();
+    for seed in 0..8 {
+        sources.push(garbage(1024, seed));
+
+        // Garbage behind a valid preamble
+        let mut prefixed = vec![0; 128];
+        prefixed.extend_from_slice(b"DICM");
+        prefixed.extend(garbage(1024, seed));
+        sources.push(prefixed);
+    }
+    // Cut before the last fragment ends, GDCM accepts a missing sequence delimiter
+    for len in (132..encoded.len() - 16).step_by(97) {
+        sources.push(encoded[..len].to_vec());
+    }
+
+    let pipeline = Pipeline::new().post(TransferSyntax::ExplicitVRLittleEndian);
+    for source in sources {
+        assert!(pipeline.check(&source).is_err(), "{}", source.len());
+        assert!(pipeline.run(source).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{sample, Fixture};
+use gdcm_conv::{Error, GDCMError, Preset, TransferSyntax};
+
+/// 4 frames of MPEG-4 AVC/H.264 in a single fragment, the stream is never decoded.
+fn video(transfer_syntax: TransferSyntax) -> Vec<u8> {
+    let stream: Vec<u8> = (0..64).collect();
+    let mut fixture = Fixture::image(16, 16, 3, 8, "YBR_PARTIAL_420")
+        .frames(4)
+        .pixels(common::encapsulate(&[stream]))
+        .build_with_syntax(transfer_syntax.to_uid());
+    common::close_encapsulation(&mut fixture);
+    fixture
+}
+
+#[test]
+fn web_viewer_j2k_round_trip() {
+    let source = sample();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+
+    let output = gdcm_conv::convert_preset(source, Preset::WebViewerJ2k).unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::JPEG2000Lossless
+    );
+    assert_eq!(common::offsets(&common::fragments(&output)[0]), vec![0]);
+    assert_eq!(common::decoded_pixels(output), pixels);
+}
+
+#[test]
+fn archive_lossless_jpegls_round_trip() {
+    let source = sample();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+
+    let output = gdcm_conv::convert_preset(source, Preset::ArchiveLosslessJpegLs).unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::JPEGLSLossless
+    );
+    assert_eq!(common::decoded_pixels(output), pixels);
+}
+
+#[test]
+fn legacy_pacs_explicit_le_round_trip() {
+    let source = sample();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+    let compressed = gdcm_conv::convert_preset(source, Preset::WebViewerJ2k).unwrap();
+
+    let output = gdcm_conv::convert_preset(compressed, Preset::LegacyPacsExplicitLE).unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::ExplicitVRLittleEndian
+    );
+    assert_eq!(common::element(&output, 0x7FE0, 0x0010).unwrap(), pixels);
+}
+
+#[test]
+fn video_mpeg4_keeps_the_stream() {
+    let source = video(TransferSyntax::MPEG4AVCH264HP41);
+
+    let output = gdcm_conv::convert_preset(source.clone(), Preset::VideoMpeg4).unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::MPEG4AVCH264HP41
+    );
+    assert_eq!(common::fragments(&output), common::fragments(&source));
+    assert_eq!(gdcm_conv::frame_count(&output).unwrap(), 4);
+}
+
+#[test]
+fn video_mpeg4_rejects_other_sources() {
+    for source in [video(TransferSyntax::MPEG4AVCH264BDCompat), sample()] {
+        let result = gdcm_conv::convert_preset(source, Preset::VideoMpeg4);
+
+        assert!(
+            matches!(result, Err(GDCMError::Post(Error::IncompatibleEncoding))),
+            "{:?}",
+            result
+        );
+    }
+}
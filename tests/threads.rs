@@ -0,0 +1,30 @@
+mod common;
+
+use common::sample;
+use gdcm_conv::{Pipeline, TransferSyntax};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn concurrent_conversions_match_the_baseline() {
+    let pipeline = Pipeline::new().post(TransferSyntax::JPEGLSLossless);
+    let source = Arc::new(sample());
+    let baseline = Arc::new(pipeline.run(source.to_vec()).unwrap());
+
+    let workers: Vec<_> = (0..16)
+        .map(|_| {
+            let (pipeline, source, baseline) =
+                (pipeline.clone(), Arc::clone(&source), Arc::clone(&baseline));
+            thread::spawn(move || {
+                for _ in 0..4 {
+                    let output = pipeline.run(source.to_vec()).unwrap();
+                    assert!(output == *baseline, "output differs from the baseline");
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
@@ -0,0 +1,693 @@
+mod common;
+
+use common::{sample, Fixture};
+use gdcm_conv::{
+    Error, FrameTranscoder, GDCMError, J2kOptions, J2kProgressionOrder, JpegLsInterleaveMode,
+    JpegLsOptions, PhotometricInterpretation, Pipeline, TransferSyntax,
+};
+
+const HTJ2K: [TransferSyntax; 3] = [
+    TransferSyntax::HTJ2KLossless,
+    TransferSyntax::HTJ2KLosslessRPCL,
+    TransferSyntax::HTJ2K(0),
+];
+
+const VIDEO: [TransferSyntax; 4] = [
+    TransferSyntax::MPEG2MainProfileMainLevel,
+    TransferSyntax::MPEG4AVCH264HP41,
+    TransferSyntax::MPEG4AVCH264BDCompat,
+    TransferSyntax::JPIPReferenced,
+];
+
+fn multi_frame_rgb() -> Vec<u8> {
+    Fixture::image(16, 16, 3, 8, "RGB")
+        .frames(4)
+        .pixels(common::rgb_frames(16, 16, 4))
+        .build()
+}
+
+#[test]
+fn htj2k_targets_are_decode_only() {
+    let source = sample();
+
+    for t in HTJ2K.iter().copied() {
+        assert!(t.is_decode_only(), "{}", t);
+        assert!(!t.is_retired(), "{}", t);
+
+        let pre = gdcm_conv::pipeline(
+            source.clone(),
+            None,
+            t,
+            PhotometricInterpretation::None,
+            TransferSyntax::None,
+        );
+        assert!(
+            matches!(pre, Err(GDCMError::Pre(Error::UnsupportedTarget))),
+            "{}: {:?}",
+            t,
+            pre.map(|t| t.len())
+        );
+
+        let post = Pipeline::new().post(t).check(&source);
+        assert!(
+            matches!(post, Err(GDCMError::Post(Error::UnsupportedTarget))),
+            "{}: {:?}",
+            t,
+            post
+        );
+
+        assert!(matches!(
+            FrameTranscoder::new(&source, t),
+            Err(GDCMError::Post(Error::UnsupportedTarget))
+        ));
+    }
+}
+
+#[test]
+fn htj2k_is_not_listed_as_supported() {
+    let supported = gdcm_conv::supported_transfer_syntaxes();
+
+    for t in HTJ2K.iter() {
+        assert!(!supported.contains(t), "{}", t);
+    }
+    assert!(supported.contains(&TransferSyntax::JPEG2000Lossless));
+}
+
+#[test]
+fn htj2k_quality_is_bounded() {
+    assert!(TransferSyntax::HTJ2K(0).validate_params().is_ok());
+    assert!(TransferSyntax::HTJ2K(100).validate_params().is_ok());
+    assert!(matches!(
+        TransferSyntax::HTJ2K(101).validate_params(),
+        Err(Error::InvalidQuality)
+    ));
+    assert!(matches!(
+        TransferSyntax::HTJ2K(u32::MAX).validate_params(),
+        Err(Error::InvalidQuality)
+    ));
+}
+
+#[test]
+fn htj2k_uids_are_recognized() {
+    for t in HTJ2K.iter().copied() {
+        assert_eq!(TransferSyntax::from_uid(t.to_uid()), Some(t));
+    }
+
+    // A source declaring HTJ2K is classified, even if it can not be produced
+    let source = Fixture::new().build_with_syntax(TransferSyntax::HTJ2KLossless.to_uid());
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&source).unwrap(),
+        TransferSyntax::HTJ2KLossless
+    );
+}
+
+#[test]
+fn video_targets_are_decode_only() {
+    let source = multi_frame_rgb();
+    let supported = gdcm_conv::supported_transfer_syntaxes();
+
+    for t in VIDEO.iter().copied() {
+        assert!(t.is_decode_only(), "{}", t);
+        assert!(!supported.contains(&t), "{}", t);
+
+        let pre = Pipeline::new().pre(t).check(&source);
+        assert!(
+            matches!(pre, Err(GDCMError::Pre(Error::UnsupportedTarget))),
+            "{}: {:?}",
+            t,
+            pre
+        );
+
+        let post = Pipeline::new().post(t).check(&source);
+        assert!(
+            matches!(post, Err(GDCMError::Post(Error::UnsupportedTarget))),
+            "{}: {:?}",
+            t,
+            post
+        );
+
+        let run = Pipeline::new().post(t).run(source.clone());
+        assert!(
+            matches!(run, Err(GDCMError::Post(Error::UnsupportedTarget))),
+            "{}",
+            t
+        );
+    }
+}
+
+#[test]
+fn video_sources_keep_their_frames() {
+    for t in VIDEO[..3].iter().copied() {
+        let mut fixture = Fixture::image(16, 16, 3, 8, "YBR_PARTIAL_420")
+            .frames(4)
+            .pixels(common::encapsulate(&[vec![0; 64]]))
+            .build_with_syntax(t.to_uid());
+        common::close_encapsulation(&mut fixture);
+
+        assert_eq!(gdcm_conv::read_transfer_syntax(&fixture).unwrap(), t);
+        assert_eq!(gdcm_conv::frame_count(&fixture).unwrap(), 4, "{}", t);
+    }
+
+    // A multi-frame source stays multi-frame on the way to an encodable target
+    let source = multi_frame_rgb();
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::RLELossless)
+        .run(source)
+        .unwrap();
+    assert_eq!(gdcm_conv::frame_count(&encoded).unwrap(), 4);
+}
+
+#[test]
+fn max_frames_truncates_the_output() {
+    let pixels = common::rgb_frames(16, 16, 4);
+    let frame_length = pixels.len() / 4;
+
+    for t in [
+        TransferSyntax::ExplicitVRLittleEndian,
+        TransferSyntax::RLELossless,
+    ]
+    .iter()
+    .copied()
+    {
+        let output = Pipeline::new()
+            .post(t)
+            .max_frames(2)
+            .run(multi_frame_rgb())
+            .unwrap();
+
+        assert_eq!(gdcm_conv::frame_count(&output).unwrap(), 2, "{}", t);
+        assert_eq!(
+            common::decoded_pixels(output),
+            pixels[..2 * frame_length],
+            "{}",
+            t
+        );
+    }
+
+    // Fewer frames than the limit, the source keeps all of them
+    let output = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .max_frames(8)
+        .run(multi_frame_rgb())
+        .unwrap();
+    assert_eq!(gdcm_conv::frame_count(&output).unwrap(), 4);
+}
+
+#[test]
+fn jpeg2000_lossless_round_trip_is_exact() {
+    // The encodable counterpart of the HTJ2K round trip
+    let source = sample();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(source)
+        .unwrap();
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&encoded).unwrap(),
+        TransferSyntax::JPEG2000Lossless
+    );
+    assert_eq!(common::decoded_pixels(encoded), pixels);
+}
+
+#[test]
+fn deflated_output_is_smaller_and_lossless() {
+    let source = sample();
+
+    let explicit = Pipeline::new()
+        .post(TransferSyntax::ExplicitVRLittleEndian)
+        .run(source.clone())
+        .unwrap();
+    let deflated = Pipeline::new()
+        .post(TransferSyntax::DeflatedExplicitVRLittleEndian)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&deflated).unwrap(),
+        TransferSyntax::DeflatedExplicitVRLittleEndian
+    );
+    assert!(
+        deflated.len() < explicit.len(),
+        "{} >= {}",
+        deflated.len(),
+        explicit.len()
+    );
+    assert_eq!(
+        common::decoded_pixels(deflated),
+        common::element(&explicit, 0x7FE0, 0x0010).unwrap()
+    );
+}
+
+#[test]
+fn uids_round_trip() {
+    let plain = [
+        TransferSyntax::ImplicitVRLittleEndian,
+        TransferSyntax::ExplicitVRLittleEndian,
+        TransferSyntax::DeflatedExplicitVRLittleEndian,
+        TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian,
+        TransferSyntax::ExplicitVRBigEndian,
+        TransferSyntax::RLELossless,
+        TransferSyntax::JPEGExtendedProcess3_5,
+        TransferSyntax::JPEGSpectralSelectionProcess6_8,
+        TransferSyntax::JPEGFullProgressionProcess10_12,
+        TransferSyntax::JPEGLosslessProcess14,
+        TransferSyntax::JPEGLosslessProcess14_1,
+        TransferSyntax::JPEGLSLossless,
+        TransferSyntax::JPEG2000Lossless,
+        TransferSyntax::JPEG2000Part2Lossless,
+        TransferSyntax::JPIPReferenced,
+        TransferSyntax::MPEG2MainProfileMainLevel,
+        TransferSyntax::HTJ2KLossless,
+        TransferSyntax::HTJ2KLosslessRPCL,
+        TransferSyntax::MPEG4AVCH264HP41,
+        TransferSyntax::MPEG4AVCH264BDCompat,
+    ];
+
+    for t in plain.iter().copied() {
+        assert!(t.param_spec().is_empty(), "{}", t);
+        assert_eq!(TransferSyntax::from_uid(t.to_uid()), Some(t), "{}", t);
+    }
+
+    // Parameterized variants come back with zeroed parameters
+    assert_eq!(
+        TransferSyntax::from_uid(TransferSyntax::JPEGBaselineProcess1(90).to_uid()),
+        Some(TransferSyntax::JPEGBaselineProcess1(0))
+    );
+    assert_eq!(
+        TransferSyntax::from_uid(TransferSyntax::JPEG2000(50, 40, 30, true).to_uid()),
+        Some(TransferSyntax::JPEG2000(0, 0, 0, false))
+    );
+
+    assert_eq!(TransferSyntax::None.to_uid(), "");
+    assert_eq!(TransferSyntax::from_uid(""), None);
+    assert_eq!(TransferSyntax::from_uid("1.2.840.10008.1.2.4.999"), None);
+}
+
+/// Interleave mode (ILV) of the first scan header of a JPEG-LS codestream.
+fn jpegls_interleave(codestream: &[u8]) -> u8 {
+    let sos = codestream
+        .windows(2)
+        .position(|t| t == [0xFF, 0xDA])
+        .expect("no SOS marker");
+    let components = codestream[sos + 4] as usize;
+    codestream[sos + 5 + components * 2 + 1]
+}
+
+/// T1, T2, T3 and RESET of the preset coding parameters (LSE id 1) of a JPEG-LS codestream.
+fn jpegls_preset(codestream: &[u8]) -> Option<[u16; 4]> {
+    let lse = codestream
+        .windows(3)
+        .position(|t| t == [0xFF, 0xF8, 0x00])?;
+    let value = |i: usize| u16::from_be_bytes([codestream[lse + i], codestream[lse + i + 1]]);
+    if codestream[lse + 4] != 1 {
+        return None;
+    }
+    Some([value(7), value(9), value(11), value(13)])
+}
+
+#[test]
+fn jpegls_options_are_encoded_and_decode() {
+    let source = multi_frame_rgb();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+
+    let modes = [
+        (JpegLsInterleaveMode::None, 0),
+        (JpegLsInterleaveMode::Line, 1),
+        (JpegLsInterleaveMode::Sample, 2),
+    ];
+    for (mode, ilv) in modes.iter().copied() {
+        let options = JpegLsOptions {
+            interleave_mode: Some(mode),
+            t1: Some(4),
+            t2: Some(8),
+            t3: Some(22),
+            reset: Some(32),
+        };
+        let encoded = Pipeline::new()
+            .post(TransferSyntax::JPEGLSLossless)
+            .jpegls(options)
+            .run(source.clone())
+            .unwrap();
+
+        assert_eq!(
+            gdcm_conv::read_transfer_syntax(&encoded).unwrap(),
+            TransferSyntax::JPEGLSLossless
+        );
+        let fragments = common::fragments(&encoded);
+        assert_eq!(fragments.len(), 1 + 4, "{:?}", mode);
+        for codestream in &fragments[1..] {
+            assert_eq!(jpegls_interleave(codestream), ilv, "{:?}", mode);
+            assert_eq!(
+                jpegls_preset(codestream),
+                Some([4, 8, 22, 32]),
+                "{:?}",
+                mode
+            );
+        }
+        // Separated scans may decode to separated planes
+        let decoded = Pipeline::new()
+            .photometric(PhotometricInterpretation::RGB)
+            .post(TransferSyntax::ExplicitVRLittleEndian)
+            .planar_configuration(0)
+            .run(encoded)
+            .unwrap();
+        assert_eq!(
+            common::element(&decoded, 0x7FE0, 0x0010).unwrap(),
+            pixels,
+            "{:?}",
+            mode
+        );
+    }
+}
+
+#[test]
+fn jpegls_invalid_thresholds_fail() {
+    let options = JpegLsOptions {
+        t1: Some(20),
+        t2: Some(10),
+        ..JpegLsOptions::default()
+    };
+    let result = Pipeline::new()
+        .post(TransferSyntax::JPEGLSLossless)
+        .jpegls(options)
+        .run(multi_frame_rgb());
+
+    assert!(matches!(result, Err(GDCMError::Post(Error::ExecuteChange))));
+}
+
+/// COD marker segment of a JPEG 2000 codestream, from the marker on.
+fn j2k_cod(codestream: &[u8]) -> &[u8] {
+    let cod = codestream
+        .windows(2)
+        .position(|t| t == [0xFF, 0x52])
+        .expect("no COD marker");
+    &codestream[cod..]
+}
+
+/// Number of decomposition levels of the COD marker of a JPEG 2000 codestream.
+fn j2k_decomposition_levels(codestream: &[u8]) -> u8 {
+    j2k_cod(codestream)[9]
+}
+
+#[test]
+fn j2k_resolutions_are_encoded() {
+    let source = sample();
+    let pixels = common::element(&source, 0x7FE0, 0x0010).unwrap();
+
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .j2k(J2kOptions {
+            resolutions: Some(6),
+            ..J2kOptions::default()
+        })
+        .run(source)
+        .unwrap();
+
+    let fragments = common::fragments(&encoded);
+    assert_eq!(j2k_decomposition_levels(&fragments[1]), 5);
+    assert_eq!(common::decoded_pixels(encoded), pixels);
+}
+
+#[test]
+fn j2k_progression_order_and_codeblocks_are_encoded() {
+    let source = multi_frame_rgb();
+    let pixels = common::rgb_frames(16, 16, 4);
+
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .j2k(J2kOptions {
+            codeblock_size: Some((32, 16)),
+            progression_order: Some(J2kProgressionOrder::RPCL),
+            ..J2kOptions::default()
+        })
+        .run(source)
+        .unwrap();
+
+    // Progression order, then codeblock width and height exponents minus 2
+    let fragments = common::fragments(&encoded);
+    assert_eq!(fragments.len(), 1 + 4);
+    for codestream in &fragments[1..] {
+        let cod = j2k_cod(codestream);
+        assert_eq!(cod[5], J2kProgressionOrder::RPCL.to_id() as u8);
+        assert_eq!((cod[10], cod[11]), (3, 2));
+    }
+    assert_eq!(common::decoded_pixels(encoded), pixels);
+}
+
+#[test]
+fn j2k_invalid_codeblock_size_fails() {
+    let result = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .j2k(J2kOptions {
+            codeblock_size: Some((128, 128)),
+            ..J2kOptions::default()
+        })
+        .run(sample());
+
+    assert!(matches!(result, Err(GDCMError::Post(Error::ExecuteChange))));
+}
+
+#[test]
+fn skip_if_same_applies_j2k_options() {
+    let source = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(sample())
+        .unwrap();
+    let levels = j2k_decomposition_levels(&common::fragments(&source)[1]);
+
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .skip_if_same(true)
+        .j2k(J2kOptions {
+            resolutions: Some(levels as u32),
+            ..J2kOptions::default()
+        })
+        .run(source.clone())
+        .unwrap();
+
+    assert_ne!(encoded, source);
+    assert_eq!(
+        j2k_decomposition_levels(&common::fragments(&encoded)[1]),
+        levels - 1
+    );
+}
+
+#[test]
+fn rle_multi_frame_rgb_round_trip_is_exact() {
+    let interleaved = common::rgb_frames(16, 16, 4);
+
+    // Separated planes, frame by frame
+    let mut planar = Vec::with_capacity(interleaved.len());
+    for frame in interleaved.chunks(16 * 16 * 3) {
+        for k in 0..3 {
+            planar.extend(frame.iter().skip(k).step_by(3));
+        }
+    }
+
+    for planar_configuration in 0..2 {
+        let pixels = [&interleaved, &planar];
+        let source = Fixture::image(16, 16, 3, 8, "RGB")
+            .us(0x0028, 0x0006, planar_configuration)
+            .frames(4)
+            .pixels(pixels[planar_configuration as usize].clone())
+            .build();
+
+        let encoded = Pipeline::new()
+            .post(TransferSyntax::RLELossless)
+            .run(source)
+            .unwrap();
+        assert_eq!(common::fragments(&encoded).len(), 1 + 4);
+
+        let decoded = Pipeline::new()
+            .post(TransferSyntax::ExplicitVRLittleEndian)
+            .run(encoded)
+            .unwrap();
+        assert_eq!(gdcm_conv::frame_count(&decoded).unwrap(), 4);
+
+        // Compared in the planar configuration of the decoded file
+        let decoded_planar = common::us(&decoded, 0x0028, 0x0006).unwrap();
+        assert_eq!(
+            &common::element(&decoded, 0x7FE0, 0x0010).unwrap(),
+            pixels[decoded_planar as usize],
+            "planar configuration {}",
+            planar_configuration
+        );
+    }
+}
+
+#[test]
+fn encapsulated_uncompressed_keeps_one_fragment_per_frame() {
+    let source = multi_frame_rgb();
+    let pixels = common::rgb_frames(16, 16, 4);
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::EncapsulatedUncompressedExplicitVRLittleEndian)
+        .run(source)
+        .unwrap();
+
+    assert_eq!(
+        common::string(&output, 0x0002, 0x0010).unwrap(),
+        "1.2.840.10008.1.2.1.98"
+    );
+
+    // Basic Offset Table, then the native frames as they are
+    let fragments = common::fragments(&output);
+    assert_eq!(fragments.len(), 5);
+    for (fragment, frame) in fragments[1..].iter().zip(pixels.chunks(16 * 16 * 3)) {
+        assert_eq!(fragment.as_slice(), frame);
+    }
+    assert_eq!(common::decoded_pixels(output), pixels);
+}
+
+#[test]
+fn skip_if_same_applies_the_extended_offset_table() {
+    let source = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(multi_frame_rgb())
+        .unwrap();
+    assert!(common::element(&source, 0x7FE0, 0x0001).is_none());
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .skip_if_same(true)
+        .force_extended_offset_table(true)
+        .run(source.clone())
+        .unwrap();
+
+    assert_ne!(output, source);
+    let offsets = common::element(&output, 0x7FE0, 0x0001).unwrap();
+    let lengths = common::element(&output, 0x7FE0, 0x0002).unwrap();
+    assert_eq!(offsets.len(), 4 * 8);
+    assert_eq!(lengths.len(), 4 * 8);
+    assert!(common::fragments(&output)[0].is_empty());
+}
+
+#[test]
+fn ybr_jpeg_baseline_to_j2k_keeps_the_colors() {
+    // Pure red in YBR_FULL
+    let ybr = [76u8, 85, 255].repeat(16 * 16);
+    let source = Pipeline::new()
+        .post(TransferSyntax::JPEGBaselineProcess1(95))
+        .run(Fixture::image(16, 16, 3, 8, "YBR_FULL").pixels(ybr).build())
+        .unwrap();
+    assert!(common::string(&source, 0x0028, 0x0004)
+        .unwrap()
+        .starts_with("YBR_FULL"));
+
+    let output = gdcm_conv::convert_ybr_jpeg_to_j2k(source).unwrap();
+
+    assert_eq!(
+        gdcm_conv::read_transfer_syntax(&output).unwrap(),
+        TransferSyntax::JPEG2000Lossless
+    );
+    assert_eq!(
+        common::string(&output, 0x0028, 0x0004).as_deref(),
+        Some("RGB")
+    );
+    for pixel in common::decoded_pixels(output).chunks(3) {
+        assert!(
+            pixel[0] >= 245 && pixel[1] <= 10 && pixel[2] <= 10,
+            "{:?}",
+            pixel
+        );
+    }
+}
+
+#[test]
+fn j2k_frames_are_single_fragments() {
+    let output = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(multi_frame_rgb())
+        .unwrap();
+
+    let fragments = common::fragments(&output);
+    let offsets = common::offsets(&fragments[0]);
+    assert_eq!(offsets.len(), 4);
+    assert_eq!(fragments.len(), 1 + 4);
+
+    // Each offset points at the item of its frame
+    let mut position = 0;
+    for (offset, fragment) in offsets.iter().zip(&fragments[1..]) {
+        assert_eq!(*offset as usize, position);
+        position += 8 + fragment.len();
+    }
+}
+
+#[test]
+fn rebuild_offset_table_repairs_a_zeroed_table() {
+    let encoded = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .run(multi_frame_rgb())
+        .unwrap();
+    let codestreams = common::fragments(&encoded)[1..].to_vec();
+
+    // Same fragments behind a Basic Offset Table of 4 zero offsets
+    let mut value = vec![0xFE, 0xFF, 0x00, 0xE0, 16, 0, 0, 0];
+    value.extend_from_slice(&[0; 16]);
+    value.extend_from_slice(&common::encapsulate(&codestreams)[8..]);
+    let mut source = Fixture::image(16, 16, 3, 8, "RGB")
+        .frames(4)
+        .bytes(0x7FE0, 0x0010, "OB", value)
+        .build_with_syntax(TransferSyntax::JPEG2000Lossless.to_uid());
+    common::close_encapsulation(&mut source);
+    assert_eq!(common::offsets(&common::fragments(&source)[0]), vec![0; 4]);
+
+    let output = Pipeline::new()
+        .post(TransferSyntax::JPEG2000Lossless)
+        .skip_if_same(true)
+        .rebuild_offset_table(true)
+        .run(source)
+        .unwrap();
+
+    // The fragments are kept, the offsets point at their items
+    let fragments = common::fragments(&output);
+    assert_eq!(&fragments[1..], codestreams.as_slice());
+    let mut position = 0;
+    let mut expected = Vec::new();
+    for fragment in &codestreams {
+        expected.push(position as u32);
+        position += 8 + fragment.len();
+    }
+    assert_eq!(common::offsets(&fragments[0]), expected);
+}
+
+#[test]
+fn retired_jpeg_sources_convert_to_j2k_lossless() {
+    let baseline = Pipeline::new()
+        .post(TransferSyntax::JPEGBaselineProcess1(95))
+        .run(
+            Fixture::image(16, 16, 1, 8, "MONOCHROME2")
+                .pixels(common::gradient(256))
+                .build(),
+        )
+        .unwrap();
+    let pixels = common::decoded_pixels(baseline.clone());
+    let uid = TransferSyntax::JPEGBaselineProcess1(0).to_uid().as_bytes();
+
+    let retired = [
+        TransferSyntax::JPEGExtendedProcess3_5,
+        TransferSyntax::JPEGSpectralSelectionProcess6_8,
+        TransferSyntax::JPEGFullProgressionProcess10_12,
+    ];
+    for t in retired.iter().copied() {
+        // The baseline stream declared with the retired UID, of the same length
+        let mut source = baseline.clone();
+        let pos = source.windows(uid.len()).position(|w| w == uid).unwrap();
+        source[pos..pos + uid.len()].copy_from_slice(t.to_uid().as_bytes());
+        assert_eq!(gdcm_conv::read_transfer_syntax(&source).unwrap(), t);
+
+        let output = Pipeline::new()
+            .post(TransferSyntax::JPEG2000Lossless)
+            .run(source)
+            .unwrap();
+
+        assert_eq!(
+            gdcm_conv::read_transfer_syntax(&output).unwrap(),
+            TransferSyntax::JPEG2000Lossless,
+            "{}",
+            t
+        );
+        assert_eq!(common::decoded_pixels(output), pixels, "{}", t);
+    }
+}
@@ -3,6 +3,89 @@ struct OutputStruct {
     size_t size;
 };
 
+struct OptionsStruct {
+    char anonymize;
+    char remove_private_tags;
+    const char *patient_name;
+    char regenerate_uids;
+    char regenerate_sop_instance_uid;
+    char dry_run;
+    void (*progress)(void *, unsigned int, unsigned int); // progress callback (data, current, total)
+    void *progress_data;
+    char (*cancelled)(void *); // cancellation callback (data)
+    void *cancel_data;
+    char apply_rescale;
+    char bake_voi_lut;
+    unsigned int voi_lut_index;
+    int overlays; // 0 preserve, 1 strip, 2 burn in
+    int icc_profile; // 0 preserve, 1 strip, 2 apply
+    unsigned int planar_configuration;
+    unsigned int target_bits;
+    char to_utf8;
+    const char *derivation_note;
+    unsigned int j2k_resolutions;
+    unsigned int j2k_tile_width;
+    unsigned int j2k_tile_height;
+    unsigned int j2k_codeblock_width; // 0 default
+    unsigned int j2k_codeblock_height;
+    int j2k_progression_order; // -1 default, 0 LRCP, 1 RLCP, 2 RPCL, 3 PCRL, 4 CPRL
+    int jpegls_interleave_mode; // -1 default, 0 none, 1 line, 2 sample
+    unsigned int jpegls_t1; // 0 default
+    unsigned int jpegls_t2;
+    unsigned int jpegls_t3;
+    unsigned int jpegls_reset;
+    char header_only;
+    char strip_private;
+    const char *const *keep_private_creators;
+    size_t keep_private_creators_len;
+    const char *implementation_class_uid;
+    const char *implementation_version_name;
+    char preserve_implementation;
+    char preserve_structure;
+    char force_extended_offset_table;
+    char preserve_spatial;
+    const char *pixel_hash; // SHA-256 of the decoded pixel data, hex
+    char strict;
+    char assume_raw_dataset;
+    char single_fragment_per_frame;
+    void (*warning)(void *, const char *); // warning callback (data, message)
+    void *warning_data;
+    char rebuild_offset_table;
+    unsigned int max_frames; // 0 keeps all frames
+    const unsigned int *tag_overrides; // (group << 16) | element
+    const char *const *tag_override_values;
+    size_t tag_overrides_len;
+    char trust_detected_photometric;
+};
+
+struct FrameStruct {
+    unsigned int status;
+    size_t size;
+    unsigned int rows;
+    unsigned int columns;
+    unsigned int number_of_frames;
+    unsigned int samples_per_pixel;
+    unsigned int bits_allocated;
+    unsigned int bits_stored;
+};
+
+struct PixelDataStruct {
+    unsigned int status;
+    size_t size;
+    char pixel_hash[65];
+};
+
+struct InfoStruct {
+    unsigned int status;
+    unsigned int rows;
+    unsigned int columns;
+    unsigned int number_of_frames;
+    unsigned int bits_allocated;
+    unsigned int bits_stored;
+    unsigned int samples_per_pixel;
+    char photometric_interpretation[17];
+};
+
 #ifdef __cplusplus
 extern "C" {
 #endif
@@ -17,6 +100,8 @@ extern "C" {
 #  define MODULE_API
 #endif
 
+MODULE_API void c_init();
+
 MODULE_API OutputStruct c_convert(
     char *,     // i_buffer_ptr
     size_t,     // i_buffer_len
@@ -29,7 +114,121 @@ MODULE_API OutputStruct c_convert(
     int,        // quality2
     int,        // quality3
     char,       // irreversible
-    int         // allow_error
+    int,        // allow_error
+    const OptionsStruct * // options
+);
+
+MODULE_API InfoStruct c_image_info(
+    char *,     // i_buffer_ptr
+    size_t      // i_buffer_len
+);
+
+MODULE_API OutputStruct c_read_tag(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned short, // group
+    unsigned short, // element
+    char *,         // value_ptr
+    size_t          // max_size
+);
+
+MODULE_API FrameStruct c_extract_frame(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned int,   // frame_index
+    char *,         // o_buffer_ptr
+    size_t          // max_size
+);
+
+MODULE_API FrameStruct c_transcode_frame(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned int,   // frame_index
+    int,            // transfer_syntax
+    char *,         // o_buffer_ptr
+    size_t          // max_size
+);
+
+MODULE_API FrameStruct c_split_frames(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    char *,         // o_buffer_ptr
+    size_t,         // max_size
+    size_t *,       // frame_sizes
+    size_t          // frame_sizes_len
+);
+
+MODULE_API OutputStruct c_combine_frames(
+    const char *const *, // frame_ptrs
+    const size_t *,      // frame_lens
+    size_t,              // frame_count
+    char *,              // o_buffer_ptr
+    size_t               // max_size
+);
+
+MODULE_API PixelDataStruct c_pixel_data(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    char *,         // o_buffer_ptr
+    size_t          // max_size
+);
+
+MODULE_API OutputStruct c_thumbnail(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned int,   // max_dim
+    int,            // quality
+    char *,         // o_buffer_ptr
+    size_t          // max_size
+);
+
+MODULE_API FrameStruct c_render_rgb(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned int,   // frame_index
+    char,           // has_window
+    double,         // window_center
+    double,         // window_width
+    char *,         // o_buffer_ptr
+    size_t          // max_size
+);
+
+MODULE_API const char *c_gdcm_version();
+
+MODULE_API char c_supported_transfer_syntax(
+    int         // transfer_syntax
+);
+
+MODULE_API unsigned int c_transfer_syntax(
+    char *,     // i_buffer_ptr
+    size_t,     // i_buffer_len
+    char *,     // uid_ptr
+    size_t      // uid_len
+);
+
+MODULE_API unsigned int c_sop_class_uid(
+    char *,     // i_buffer_ptr
+    size_t,     // i_buffer_len
+    char *,     // uid_ptr
+    size_t      // uid_len
+);
+
+MODULE_API unsigned int c_pixel_data_length(
+    char *,     // i_buffer_ptr
+    size_t,     // i_buffer_len
+    size_t *    // pixel_data_len
+);
+
+MODULE_API unsigned int c_detect_photometric(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    int *           // photometric_interpretation
+);
+
+MODULE_API unsigned int c_frame_count(
+    char *,         // i_buffer_ptr
+    size_t,         // i_buffer_len
+    unsigned int *  // number_of_frames
 );
 
 #ifdef __cplusplus